@@ -0,0 +1,51 @@
+use zub::ir::ExprNode;
+
+use super::lexer::*;
+use super::parser::*;
+use super::source::*;
+use super::visitor::*;
+
+// what every stage made of one source, for looking at how a program gets compiled
+pub struct Compilation {
+    tokens: Vec<Token>,
+    ast: Vec<Statement>,
+    ir: Vec<ExprNode>,
+}
+
+impl Compilation {
+    // lexes, parses and visits the source with the given globals, errors are reported by the stage that failed
+    pub fn new(source: &Source, globals: &[(&str, TypeNode)]) -> Result<Self, ()> {
+        let mut content = source.lines.join("\n");
+        content.push('\n');
+
+        let tokens = Lexer::default(content.chars().collect(), source).collect::<Result<Vec<Token>, ()>>()?;
+
+        let ast = Parser::new(tokens.clone(), source).parse()?;
+
+        let mut visitor = Visitor::new(source);
+
+        for (name, t) in globals.iter() {
+            visitor.set_global(name, t.clone())
+        }
+
+        visitor.visit(&ast)?;
+
+        Ok(Compilation {
+            tokens,
+            ast,
+            ir: visitor.build(),
+        })
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    pub fn ast(&self) -> &[Statement] {
+        &self.ast
+    }
+
+    pub fn ir(&self) -> &[ExprNode] {
+        &self.ir
+    }
+}
@@ -1,5 +1,8 @@
 use colored::Colorize;
+use std::cell::RefCell;
 use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
 
 pub enum Response<T: fmt::Display> {
     Wrong(T),
@@ -9,13 +12,56 @@ pub enum Response<T: fmt::Display> {
 
 use self::Response::*;
 
+thread_local! {
+    static SINK: RefCell<Box<dyn Write>> = RefCell::new(Box::new(io::stdout()));
+}
+
+// redirects everything `response!` writes on this thread, handing back the previous sink
+pub fn set_sink(sink: Box<dyn Write>) -> Box<dyn Write> {
+    SINK.with(|s| s.replace(sink))
+}
+
+pub fn write_response(args: fmt::Arguments) {
+    SINK.with(|s| {
+        let _ = s.borrow_mut().write_fmt(args);
+    })
+}
+
+// everything `response!` wrote while `f` ran, instead of where it would have gone
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, String) {
+    let written = Rc::new(RefCell::new(Vec::new()));
+    let previous = set_sink(Box::new(Captured(written.clone())));
+
+    let result = f();
+
+    set_sink(previous);
+
+    let text = String::from_utf8_lossy(&written.borrow()).into_owned();
+
+    (result, text)
+}
+
+struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 #[macro_export]
 macro_rules! response {
   ( $( $r:expr ),+ ) => {{
     $(
-        print!("{}", $r);
+        $crate::hugorm::error::write_response(format_args!("{}", $r));
     )*
-    println!();
+    $crate::hugorm::error::write_response(format_args!("\n"));
   }};
 }
 
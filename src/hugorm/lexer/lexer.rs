@@ -4,6 +4,15 @@ use super::super::error::Response::Wrong;
 
 use std::rc::Rc;
 
+// every lexeme that comes out as an operator token, longer ones first so `++` doesn't lex as two `+`
+pub const OPERATORS: &[&str] = &[
+    "^", "++", "+%", "-%", "*%", "+", "-", "*", "/", "%", "==", "!=", "<=", ">=", "<", ">",
+];
+
+pub const WORD_OPERATORS: &[&str] = &[
+    "or", "and", "not", "is",
+];
+
 pub struct Lexer<'l> {
     tokenizer: Tokenizer<'l>,
     matchers: Vec<Rc<Matcher<'l>>>,
@@ -19,6 +28,22 @@ impl<'l> Lexer<'l> {
         }
     }
 
+    // for text that starts partway into a line of the source, like the expressions of an interpolated string
+    pub fn starting_at(mut self, pos: (usize, usize)) -> Self {
+        self.tokenizer.pos = pos;
+        self
+    }
+
+    // reads the operators a host registered, see `Visitor::lexer`
+    pub(crate) fn with_operators(mut self, operators: &[(String, u8)]) -> Self {
+        // after both comment matchers, an operator can't take `#` or `/*` away from comments
+        if !operators.is_empty() {
+            self.matchers.insert(2, Rc::new(CustomOperatorMatcher::new(operators)))
+        }
+
+        self
+    }
+
     pub fn default(data: Vec<char>, source: &'l Source) -> Self {
         use self::TokenType::*;
 
@@ -26,9 +51,12 @@ impl<'l> Lexer<'l> {
         let mut lexer = Self::new(tokenizer, source);
 
         lexer.matchers.push(Rc::new(CommentMatcher));
+        lexer.matchers.push(Rc::new(BlockCommentMatcher));
 
         lexer.matchers.push(Rc::new(EOLMatcher));
+        lexer.matchers.push(Rc::new(MultilineStringMatcher));
         lexer.matchers.push(Rc::new(StringLiteralMatcher));
+        lexer.matchers.push(Rc::new(CharLiteralMatcher));
 
         lexer.matchers.push(Rc::new(KeyMatcher::new(
             Keyword,
@@ -47,7 +75,14 @@ impl<'l> Lexer<'l> {
                 "break",
                 "loop",
                 "repeat",
-                "unless"
+                "unless",
+                "when",
+                "yield",
+                "for",
+                "in",
+                "do",
+                "match",
+                "case"
             ],
         )));
 
@@ -57,31 +92,21 @@ impl<'l> Lexer<'l> {
 
         lexer
             .matchers
-            .push(Rc::new(ConstantStringMatcher::new(Symbol, &["...", ".."])));
+            .push(Rc::new(ConstantStringMatcher::new(Symbol, &["...", "..=", "..", "&&=", "||="])));
 
         lexer.matchers.push(Rc::new(NumberLiteralMatcher));
         lexer.matchers.push(Rc::new(WhitespaceMatcher));
 
-        lexer.matchers.push(Rc::new(ConstantStringMatcher::new(
-            Operator,
-            &[
-                "^", "++", "+", "-", "*", "/", "%", "==", "!=", "<=", ">=", "<", ">", 
-            ],
-        )));
+        lexer.matchers.push(Rc::new(ConstantStringMatcher::new(Operator, OPERATORS)));
 
-        lexer.matchers.push(Rc::new(KeyMatcher::new(
-            Operator,
-            &[
-                "or", "and", "not",
-            ]
-        )));
+        lexer.matchers.push(Rc::new(KeyMatcher::new(Operator, WORD_OPERATORS)));
 
         lexer.matchers.push(Rc::new(IdentifierMatcher));
 
         lexer.matchers.push(Rc::new(ConstantCharMatcher::new(
             Symbol,
             &[
-                '?', '!', '(', ')', '[', ']', '{', '}', ',', ':', ';', '=', '.', '|',
+                '?', '!', '(', ')', '[', ']', '{', '}', ',', ':', ';', '=', '.', '|', '@',
             ],
         )));
 
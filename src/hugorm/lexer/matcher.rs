@@ -1,6 +1,8 @@
 use super::super::error::Response::*;
 use super::*;
 
+use std::num::IntErrorKind;
+
 macro_rules! token {
     ($tokenizer:expr, $token_type:ident, $accum:expr) => {{
         token!($tokenizer, TokenType::$token_type, $accum)
@@ -41,22 +43,89 @@ pub trait Matcher<'t> {
     fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token>, ()>;
 }
 
+// `#` to the end of the line, lexed as whitespace so the parser never sees it,
+// the newline is left for `EOLMatcher` like after any other line
 pub struct CommentMatcher;
 
 impl<'t> Matcher<'t> for CommentMatcher {
     fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token>, ()> {
-        if tokenizer.peek_range(1).unwrap_or_else(String::new) == "#" {
-            while !tokenizer.end() && tokenizer.peek() != Some('\n') {
-                tokenizer.advance()
-            }
+        if tokenizer.peek() == Some('#') {
+            let comment = tokenizer.collect_while(|c| c != '\n');
 
-            Ok(Some(token!(tokenizer, EOL, "\n".into())))
+            Ok(Some(token!(tokenizer, Whitespace, comment)))
         } else {
             Ok(None)
         }
     }
 }
 
+// `/* ... */`, nesting so a commented out block can hold comments of its own, lexed as whitespace
+// like `#` comments, with the lines it spans counted so what follows keeps its position
+pub struct BlockCommentMatcher;
+
+impl<'t> Matcher<'t> for BlockCommentMatcher {
+    fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token>, ()> {
+        if tokenizer.peek_range(2).map_or(true, |s| s != "/*") {
+            return Ok(None)
+        }
+
+        let pos = tokenizer.pos;
+
+        let mut comment = String::new();
+        let mut depth = 0;
+
+        loop {
+            if tokenizer.end() {
+                return Err(response!(
+                    Wrong("unterminated block comment"),
+                    tokenizer.source.file,
+                    Pos(
+                        (
+                            pos.0,
+                            tokenizer
+                                .source
+                                .lines
+                                .get(pos.0.saturating_sub(1))
+                                .unwrap_or(tokenizer.source.lines.last().unwrap())
+                                .to_string()
+                        ),
+                        (pos.1 + 1, pos.1 + 2),
+                    )
+                ));
+            }
+
+            let delimiter = tokenizer.peek_range(2);
+
+            if let Some(delimiter) = delimiter.filter(|d| d == "/*" || d == "*/") {
+                depth = if delimiter == "/*" { depth + 1 } else { depth - 1 };
+
+                tokenizer.advance_n(2);
+                comment.push_str(&delimiter);
+
+                if depth == 0 {
+                    break
+                }
+
+                continue
+            }
+
+            let c = tokenizer.items[tokenizer.index];
+
+            if c == '\n' {
+                tokenizer.pos.0 += 1;
+                tokenizer.pos.1 = 0;
+                tokenizer.index += 1
+            } else {
+                tokenizer.advance()
+            }
+
+            comment.push(c)
+        }
+
+        Ok(Some(token!(tokenizer, Whitespace, comment)))
+    }
+}
+
 pub struct ConstantStringMatcher {
     token_type: TokenType,
     constants: &'static [&'static str],
@@ -75,9 +144,10 @@ impl<'t> Matcher<'t> for ConstantStringMatcher {
     fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token>, ()> {
         for constant in self.constants {
             let len = constant.len();
+            // a longer constant running past the end doesn't rule out the shorter ones
             let c = match tokenizer.peek_range(len) {
                 Some(len) => len,
-                _ => return Ok(None),
+                _ => continue,
             };
 
             if c == *constant {
@@ -98,6 +168,37 @@ impl<'t> Matcher<'t> for ConstantStringMatcher {
     }
 }
 
+// the operators a host registered, tried before the built in ones so `<>` isn't read as `<` and `>`
+pub struct CustomOperatorMatcher {
+    operators: Vec<String>,
+}
+
+impl CustomOperatorMatcher {
+    pub fn new(operators: &[(String, u8)]) -> Self {
+        let mut operators = operators.iter().map(|(symbol, _)| symbol.clone()).collect::<Vec<String>>();
+
+        operators.sort_by(|a, b| b.len().cmp(&a.len()));
+
+        CustomOperatorMatcher {
+            operators,
+        }
+    }
+}
+
+impl<'t> Matcher<'t> for CustomOperatorMatcher {
+    fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token>, ()> {
+        for operator in self.operators.iter() {
+            if tokenizer.peek_range(operator.len()).map_or(false, |s| s == *operator) {
+                tokenizer.advance_n(operator.len());
+
+                return Ok(Some(token!(tokenizer, TokenType::Operator, operator.clone())))
+            }
+        }
+
+        Ok(None)
+    }
+}
+
 pub struct ConstantCharMatcher {
     token_type: TokenType,
     constants: &'static [char],
@@ -134,32 +235,21 @@ impl<'t> Matcher<'t> for ConstantCharMatcher {
     }
 }
 
-pub struct StringLiteralMatcher;
-
-impl<'t> Matcher<'t> for StringLiteralMatcher {
-    fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token>, ()> {
-        let mut raw_marker = false;
-
-        let mut pos = tokenizer.pos;
+// a run of text in a string, or the source of an expression interpolated into one with its 0-based column
+#[derive(Debug, Clone, PartialEq)]
+pub enum Piece {
+    Text(String),
+    Code(String, usize),
+}
 
-        let delimeter = match tokenizer.peek().unwrap() {
-            '"' => '"',
-            'r' => {
-                if tokenizer.peek_n(1) == Some('"') {
-                    raw_marker = true;
-                    tokenizer.advance();
+pub struct StringLiteralMatcher;
 
-                    pos = tokenizer.pos;
+impl StringLiteralMatcher {
+    // everything up to the closing delimeter, which is left for the caller, `pos` is where the literal started
+    pub fn pieces(tokenizer: &mut Tokenizer, raw_marker: bool, pos: (usize, usize)) -> Result<Vec<Piece>, ()> {
+        let delimeter = '"';
 
-                    '"'
-                } else {
-                    return Ok(None);
-                }
-            }
-            _ => return Ok(None),
-        };
-
-        tokenizer.advance();
+        let mut pieces = Vec::new();
 
         let mut string = String::new();
         let mut found_escape = false;
@@ -191,14 +281,34 @@ impl<'t> Matcher<'t> for StringLiteralMatcher {
 
                 string.push(tokenizer.next().unwrap())
             } else if found_escape {
-                string.push(match tokenizer.next().unwrap() {
-                    c @ '"' => c,
-                    'n' => '\n',
-                    'r' => '\r',
-                    't' => '\t',
-                    escaped => {
+                let column = tokenizer.pos.1;
+
+                let escaped = tokenizer.next().unwrap();
+
+                let decoded = match escaped {
+                    '"' => Some('"'),
+                    '\\' => Some('\\'),
+                    '$' => Some('$'),
+                    'n' => Some('\n'),
+                    'r' => Some('\r'),
+                    't' => Some('\t'),
+                    '0' => Some('\0'),
+                    'u' => unicode_escape(tokenizer),
+                    _ => None,
+                };
+
+                let c = match decoded {
+                    Some(c) => c,
+
+                    None => {
+                        let message = if escaped == 'u' {
+                            "expected a unicode escape like `\\u{1F40D}`".to_string()
+                        } else {
+                            format!("unknown escape `\\{}`", escaped)
+                        };
+
                         return Err(response!(
-                            Wrong(format!("unexpected escape character: {}", escaped)),
+                            Wrong(message),
                             tokenizer.source.file,
                             Pos(
                                 (
@@ -210,11 +320,13 @@ impl<'t> Matcher<'t> for StringLiteralMatcher {
                                         .unwrap_or(tokenizer.source.lines.last().unwrap())
                                         .to_string()
                                 ),
-                                (tokenizer.pos.1 - 1, tokenizer.pos.1),
+                                (column + 1, tokenizer.pos.1.max(column + 1)),
                             )
                         ))
-                    }
-                });
+                    },
+                };
+
+                string.push(c);
 
                 found_escape = false
             } else {
@@ -224,10 +336,19 @@ impl<'t> Matcher<'t> for StringLiteralMatcher {
                         found_escape = true
                     }
 
+                    '$' if tokenizer.peek_n(1) == Some('{') => {
+                        if !string.is_empty() {
+                            pieces.push(Piece::Text(string.clone()));
+                            string.clear()
+                        }
+
+                        pieces.push(Self::interpolated(tokenizer, pos)?)
+                    }
+
                     // check for valid closing delimeter and alternative
                     c => {
                         if c == delimeter {
-                            if string.len() > 0 && string != " " {
+                            if !pieces.is_empty() || string.len() > 0 && string != " " {
                                 break;
                             } else {
                                 string.push(tokenizer.next().unwrap())
@@ -240,25 +361,331 @@ impl<'t> Matcher<'t> for StringLiteralMatcher {
             }
         }
 
+        if !string.is_empty() || pieces.is_empty() {
+            pieces.push(Piece::Text(string))
+        }
+
+        Ok(pieces)
+    }
+
+    // the expression source of a `${...}`, braces and strings inside it may nest
+    fn interpolated(tokenizer: &mut Tokenizer, pos: (usize, usize)) -> Result<Piece, ()> {
+        let opening = tokenizer.pos.1;
+
+        tokenizer.advance_n(2);
+
+        let column = tokenizer.pos.1;
+
+        let mut code = String::new();
+
+        let mut depth = 1;
+        let mut quoted = false;
+        let mut escaped = false;
+
+        while let Some(c) = tokenizer.peek() {
+            if quoted {
+                quoted = escaped || c != '"';
+                escaped = !escaped && c == '\\';
+            } else {
+                match c {
+                    '"' => quoted = true,
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => (),
+                }
+
+                if depth == 0 {
+                    break
+                }
+            }
+
+            code.push(c);
+            tokenizer.advance()
+        }
+
+        if depth > 0 || code.trim().is_empty() {
+            let message = if depth > 0 {
+                "unterminated `${`, expected a `}` to close it"
+            } else {
+                "`${}` needs an expression inside"
+            };
+
+            return Err(response!(
+                Wrong(message),
+                tokenizer.source.file,
+                Pos(
+                    (
+                        pos.0,
+                        tokenizer
+                            .source
+                            .lines
+                            .get(pos.0.saturating_sub(1))
+                            .unwrap_or(tokenizer.source.lines.last().unwrap())
+                            .to_string()
+                    ),
+                    (opening + 1, opening + 2),
+                )
+            ))
+        }
+
         tokenizer.advance();
 
-        let mut token = token!(tokenizer, Str, string);
+        Ok(Piece::Code(code, column))
+    }
+}
+
+impl<'t> Matcher<'t> for StringLiteralMatcher {
+    fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token>, ()> {
+        let mut raw_marker = false;
+
+        let mut pos = tokenizer.pos;
+
+        match tokenizer.peek().unwrap() {
+            '"' => (),
+            'r' => {
+                if tokenizer.peek_n(1) == Some('"') {
+                    raw_marker = true;
+                    tokenizer.advance();
+
+                    pos = tokenizer.pos;
+                } else {
+                    return Ok(None);
+                }
+            }
+            _ => return Ok(None),
+        };
+
+        tokenizer.advance();
+
+        let start = tokenizer.index;
+
+        let pieces = Self::pieces(tokenizer, raw_marker, pos)?;
+
+        let end = tokenizer.index;
+
+        tokenizer.advance();
+
+        let mut token = match pieces.as_slice() {
+            [Piece::Text(string)] => token!(tokenizer, Str, string.clone()),
+            _ => token!(tokenizer, Interpolation, tokenizer.items[start .. end].iter().collect()),
+        };
+
+        // escapes and the raw marker make the lexeme differ from the source, so span what was consumed
+        let width = tokenizer.index - tokenizer.peek_snapshot().map_or(0, |s| s.index);
+
+        token.slice.1 = token.slice.0 + width - 1;
+
+        Ok(Some(token))
+    }
+}
+
+// the `{...}` of a `\u{...}` escape, a code point in hex
+fn unicode_escape(tokenizer: &mut Tokenizer) -> Option<char> {
+    if tokenizer.peek() != Some('{') {
+        return None
+    }
+
+    tokenizer.advance();
+
+    let digits = tokenizer.collect_while(|c| c.is_ascii_hexdigit());
+
+    if tokenizer.peek() != Some('}') {
+        return None
+    }
+
+    tokenizer.advance();
+
+    u32::from_str_radix(&digits, 16).ok().and_then(std::char::from_u32)
+}
+
+// `'a'`, exactly one character, `\n`, `\t`, `\\` and `\'` being the escapes
+pub struct CharLiteralMatcher;
+
+impl<'t> Matcher<'t> for CharLiteralMatcher {
+    fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token>, ()> {
+        if tokenizer.peek() != Some('\'') {
+            return Ok(None)
+        }
+
+        let start = tokenizer.pos;
+
+        tokenizer.advance();
+
+        let mut content = String::new();
+
+        loop {
+            match tokenizer.peek() {
+                None | Some('\n') => return Err(response!(
+                    Wrong("unterminated char literal"),
+                    tokenizer.source.file,
+                    char_position(tokenizer, start)
+                )),
 
-        if raw_marker {
-            token.slice.1 += 1
+                Some('\'') => break,
+
+                Some('\\') => {
+                    tokenizer.advance();
+
+                    content.push(match tokenizer.peek() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('\\') => '\\',
+                        Some('\'') => '\'',
+
+                        escaped => return Err(response!(
+                            Wrong(format!("unexpected escape character: {}", escaped.unwrap_or(' '))),
+                            tokenizer.source.file,
+                            char_position(tokenizer, start)
+                        )),
+                    });
+
+                    tokenizer.advance()
+                },
+
+                Some(c) => {
+                    content.push(c);
+                    tokenizer.advance()
+                },
+            }
+        }
+
+        tokenizer.advance();
+
+        if content.chars().count() != 1 {
+            return Err(response!(
+                Wrong("a char literal holds exactly one character, strings go in `\"`"),
+                tokenizer.source.file,
+                char_position(tokenizer, start)
+            ))
         }
 
+        let position = char_position(tokenizer, start);
+
+        let mut token = token!(tokenizer, Char, content);
+
+        token.slice = position.1;
+
         Ok(Some(token))
     }
 }
 
+// from the opening quote of a char literal up to where the tokenizer got
+fn char_position(tokenizer: &Tokenizer, start: (usize, usize)) -> Pos {
+    let width = tokenizer.index - tokenizer.peek_snapshot().map_or(0, |s| s.index);
+
+    let line = tokenizer
+        .source
+        .lines
+        .get(start.0.saturating_sub(1))
+        .unwrap_or(tokenizer.source.lines.last().unwrap())
+        .to_string();
+
+    Pos((start.0, line), (start.1 + 1, start.1 + width.max(1)))
+}
+
+// `"""` strings run over as many lines as they like and take no escapes, the indentation their lines
+// share gets stripped unless they're raw, `r"""`
+pub struct MultilineStringMatcher;
+
+impl<'t> Matcher<'t> for MultilineStringMatcher {
+    fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token>, ()> {
+        let raw = tokenizer.peek() == Some('r');
+        let offset = if raw { 1 } else { 0 };
+
+        if (offset .. offset + 3).any(|n| tokenizer.peek_n(n) != Some('"')) {
+            return Ok(None)
+        }
+
+        let pos = tokenizer.pos;
+
+        tokenizer.advance_n(offset + 3);
+
+        let mut string = String::new();
+
+        loop {
+            if tokenizer.end() {
+                return Err(response!(
+                    Wrong("unterminated delimeter `\"\"\"`"),
+                    tokenizer.source.file,
+                    Pos(
+                        (
+                            pos.0,
+                            tokenizer
+                                .source
+                                .lines
+                                .get(pos.0.saturating_sub(1))
+                                .unwrap_or(tokenizer.source.lines.last().unwrap())
+                                .to_string()
+                        ),
+                        (pos.1 + offset + 1, pos.1 + offset + 3),
+                    )
+                ));
+            }
+
+            if tokenizer.peek_range(3).map_or(false, |s| s == "\"\"\"") {
+                break
+            }
+
+            let c = tokenizer.items[tokenizer.index];
+
+            if c == '\n' {
+                tokenizer.pos.0 += 1;
+                tokenizer.pos.1 = 0;
+                tokenizer.index += 1
+            } else {
+                tokenizer.advance()
+            }
+
+            string.push(c)
+        }
+
+        tokenizer.advance_n(3);
+
+        let string = if raw { string } else { dedent(&string) };
+
+        let mut token = token!(tokenizer, Str, string);
+
+        // only the opening delimiter is on the token's line
+        token.slice.1 = token.slice.0 + offset + 2;
+
+        Ok(Some(token))
+    }
+}
+
+// lines right after the opening and before the closing `"""` don't count, the rest lose their common indentation
+fn dedent(string: &str) -> String {
+    let mut lines = string.split('\n').collect::<Vec<&str>>();
+
+    if lines.len() > 1 && lines[0].trim().is_empty() {
+        lines.remove(0);
+    }
+
+    if lines.len() > 1 && lines.last().unwrap().trim().is_empty() {
+        lines.pop();
+    }
+
+    let indentation = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+
+    let common = lines.iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| indentation(line))
+        .min()
+        .unwrap_or(0);
+
+    lines.iter()
+        .map(|line| line.chars().skip(common.min(indentation(line))).collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
 pub struct IdentifierMatcher;
 
 impl<'t> Matcher<'t> for IdentifierMatcher {
     fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token>, ()> {
         let peeked = tokenizer.peek().unwrap();
 
-        if !peeked.is_alphabetic() && ['_', '\''].contains(&peeked) {
+        // a leading `_` is fine, it marks a name that's meant to go unused
+        if peeked == '\'' {
             return Ok(None);
         }
 
@@ -274,11 +701,80 @@ impl<'t> Matcher<'t> for IdentifierMatcher {
 
 pub struct NumberLiteralMatcher;
 
+impl NumberLiteralMatcher {
+    // `0x1F`, `0b101` and `0o17`, the token holds the value in decimal like any other integer
+    fn radix_literal<'t>(tokenizer: &mut Tokenizer<'t>, negative: bool) -> Result<Option<Token>, ()> {
+        if negative {
+            tokenizer.advance()
+        }
+
+        let radix = match tokenizer.next() {
+            Some('x') => 16,
+            Some('b') => 2,
+            _ => 8,
+        };
+
+        let digits = tokenizer.collect_while(|c| c.is_alphanumeric() || c == '_');
+
+        let literal: String = tokenizer.items[tokenizer.peek_snapshot().map_or(0, |s| s.index) .. tokenizer.index].iter().collect();
+
+        if misplaced_separator(&digits) {
+            return Err(separator_error(tokenizer, &literal))
+        }
+
+        let digits = digits.replace('_', "");
+
+        let value = match i64::from_str_radix(&format!("{}{}", if negative { "-" } else { "" }, digits), radix) {
+            Ok(value) => value,
+
+            Err(error) => {
+                let message = match error.kind() {
+                    IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => format!("integer literal `{}` is too large", literal),
+                    _ => format!("invalid integer literal `{}`", literal),
+                };
+
+                let pos = tokenizer.last_position();
+
+                return Err(response!(
+                    Wrong(message),
+                    tokenizer.source.file,
+                    Pos(
+                        (
+                            pos.0,
+                            tokenizer
+                                .source
+                                .lines
+                                .get(pos.0.saturating_sub(1))
+                                .unwrap_or(tokenizer.source.lines.last().unwrap())
+                                .to_string()
+                        ),
+                        (pos.1 + 1, pos.1 + literal.len()),
+                    )
+                ))
+            },
+        };
+
+        let mut token = token!(tokenizer, Int, value.to_string());
+
+        token.slice.1 = token.slice.0 + consumed(tokenizer) - 1;
+
+        Ok(Some(token))
+    }
+}
+
 impl<'t> Matcher<'t> for NumberLiteralMatcher {
     fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token>, ()> {
         let mut accum = String::new();
 
         let curr = tokenizer.next().unwrap();
+
+        let zero = if curr == '-' { tokenizer.peek() } else { Some(curr) };
+        let prefix = tokenizer.peek_n(if curr == '-' { 1 } else { 0 });
+
+        if zero == Some('0') && ['x', 'b', 'o'].iter().any(|c| prefix == Some(*c)) {
+            return Self::radix_literal(tokenizer, curr == '-')
+        }
+
         if curr.is_digit(10) {
             accum.push(curr)
         } else if curr == '.' {
@@ -291,7 +787,13 @@ impl<'t> Matcher<'t> for NumberLiteralMatcher {
 
         while !tokenizer.end() {
             let current = tokenizer.peek().unwrap();
-            if !current.is_whitespace() && current.is_digit(10) || current == '.' {
+
+            // `0..5` is a range, not the float `0.`
+            if current == '.' && tokenizer.peek_n(1) == Some('.') {
+                break
+            }
+
+            if !current.is_whitespace() && current.is_digit(10) || current == '.' || current == '_' {
                 if current == '.' && accum.contains('.') {
                     let pos = tokenizer.pos;
 
@@ -318,28 +820,141 @@ impl<'t> Matcher<'t> for NumberLiteralMatcher {
             }
         }
 
+        if misplaced_separator(&accum) {
+            return Err(separator_error(tokenizer, &accum))
+        }
+
+        let separated = accum.contains('_');
+
+        accum = accum.replace('_', "");
+
+        // scientific notation, `1.5e-3` and `2E10`
+        if accum.chars().any(|c| c.is_digit(10)) && (tokenizer.peek() == Some('e') || tokenizer.peek() == Some('E')) {
+            accum.push(tokenizer.next().unwrap());
+
+            if tokenizer.peek() == Some('-') || tokenizer.peek() == Some('+') {
+                accum.push(tokenizer.next().unwrap())
+            }
+
+            let mut digits = 0;
+
+            while tokenizer.peek().map_or(false, |c| c.is_digit(10)) {
+                accum.push(tokenizer.next().unwrap());
+                digits += 1
+            }
+
+            // the exponent can't run into a name either, `1e3x` is as wrong as `1e`
+            if digits == 0 || tokenizer.peek().map_or(false, |c| c.is_alphanumeric() || c == '_') {
+                while tokenizer.peek().map_or(false, |c| c.is_alphanumeric() || c == '_') {
+                    accum.push(tokenizer.next().unwrap())
+                }
+
+                let pos = tokenizer.last_position();
+
+                return Err(response!(
+                    Wrong(format!("malformed exponent in number literal `{}`", accum)),
+                    tokenizer.source.file,
+                    Pos(
+                        (
+                            pos.0,
+                            tokenizer
+                                .source
+                                .lines
+                                .get(pos.0.saturating_sub(1))
+                                .unwrap_or(tokenizer.source.lines.last().unwrap())
+                                .to_string()
+                        ),
+                        (pos.1 + 1, pos.1 + accum.len()),
+                    )
+                ));
+            }
+        }
+
         if ["-", "-0.", "-.", "0."].contains(&accum.as_str()) {
             Ok(None)
         } else {
-            if accum.contains(".") {
+            if accum.contains(".") || accum.contains(|c| c == 'e' || c == 'E') {
                 let literal: String = match accum.parse::<f64>() {
                     Ok(result) => result.to_string(),
                     Err(error) => panic!("unable to parse float `{}`: {}", accum, error),
                 };
 
-                Ok(Some(token!(tokenizer, Float, literal)))
+                let mut token = token!(tokenizer, Float, literal);
+
+                // `1e3` is `1000` and `1.50` is `1.5`, the lexeme's length says nothing about the source
+                token.slice.1 = token.slice.0 + consumed(tokenizer) - 1;
+
+                Ok(Some(token))
             } else {
-                let literal: String = match accum.parse::<f64>() {
-                    Ok(result) => result.to_string(),
-                    Err(error) => panic!("unable to parse int `{}`: {}", accum, error),
-                };
+                // keep the exact digits, going through f64 here would round large integers
+                if accum.parse::<i64>().is_err() {
+                    let pos = tokenizer.last_position();
 
-                Ok(Some(token!(tokenizer, Int, literal)))
+                    return Err(response!(
+                        Wrong(format!("integer literal `{}` is too large", accum)),
+                        tokenizer.source.file,
+                        Pos(
+                            (
+                                pos.0,
+                                tokenizer
+                                    .source
+                                    .lines
+                                    .get(pos.0.saturating_sub(1))
+                                    .unwrap_or(tokenizer.source.lines.last().unwrap())
+                                    .to_string()
+                            ),
+                            (pos.1 + 1, pos.1 + accum.len()),
+                        )
+                    ));
+                }
+
+                let mut token = token!(tokenizer, Int, accum);
+
+                if separated {
+                    token.slice.1 = token.slice.0 + consumed(tokenizer) - 1
+                }
+
+                Ok(Some(token))
             }
         }
     }
 }
 
+// a `_` in a number has to sit between two digits, `1_000` but not `1__000`, `5_` or `1_.5`
+fn misplaced_separator(digits: &str) -> bool {
+    let chars = digits.chars().collect::<Vec<char>>();
+
+    chars.iter().enumerate().any(|(i, c)| {
+        *c == '_' && !(i > 0 && chars[i - 1].is_alphanumeric() && chars.get(i + 1).map_or(false, |c| c.is_alphanumeric()))
+    })
+}
+
+fn separator_error(tokenizer: &Tokenizer, literal: &str) {
+    let pos = tokenizer.last_position();
+
+    response!(
+        Wrong(format!("misplaced `_` in `{}`, it can only go between two digits", literal)),
+        tokenizer.source.file,
+        Pos(
+            (
+                pos.0,
+                tokenizer
+                    .source
+                    .lines
+                    .get(pos.0.saturating_sub(1))
+                    .unwrap_or(tokenizer.source.lines.last().unwrap())
+                    .to_string()
+            ),
+            (pos.1 + 1, pos.1 + literal.len()),
+        )
+    )
+}
+
+// how many characters the current match took from the source
+fn consumed(tokenizer: &Tokenizer) -> usize {
+    tokenizer.index - tokenizer.peek_snapshot().map_or(0, |s| s.index)
+}
+
 pub struct KeyMatcher {
     token_type: TokenType,
     constants: &'static [&'static str],
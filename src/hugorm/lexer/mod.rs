@@ -9,3 +9,6 @@ pub use self::lexer::*;
 pub use self::matcher::*;
 pub use self::token::*;
 pub use self::tokenizer::*;
+
+#[cfg(test)]
+mod tests;
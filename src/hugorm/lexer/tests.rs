@@ -0,0 +1,166 @@
+use super::*;
+use super::super::testing::*;
+
+#[test]
+fn raw_strings_keep_backslashes() {
+    let escaped = lex("\"\\n\"").unwrap();
+    let raw = lex("r\"\\n\"").unwrap();
+
+    assert_eq!(escaped[0].token_type, TokenType::Str);
+    assert_eq!(escaped[0].lexeme.len(), 1);
+
+    assert_eq!(raw[0].token_type, TokenType::Str);
+    assert_eq!(raw[0].lexeme, "\\n");
+}
+
+#[test]
+fn raw_and_escaped_strings_span_their_source() {
+    assert_eq!(lex("\"a\\tb\"").unwrap()[0].slice, (1, 6));
+    assert_eq!(lex("r\"\\d+\"").unwrap()[0].slice, (1, 6));
+}
+
+#[test]
+fn scientific_notation_is_one_float() {
+    for code in ["1.5e-3", "2E10", "6e+2"].iter() {
+        let tokens = lex(code).unwrap();
+
+        assert_eq!(tokens[0].token_type, TokenType::Float, "{}", code);
+        assert_eq!(tokens[0].slice, (1, code.len()));
+    }
+
+    assert_eq!(run("print(1.5e-3)\nprint(2E10)\nprint(6e+2)\n").unwrap(), vec!["0.0015", "20000000000", "600"]);
+}
+
+#[test]
+fn malformed_exponents_are_errors() {
+    for code in ["1e", "1e+", "1e3x"].iter() {
+        let (result, reported) = reported(|| lex(code));
+
+        assert!(result.is_err(), "{}", code);
+        assert!(reported.contains("malformed exponent"), "{}", reported);
+    }
+}
+
+#[test]
+fn triple_quoted_strings_keep_their_newlines() {
+    let code = "let text = \"\"\"\n    one\n      two\n    three\n    \"\"\"\n";
+    let tokens = lex(code).unwrap();
+
+    let text = tokens.iter().find(|token| token.token_type == TokenType::Str).unwrap();
+
+    assert_eq!(text.lexeme, "one\n  two\nthree");
+}
+
+#[test]
+fn raw_triple_quoted_strings_keep_their_indentation() {
+    let code = "let text = r\"\"\"\n    one\n      two\n    \"\"\"\n";
+    let tokens = lex(code).unwrap();
+
+    let text = tokens.iter().find(|token| token.token_type == TokenType::Str).unwrap();
+
+    assert_eq!(text.lexeme, "\n    one\n      two\n    ");
+}
+
+#[test]
+fn char_literals_are_one_character() {
+    for (code, c) in [("'a'", 'a'), ("'\\n'", '\n'), ("'\\t'", '\t'), ("'\\\\'", '\\'), ("'\\''", '\'')].iter() {
+        let tokens = lex(code).unwrap();
+
+        assert_eq!(tokens[0].token_type, TokenType::Char, "{}", code);
+        assert_eq!(tokens[0].lexeme, c.to_string(), "{}", code);
+    }
+
+    for code in ["'ab'", "''", "'a"].iter() {
+        assert!(reported(|| lex(code)).0.is_err(), "{}", code);
+    }
+}
+
+#[test]
+fn string_escapes_are_decoded() {
+    let tokens = lex("\"a\\tb\\n\\\"q\\\" \\\\ \\0 \\u{41}\"").unwrap();
+
+    assert_eq!(tokens[0].lexeme, "a\tb\n\"q\" \\ \0 A");
+}
+
+#[test]
+fn unknown_escapes_are_errors() {
+    let (result, reported) = reported(|| lex("\"a\\qb\""));
+
+    assert!(result.is_err());
+    assert!(reported.contains("unknown escape `\\q`"), "{}", reported);
+}
+
+#[test]
+fn radix_literals_lex_to_their_value() {
+    for (code, value) in [("0x1F", "31"), ("0b101", "5"), ("0o17", "15"), ("0xFFFFFFFF", "4294967295"), ("0xff_ff", "65535")].iter() {
+        let tokens = lex(code).unwrap();
+
+        assert_eq!(tokens[0].token_type, TokenType::Int, "{}", code);
+        assert_eq!(tokens[0].lexeme, *value, "{}", code);
+    }
+}
+
+#[test]
+fn malformed_radix_literals_are_errors() {
+    for (code, message) in [("0x1G", "invalid integer literal"), ("0b102", "invalid integer literal"), ("0xFFFFFFFFFFFFFFFFF", "too large")].iter() {
+        let (result, reported) = reported(|| lex(code));
+
+        assert!(result.is_err(), "{}", code);
+        assert!(reported.contains(message), "{}", reported);
+    }
+}
+
+#[test]
+fn underscores_separate_digits() {
+    assert_eq!(lex("1_000_000").unwrap()[0].lexeme, "1000000");
+    assert_eq!(lex("3.141_592").unwrap()[0].lexeme, "3.141592");
+
+    assert_eq!(run("print(1_000 + 1)\nprint(0.000_5)\n").unwrap(), vec!["1001", "0.0005"]);
+}
+
+#[test]
+fn underscores_only_go_between_digits() {
+    for code in ["5_", "1__000", "1_.5", "1._5", "0x_1"].iter() {
+        let (result, reported) = reported(|| lex(code));
+
+        assert!(result.is_err(), "{}", code);
+        assert!(reported.contains("misplaced `_`"), "{}: {}", code, reported);
+    }
+}
+
+#[test]
+fn block_comments_nest() {
+    let tokens = lex("1 /* a /* b */ c */ 2").unwrap();
+    let shown = tokens.iter()
+        .filter(|token| token.token_type != TokenType::Whitespace)
+        .map(|token| token.lexeme.as_str())
+        .collect::<Vec<_>>();
+
+    assert_eq!(shown, vec!["1", "2"]);
+    assert_eq!(run("print(/* not /* this */ either */ 1)\n").unwrap(), vec!["1"]);
+}
+
+#[test]
+fn unterminated_block_comments_point_at_their_start() {
+    let (result, reported) = reported(|| lex("let a = 1\n  /* a /* b */\nlet b = 2\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("unterminated block comment"), "{}", reported);
+    assert!(reported.contains("2 │   /* a /* b */"), "{}", reported);
+}
+
+#[test]
+fn block_comments_over_indented_lines_keep_the_block() {
+    let code = "\
+fun f(a):
+    /* the first line
+        goes deeper
+  and back out */
+    print(a)
+    /* one */ print(a + 1)
+f(1)
+print(3)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["1", "2", "3"]);
+}
@@ -8,6 +8,7 @@ pub enum TokenType {
     Float,
     Keyword,
     Str,
+    Interpolation, // a string with `${...}` in it, the lexeme is its source between the quotes
     Char,
     Symbol,
     Operator,
@@ -26,6 +27,7 @@ impl fmt::Display for TokenType {
             Int => write!(f, "Int"),
             Float => write!(f, "Float"),
             Str => write!(f, "Str"),
+            Interpolation => write!(f, "Interpolation"),
             Char => write!(f, "Char"),
             Keyword => write!(f, "Keyword"),
             Bool => write!(f, "Bool"),
@@ -42,6 +44,11 @@ impl fmt::Display for TokenType {
 pub struct Pos(pub (usize, String), pub (usize, usize));
 
 impl Pos {
+    // for nodes that don't come from source text
+    pub fn synthetic() -> Self {
+        Pos((0, String::new()), (0, 0))
+    }
+
     pub fn get_lexeme(&self) -> String {
         (self.0).1[(self.1).0 - if (self.1).0 > 0 { 1 } else { 0 }..(self.1).1].to_string()
     }
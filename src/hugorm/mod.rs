@@ -4,4 +4,7 @@ pub mod source;
 pub mod lexer;
 pub mod parser;
 pub mod visitor;
-pub mod prelude;
\ No newline at end of file
+pub mod prelude;
+pub mod compilation;
+#[cfg(test)]
+pub mod testing;
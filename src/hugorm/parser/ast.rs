@@ -1,25 +1,80 @@
 use std::rc::Rc;
 use std::fmt;
+use std::cell::Cell;
 
 use super::*;
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeNode {
+  Int,
+  Float,
+  Bool,
+  Str,
+  Any,
+  Char,
+  Nil,
+  Func(usize),
+  Dict(Vec<(String, TypeNode)>),
+  Array(Box<TypeNode>), // what every element is, `Any` when they differ
+  DictOf(Box<TypeNode>), // a dict annotated as `Dict(T)`, only its values are known
+}
+
+impl TypeNode {
+  // what a type is called in an annotation, like `let x: int` or `Array(Int)`
+  pub fn from_name(name: &str) -> Option<TypeNode> {
+    use self::TypeNode::*;
+
+    let t = match name {
+      "int"   | "Int"   => Int,
+      "float" | "Float" => Float,
+      "str"   | "Str"   => Str,
+      "bool"  | "Bool"  => Bool,
+      "char"  | "Char"  => Char,
+      "any"   | "Any"   => Any,
+      _       => return None,
+    };
+
+    Some(t)
+  }
+
+  // `any` goes both ways, containers fit by what they hold, everything else has to match exactly
+  pub fn fits(&self, other: &TypeNode) -> bool {
+    use self::TypeNode::*;
+
+    match (self, other) {
+      (Any, _) | (_, Any) => true,
+      (Array(a), Array(b)) | (DictOf(a), DictOf(b)) => a.fits(b),
+      (Dict(fields), DictOf(t)) => fields.iter().all(|(_, field)| field.fits(t)),
+      _ => self == other,
+    }
+  }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum StatementNode {
   Expression(Expression),
-  Declaration(String, Option<Expression>),
+  Declaration(String, Option<TypeNode>, Option<Expression>), // the type is only there when annotated
   Const(String, Expression),
   ConstFunction(Rc<Statement>),
   Assignment(Expression, Expression),
-  Function(String, Vec<String>, Vec<Statement>),
+  Function(String, Vec<(String, Option<TypeNode>, bool)>, Vec<Statement>), // params with their annotated type, and whether they're `const`
   Return(Option<Expression>),
   Interface(String, Vec<Statement>),
   If(Expression, Vec<Statement>, Vec<(Option<Expression>, Vec<Statement>)>),
   While(Expression, Vec<Statement>),
+  WhileLet(String, Expression, Vec<Statement>, Option<Vec<Statement>>), // loops while the binding is truthy, else runs if it never was
   Block(Vec<Statement>),
+  When(String, Vec<Statement>), // compiled only if the flag is defined
+  Yield(Expression),
+  For(String, Option<String>, Expression, Vec<Statement>), // `for x in xs` or, over a dict, `for (key, value) in d`
+  Match(Expression, Vec<(Expression, Vec<Statement>)>, Option<Vec<Statement>>), // literal `case`s, then an optional `else`
+  Check(Expression), // stops the program when false, compiled out in release mode
   Break,
+  BreakLabel(String, Option<Expression>), // leaves the labeled block, which then evaluates to the value
+  Error, // stands in for a statement that didn't parse, see `Parser::parse_partial`
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct Statement {
   pub node: StatementNode,
   pub pos:  Pos,
@@ -34,29 +89,110 @@ impl Statement {
   }
 }
 
+// shorthands for building statements in code, positions are synthetic
+impl Statement {
+  pub fn expression(expression: Expression) -> Self {
+    Statement::from(StatementNode::Expression(expression))
+  }
+
+  pub fn let_(name: &str, value: Expression) -> Self {
+    Statement::from(StatementNode::Declaration(name.to_string(), None, Some(value)))
+  }
+
+  pub fn assign(target: Expression, value: Expression) -> Self {
+    Statement::from(StatementNode::Assignment(target, value))
+  }
+
+  pub fn return_(value: Option<Expression>) -> Self {
+    Statement::from(StatementNode::Return(value))
+  }
+
+  pub fn function(name: &str, params: &[&str], body: Vec<Statement>) -> Self {
+    Statement::from(
+      StatementNode::Function(name.to_string(), params.iter().map(|p| (p.to_string(), None, false)).collect(), body)
+    )
+  }
+}
+
+impl From<StatementNode> for Statement {
+  fn from(node: StatementNode) -> Self {
+    Statement::new(node, Pos::synthetic())
+  }
+}
+
+impl From<Expression> for Statement {
+  fn from(expression: Expression) -> Self {
+    Statement::expression(expression)
+  }
+}
+
+impl fmt::Debug for Statement {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if ELIDE_POS.with(Cell::get) {
+      self.node.fmt(f)
+    } else {
+      f.debug_struct("Statement").field("node", &self.node).field("pos", &self.pos).finish()
+    }
+  }
+}
+
 
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ExpressionNode {
   Nil,
-  Int(i32),
+  Int(i64),
   Float(f64),
   Str(String),
+  Char(char),
   Identifier(String),
   Bool(bool),
   Neg(Rc<Expression>), // -
   Not(Rc<Expression>), // not
   Binary(Rc<Expression>, Operator, Rc<Expression>),
-  Call(Rc<Expression>, Vec<Expression>),
+  Call(Rc<Expression>, Vec<Expression>, Vec<(String, Expression)>), // callee, positional and keyword args
   Array(Vec<Expression>),
-  Dict(Vec<(String, Expression)>),
+  Dict(Vec<DictEntry>),
+  Tuple(Vec<Expression>), // (a, b), a list at runtime
+  Range(Rc<Expression>, Rc<Expression>), // start..end, without end
+  RangeInclusive(Rc<Expression>, Rc<Expression>), // start..=end
+  Slice(Rc<Expression>, Option<Rc<Expression>>, Option<Rc<Expression>>), // xs[start:end], either bound can be left out
   With(Rc<Expression>, Rc<Expression>),
+  OptionalIndex(Rc<Expression>, Rc<Expression>), // a?.b and a?[b], nil when a is nil
+  Conditional(Rc<Expression>, Rc<Expression>, Rc<Expression>), // a if cond else b, held as cond, a, b
   AnonFunction(String, Vec<String>, Vec<Statement>), // name is ID, still GDPR-anonymous
+  Labeled(String, Vec<Statement>), // @name do: ..., nil unless a `break @name value` gets out of it
+  Spread(Rc<Expression>), // ...xs inside an array or call, dicts have `DictEntry::Spread`
   Empty,
+  Error, // stands in for a value that didn't parse, see `Parser::parse_partial`
   EOF,
 }
 
+// what goes between the braces of a dict literal, `key: value` or `...source`
 #[derive(Debug, Clone, PartialEq)]
+pub enum DictEntry {
+  Field(String, Expression),
+  Spread(Expression), // copies every field of another dict in
+}
+
+impl DictEntry {
+  // the field's value, or the dict spread in
+  pub fn value(&self) -> &Expression {
+    match *self {
+      DictEntry::Field(_, ref value) | DictEntry::Spread(ref value) => value,
+    }
+  }
+
+  // the same entry around another value
+  pub fn map(&self, f: impl FnOnce(&Expression) -> Expression) -> Self {
+    match *self {
+      DictEntry::Field(ref key, ref value) => DictEntry::Field(key.clone(), f(value)),
+      DictEntry::Spread(ref source) => DictEntry::Spread(f(source)),
+    }
+  }
+}
+
+#[derive(Clone, PartialEq)]
 pub struct Expression {
   pub node: ExpressionNode,
   pub pos:  Pos
@@ -71,11 +207,103 @@ impl Expression {
   }
 }
 
+// shorthands for building expressions in code, positions are synthetic
+impl Expression {
+  pub fn nil() -> Self {
+    Expression::from(ExpressionNode::Nil)
+  }
+
+  pub fn int(n: i64) -> Self {
+    Expression::from(ExpressionNode::Int(n))
+  }
+
+  pub fn float(n: f64) -> Self {
+    Expression::from(ExpressionNode::Float(n))
+  }
+
+  pub fn str(s: &str) -> Self {
+    Expression::from(ExpressionNode::Str(s.to_string()))
+  }
+
+  pub fn char(c: char) -> Self {
+    Expression::from(ExpressionNode::Char(c))
+  }
+
+  pub fn bool(b: bool) -> Self {
+    Expression::from(ExpressionNode::Bool(b))
+  }
+
+  pub fn identifier(name: &str) -> Self {
+    Expression::from(ExpressionNode::Identifier(name.to_string()))
+  }
+
+  pub fn binary(left: Expression, op: Operator, right: Expression) -> Self {
+    Expression::from(ExpressionNode::Binary(Rc::new(left), op, Rc::new(right)))
+  }
+
+  pub fn call(callee: Expression, args: Vec<Expression>) -> Self {
+    Expression::from(ExpressionNode::Call(Rc::new(callee), args, Vec::new()))
+  }
+
+  pub fn array(content: Vec<Expression>) -> Self {
+    Expression::from(ExpressionNode::Array(content))
+  }
+
+  pub fn tuple(content: Vec<Expression>) -> Self {
+    Expression::from(ExpressionNode::Tuple(content))
+  }
+
+  pub fn dict(content: Vec<(&str, Expression)>) -> Self {
+    Expression::from(
+      ExpressionNode::Dict(content.into_iter().map(|(key, value)| DictEntry::Field(key.to_string(), value)).collect())
+    )
+  }
+}
+
+impl From<ExpressionNode> for Expression {
+  fn from(node: ExpressionNode) -> Self {
+    Expression::new(node, Pos::synthetic())
+  }
+}
+
+impl fmt::Debug for Expression {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if ELIDE_POS.with(Cell::get) {
+      self.node.fmt(f)
+    } else {
+      f.debug_struct("Expression").field("node", &self.node).field("pos", &self.pos).finish()
+    }
+  }
+}
+
+
+
+thread_local! {
+  static ELIDE_POS: Cell<bool> = Cell::new(false);
+}
+
+// Debug-formats an AST without positions, `{:#?}` of `NoPos(&ast)` only changes when the structure does
+pub struct NoPos<'a, T: fmt::Debug + ?Sized>(pub &'a T);
+
+impl<'a, T: fmt::Debug + ?Sized> fmt::Debug for NoPos<'a, T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let elided = ELIDE_POS.with(|e| e.replace(true));
+    let result = self.0.fmt(f);
+
+    ELIDE_POS.with(|e| e.set(elided));
+
+    result
+  }
+}
+
 
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Operator {
   Add, Sub, Mul, Div, Mod, Pow, Concat, Eq, Lt, Gt, NEq, LtEq, GtEq, Or, And, Index,
+  WrapAdd, WrapSub, WrapMul, // two's complement on ints instead of float arithmetic
+  Is, // runtime type test, `x is Int`
+  Custom(String), // added by the host, see `Visitor::register_operator`
 }
 
 impl Operator {
@@ -95,10 +323,14 @@ impl Operator {
       "!="  => (NEq,    1),
       "<="  => (LtEq,   1),
       ">="  => (GtEq,   1),
+      "is"  => (Is,     1),
       "+"   => (Add,    2),
       "-"   => (Sub,    2),
       "++"  => (Concat, 2),
+      "+%"  => (WrapAdd, 2),
+      "-%"  => (WrapSub, 2),
       "*"   => (Mul,    3),
+      "*%"  => (WrapMul, 3),
       "/"   => (Div,    3),
       "%"   => (Mod,    3),
       "^"   => (Pow,    4),
@@ -129,6 +361,11 @@ impl Operator {
       GtEq   => ">=",
       Or     => "or",
       And    => "and",
+      WrapAdd => "+%",
+      WrapSub => "-%",
+      WrapMul => "*%",
+      Is     => "is",
+      Custom(ref symbol) => symbol,
     }
   }
 
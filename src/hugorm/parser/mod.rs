@@ -5,4 +5,6 @@ use self::super::lexer::*;
 use self::super::source::Source;
 
 pub use self::ast::*;
-pub use self::parser::*;
\ No newline at end of file
+pub use self::parser::*;
+#[cfg(test)]
+mod tests;
@@ -1,8 +1,24 @@
-use super::super::error::Response::Wrong;
+use super::super::error::{ self, Response };
+use super::super::error::Response::{ Wrong, Weird };
 use super::*;
 
 use std::rc::Rc;
 
+// operators that only ever start an expression, the precedence table has no use for them
+const PREFIX_OPERATORS: &[&str] = &["not"];
+
+// the loosest binary operator each prefix operator's operand takes, see `Operator::from_str` for the rest
+const UNARY_MINUS_PREC: usize = 4; // `^` and `.`
+const UNARY_NOT_PREC: usize = 1; // comparisons and up, not `and` and `or`
+
+// where a parser is and how deep it's indented, see `Parser::save`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snapshot {
+    index: usize,
+    indent: usize,
+    indent_standard: usize,
+}
+
 pub struct Parser<'p> {
     index: usize,
     tokens: Vec<Token>,
@@ -11,7 +27,16 @@ pub struct Parser<'p> {
     indent_standard: usize,
     indent: usize,
 
-    min_prec: usize,
+    command_calls: bool,
+
+    recovery: bool,
+    pub errors: Vec<Response<String>>, // what each statement the last parse skipped reported
+
+    tabs_forbidden: bool,
+
+    operators: Vec<(String, u8)>, // registered by the host, with their precedence
+
+    indents: Option<Vec<(usize, bool)>>, // per token, the indent it was parsed at and whether a block ended there
 }
 
 impl<'p> Parser<'p> {
@@ -24,13 +49,86 @@ impl<'p> Parser<'p> {
             indent_standard: 0,
             indent: 0,
 
-            min_prec: 0
+            command_calls: false,
+
+            recovery: false,
+            errors: Vec::new(),
+
+            tabs_forbidden: false,
+
+            operators: Vec::new(),
+
+            indents: None,
         }
     }
 
+    // operators the lexer can produce that `parse_binary` wouldn't know the precedence of
+    pub fn untabled_operators() -> Vec<&'static str> {
+        OPERATORS.iter().chain(WORD_OPERATORS.iter())
+            .filter(|op| Operator::from_str(op).is_none() && !PREFIX_OPERATORS.contains(op))
+            .cloned()
+            .collect()
+    }
+
+    // lets `print "hi"` stand for `print("hi")` at statement position
+    pub fn set_command_calls(&mut self, enabled: bool) {
+        self.command_calls = enabled
+    }
+
+    // keeps parsing after a broken statement so every one of them gets reported, the parse still fails
+    pub fn set_recovery(&mut self, enabled: bool) {
+        self.recovery = enabled
+    }
+
+    // rejects lines indented with tabs, which `indent_standard` would count as a single space
+    pub fn set_forbid_tabs(&mut self, enabled: bool) {
+        self.tabs_forbidden = enabled
+    }
+
+    // binary operators beyond the built in ones, see `Visitor::parser`
+    pub(crate) fn set_operators(&mut self, operators: &[(String, u8)]) {
+        self.operators = operators.to_vec()
+    }
+
+    // records the indentation every token gets parsed at for `dump_indents`
+    pub fn set_trace_indents(&mut self, enabled: bool) {
+        self.indents = if enabled {
+            Some(vec!((0, false); self.tokens.len()))
+        } else {
+            None
+        }
+    }
+
+    // for reparsing only part of a file, `restore` picks up again from exactly here
+    pub fn save(&self) -> Snapshot {
+        Snapshot {
+            index: self.index,
+            indent: self.indent,
+            indent_standard: self.indent_standard,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.index = snapshot.index;
+        self.indent = snapshot.indent;
+        self.indent_standard = snapshot.indent_standard;
+    }
+
     pub fn parse(&mut self) -> Result<Vec<Statement>, ()> {
+        if self.tabs_forbidden {
+            self.check_tabs()?
+        }
+
+        if self.recovery {
+            let ast = self.parse_partial();
+
+            return if self.errors.is_empty() { Ok(ast) } else { Err(()) }
+        }
+
         let mut ast = Vec::new();
 
+        self.errors.clear();
+
         while self.remaining() > 0 {
             ast.push(self.parse_statement()?)
         }
@@ -38,6 +136,122 @@ impl<'p> Parser<'p> {
         Ok(ast)
     }
 
+    // for hosts and sub-parsers stopping after one statement or expression, anything but blank lines left over is an error
+    pub fn expect_eof(&self) -> Result<(), ()> {
+        let trailing = self.tokens.iter()
+            .skip(self.index)
+            .find(|token| token.lexeme != "\n");
+
+        match trailing {
+            Some(token) => Err(response!(
+                Wrong(format!("unexpected trailing tokens, starting at `{}`", token.lexeme)),
+                self.source.file,
+                Pos(token.line.clone(), token.slice)
+            )),
+
+            None => Ok(()),
+        }
+    }
+
+    // for editors, always gives back an AST where each broken statement is a `StatementNode::Error`
+    // and the good ones around it are intact, `errors` holds what each broken one reported. a broken `let` still
+    // declares its name, with an `ExpressionNode::Error` for a value, so what comes after can use it
+    pub fn parse_partial(&mut self) -> Vec<Statement> {
+        let mut ast = Vec::new();
+
+        self.errors.clear();
+
+        while self.remaining() > 0 {
+            let start = self.index;
+
+            let (result, report) = error::capture(|| self.parse_statement());
+
+            error::write_response(format_args!("{}", report));
+
+            match result {
+                Ok(statement) => ast.push(statement),
+
+                Err(()) => {
+                    self.errors.push(Wrong(report));
+
+                    self.index = start;
+
+                    while self.remaining() > 0 && self.current_lexeme() == "\n" {
+                        self.index += 1
+                    }
+
+                    let position = self.current_position();
+
+                    let declared = if self.current_lexeme() == "let" && self.remaining() > 1 && self.tokens[self.index + 1].token_type == TokenType::Identifier {
+                        Some(self.tokens[self.index + 1].lexeme.clone())
+                    } else {
+                        None
+                    };
+
+                    self.synchronize(start);
+
+                    let node = match declared {
+                        Some(name) => StatementNode::Declaration(name, None, Some(Expression::new(ExpressionNode::Error, position.clone()))),
+                        None => StatementNode::Error,
+                    };
+
+                    ast.push(Statement::new(node, position))
+                },
+            }
+        }
+
+        ast
+    }
+
+    // the first line with a tab in its indentation, marking all of it
+    fn check_tabs(&self) -> Result<(), ()> {
+        let mut line = None;
+
+        for token in self.tokens.iter() {
+            if line == Some(token.line.0) {
+                continue
+            }
+
+            line = Some(token.line.0);
+
+            let text = &token.line.1;
+            let indentation = &text[.. text.len() - text.trim_start().len()];
+
+            if indentation.contains('\t') {
+                return Err(response!(
+                    Wrong("indented with a tab, only spaces are allowed"),
+                    self.source.file,
+                    Pos(token.line.clone(), (1, indentation.len()))
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    // skips what's left of a broken top level statement, lines indented past its first one are its body
+    fn synchronize(&mut self, start: usize) {
+        let indent = self.tokens[start ..].iter()
+            .find(|token| token.lexeme != "\n")
+            .map_or(0, |token| token.slice.0 - 1);
+
+        self.indent = 0;
+
+        loop {
+            while self.remaining() > 0 && self.current_lexeme() != "\n" {
+                self.index += 1
+            }
+
+            while self.remaining() > 0 && self.current_lexeme() == "\n" {
+                self.index += 1
+            }
+
+            if self.remaining() == 0 || self.get_indent() <= indent {
+                break
+            }
+        }
+    }
+
     pub fn parse_statement(&mut self) -> Result<Statement, ()> {
         use self::TokenType::*;
 
@@ -48,7 +262,7 @@ impl<'p> Parser<'p> {
         let position = self.current_position();
 
         let statement = match self.current_type() {
-            Keyword => match self.current_lexeme().as_str() {
+            Keyword => match self.current_lexeme() {
                 "return" => {
                     self.next()?;
 
@@ -69,15 +283,69 @@ impl<'p> Parser<'p> {
                     }
                 }
 
+                "yield" => {
+                    self.next()?;
+
+                    Statement::new(
+                        StatementNode::Yield(self.parse_expression()?),
+                        self.span_from(position)
+                    )
+                }
+
+                "for" => {
+                    self.next()?;
+
+                    let (name, value) = if self.current_lexeme() == "(" {
+                        self.next()?;
+
+                        let key = self.eat_type(&Identifier)?;
+
+                        self.eat_lexeme(",")?;
+
+                        let value = self.eat_type(&Identifier)?;
+
+                        self.eat_lexeme(")")?;
+
+                        (key, Some(value))
+                    } else {
+                        (self.eat_type(&Identifier)?, None)
+                    };
+
+                    self.eat_lexeme("in")?;
+
+                    let iterable = self.parse_expression()?;
+
+                    self.eat_lexeme(":")?;
+
+                    let pos = self.span_from(position);
+
+                    let body = self.parse_block_or_inline()?;
+
+                    return Ok(
+                        Statement::new(
+                            StatementNode::For(name, value, iterable, body),
+                            pos
+                        )
+                    )
+                }
+
                 "let" => {
                     self.next()?;
 
                     let name = self.eat_type(&TokenType::Identifier)?;
 
+                    let annotation = if self.current_lexeme() == ":" {
+                        self.next()?;
+                        Some(self.parse_type()?)
+                    } else {
+                        None
+                    };
+
                     if self.current_lexeme() == "\n" {
                         Statement::new(
                             StatementNode::Declaration(
                                 name,
+                                annotation,
                                 None
                             ),
                             self.span_from(position)
@@ -90,6 +358,7 @@ impl<'p> Parser<'p> {
                         Statement::new(
                             StatementNode::Declaration(
                                 name,
+                                annotation,
                                 Some(right)
                             ),
                             self.span_from(position)
@@ -101,11 +370,14 @@ impl<'p> Parser<'p> {
                     self.next()?;
 
                     if self.current_lexeme() == "fun" {
-                        Statement::new(
-                            StatementNode::ConstFunction(
-                                Rc::new(self.parse_statement()?)
-                            ),
-                            self.span_from(position)
+                        let fun = self.parse_statement()?;
+
+                        // the function already ate its body and the newline after it
+                        return Ok(
+                            Statement::new(
+                                StatementNode::ConstFunction(Rc::new(fun)),
+                                position
+                            )
                         )
                     } else {
                         let name = self.eat_type(&TokenType::Identifier)?;
@@ -115,9 +387,9 @@ impl<'p> Parser<'p> {
                         let right = self.parse_expression()?;
 
                         Statement::new(
-                            StatementNode::Declaration(
+                            StatementNode::Const(
                                 name,
-                                Some(right)
+                                right
                             ),
                             self.span_from(position)
                         )
@@ -137,25 +409,20 @@ impl<'p> Parser<'p> {
                     let mut params = Vec::new();
 
                     if self.current_lexeme() != ")" {
-                        params.push(self.eat_type(&TokenType::Identifier)?);
+                        params.push(self.parse_param()?);
 
                         while self.current_lexeme() == "," {
                             self.next()?;
                             self.next_newline()?;
                             
-                            params.push(self.eat_type(&TokenType::Identifier)?)
+                            params.push(self.parse_param()?)
                         }
                     }
 
                     self.eat_lexeme(")")?;
                     self.eat_lexeme(":")?;
 
-                    let body = if self.current_lexeme() == "\n" {
-                        self.next()?;
-                        self.parse_body()?
-                    } else {
-                        vec!(self.parse_statement()?)
-                    };
+                    let body = self.parse_function_body()?;
 
                     return Ok(
                         Statement::new(
@@ -177,16 +444,21 @@ impl<'p> Parser<'p> {
 
                     self.eat_lexeme(":")?;
 
-                    let body = if self.current_lexeme() == "\n" {
-                        self.next()?;
-                        self.parse_body()?
-                    } else {
-                        vec!(self.parse_statement()?)
-                    };
+                    let body = self.parse_block_or_inline()?;
+
+                    let mut names: Vec<&String> = Vec::new();
 
                     for s in body.iter() {
-                        if let StatementNode::Function(..) = s.node {
-                            continue
+                        if let StatementNode::Function(ref function, ..) = s.node {
+                            if names.contains(&function) {
+                                return Err(response!(
+                                    Wrong(format!("interface `{}` already declares `{}`", name, function)),
+                                    self.source.file,
+                                    s.pos
+                                ));
+                            }
+
+                            names.push(function)
                         } else {
                             return Err(response!(
                                 Wrong(format!("can't interface non-function")),
@@ -210,18 +482,46 @@ impl<'p> Parser<'p> {
                 "while" => {
                     self.next()?;
 
+                    if self.current_lexeme() == "let" {
+                        self.next()?;
+
+                        let name = self.eat_type(&Identifier)?;
+
+                        self.eat_lexeme("=")?;
+
+                        let right = self.parse_expression()?;
+
+                        self.eat_lexeme(":")?;
+
+                        let pos = self.span_from(position);
+
+                        let inline = self.current_lexeme() != "\n";
+                        let body = self.parse_block_or_inline()?;
+
+                        let else_ = if !inline && self.current_lexeme() == "else" {
+                            self.next()?;
+                            self.eat_lexeme(":")?;
+
+                            Some(self.parse_block_or_inline()?)
+                        } else {
+                            None
+                        };
+
+                        return Ok(
+                            Statement::new(
+                                StatementNode::WhileLet(name, right, body, else_),
+                                pos
+                            )
+                        )
+                    }
+
                     let cond = self.parse_expression()?;
 
                     self.eat_lexeme(":")?;
 
                     let pos = self.span_from(position);
 
-                    let body = if self.current_lexeme() == "\n" {
-                        self.next()?;
-                        self.parse_body()?
-                    } else {
-                        vec!(self.parse_statement()?)
-                    };
+                    let body = self.parse_block_or_inline()?;
 
                     return Ok(
                         Statement::new(
@@ -244,12 +544,7 @@ impl<'p> Parser<'p> {
                             pos.clone()
                         );
 
-                        let body = if self.current_lexeme() == "\n" {
-                            self.next()?;
-                            self.parse_body()?
-                        } else {
-                            vec!(self.parse_statement()?)
-                        };
+                        let body = self.parse_block_or_inline()?;
 
                         return Ok(
                             Statement::new(
@@ -269,6 +564,7 @@ impl<'p> Parser<'p> {
                         let iterator = Statement::new(
                             StatementNode::Declaration(
                                 name.clone(),
+                                None,
                                 Some(
                                     Expression::new(
                                     ExpressionNode::Int(0),
@@ -315,14 +611,10 @@ impl<'p> Parser<'p> {
                             pos.clone()
                         );
 
-                        let mut body = if self.current_lexeme() == "\n" {
-                            self.next()?;
-                            self.parse_body()?
-                        } else {
-                            vec!(self.parse_statement()?)
-                        };
+                        let mut body = self.parse_block_or_inline()?;
 
-                        body.push(increment);
+                        // counting first, nothing that leaves the body early can skip it
+                        body.insert(0, increment);
 
                         let loopy = Statement::new(
                             StatementNode::Block(
@@ -349,13 +641,47 @@ impl<'p> Parser<'p> {
                 "break" => {
                     self.next()?;
 
-                    Statement::new(
-                        StatementNode::Break,
-                        position
-                    )
+                    if self.current_lexeme() == "@" {
+                        self.next()?;
+
+                        let label = self.eat_type(&TokenType::Identifier)?;
+
+                        let value = if self.current_lexeme() == "\n" || self.remaining() == 0 {
+                            None
+                        } else {
+                            Some(self.parse_expression()?)
+                        };
+
+                        Statement::new(
+                            StatementNode::BreakLabel(label, value),
+                            self.span_from(position)
+                        )
+                    } else {
+                        Statement::new(
+                            StatementNode::Break,
+                            position
+                        )
+                    }
                 }
 
-                
+                "match" => {
+                    self.next()?;
+
+                    let scrutinee = self.parse_expression()?;
+
+                    self.eat_lexeme(":")?;
+
+                    let pos = self.span_from(position);
+
+                    let (arms, else_) = self.parse_match_arms()?;
+
+                    return Ok(
+                        Statement::new(
+                            StatementNode::Match(scrutinee, arms, else_),
+                            pos
+                        )
+                    )
+                }
 
                 "if" => {
                     self.next()?;
@@ -366,15 +692,10 @@ impl<'p> Parser<'p> {
 
                     let pos = self.span_from(position);
 
-                    let mut no_else = false;
+                    // an inline body can't be followed by `elif` or `else`
+                    let no_else = self.current_lexeme() != "\n";
 
-                    let body = if self.current_lexeme() == "\n" {
-                        self.next()?;
-                        self.parse_body()?
-                    } else {
-                        no_else = true;
-                        vec!(self.parse_statement()?)
-                    };
+                    let body = self.parse_block_or_inline()?;
                     
                     if no_else {
                         return Ok(
@@ -386,7 +707,7 @@ impl<'p> Parser<'p> {
                     } else {
                         let mut else_ = Vec::new();
 
-                        let mut cur = self.current_lexeme();
+                        let mut cur = self.current_lexeme().to_string();
 
                         while ["elif", "else"].contains(&cur.as_str()) {
                             self.next()?;
@@ -394,29 +715,19 @@ impl<'p> Parser<'p> {
                             if cur == "else" {
                                 self.eat_lexeme(":")?;
 
-                                let body = if self.current_lexeme() == "\n" {
-                                    self.next()?;
-                                    self.parse_body()?
-                                } else {
-                                    vec!(self.parse_statement()?)
-                                };
+                                let body = self.parse_block_or_inline()?;
 
                                 else_.push((None, body))
                             } else if cur == "elif" {
                                 let cond = self.parse_expression()?;
                                 self.eat_lexeme(":")?;
 
-                                let body = if self.current_lexeme() == "\n" {
-                                    self.next()?;
-                                    self.parse_body()?
-                                } else {
-                                    vec!(self.parse_statement()?)
-                                };
+                                let body = self.parse_block_or_inline()?;
 
                                 else_.push((Some(cond), body))
                             }
 
-                            cur = self.current_lexeme()
+                            cur = self.current_lexeme().to_string()
                         }
 
                         return Ok(
@@ -439,15 +750,10 @@ impl<'p> Parser<'p> {
 
                     let pos = self.span_from(position);
 
-                    let mut no_else = false;
+                    // an inline body can't be followed by `elif` or `else`
+                    let no_else = self.current_lexeme() != "\n";
 
-                    let body = if self.current_lexeme() == "\n" {
-                        self.next()?;
-                        self.parse_body()?
-                    } else {
-                        no_else = true;
-                        vec!(self.parse_statement()?)
-                    };
+                    let body = self.parse_block_or_inline()?;
                     
                     if no_else {
                         return Ok(
@@ -459,7 +765,7 @@ impl<'p> Parser<'p> {
                     } else {
                         let mut else_ = Vec::new();
 
-                        let mut cur = self.current_lexeme();
+                        let mut cur = self.current_lexeme().to_string();
 
                         while ["elif", "else"].contains(&cur.as_str()) {
                             self.next()?;
@@ -467,29 +773,19 @@ impl<'p> Parser<'p> {
                             if cur == "else" {
                                 self.eat_lexeme(":")?;
 
-                                let body = if self.current_lexeme() == "\n" {
-                                    self.next()?;
-                                    self.parse_body()?
-                                } else {
-                                    vec!(self.parse_statement()?)
-                                };
+                                let body = self.parse_block_or_inline()?;
 
                                 else_.push((None, body))
                             } else if cur == "elif" {
                                 let cond = self.parse_expression()?;
                                 self.eat_lexeme(":")?;
 
-                                let body = if self.current_lexeme() == "\n" {
-                                    self.next()?;
-                                    self.parse_body()?
-                                } else {
-                                    vec!(self.parse_statement()?)
-                                };
+                                let body = self.parse_block_or_inline()?;
 
                                 else_.push((Some(cond), body))
                             }
 
-                            cur = self.current_lexeme()
+                            cur = self.current_lexeme().to_string()
                         }
 
                         return Ok(
@@ -501,6 +797,29 @@ impl<'p> Parser<'p> {
                     }
                 }
 
+                "when" => {
+                    self.next()?;
+
+                    self.eat_lexeme("defined")?;
+                    self.eat_lexeme("(")?;
+
+                    let flag = self.eat_type(&TokenType::Str)?;
+
+                    self.eat_lexeme(")")?;
+                    self.eat_lexeme(":")?;
+
+                    let pos = self.span_from(position);
+
+                    let body = self.parse_block_or_inline()?;
+
+                    return Ok(
+                        Statement::new(
+                            StatementNode::When(flag, body),
+                            pos
+                        )
+                    )
+                }
+
                 _ => {
                     let expression = self.parse_expression()?;
                     let position = expression.pos.clone();
@@ -509,6 +828,30 @@ impl<'p> Parser<'p> {
                 }
             },
 
+            // `check` is only a statement when a condition follows it, anywhere else it's a name
+            Identifier if self.current_lexeme() == "check" && self.is_check() => {
+                self.next()?;
+
+                let cond = self.parse_expression()?;
+
+                Statement::new(StatementNode::Check(cond), self.span_from(position))
+            }
+
+            Identifier if self.command_calls && self.is_command_call() => {
+                let callee = Expression::new(ExpressionNode::Identifier(self.eat()?), position.clone());
+                let arg = self.parse_expression()?;
+
+                let pos = self.span_from(position);
+
+                Statement::new(
+                    StatementNode::Expression(
+                        Expression::new(ExpressionNode::Call(Rc::new(callee), vec!(arg), Vec::new()), pos.clone())
+                    ),
+                    pos
+                )
+            }
+
+            // a line opening with `[` or `{` is a list or dict literal, blocks only ever come indented after a `:`
             _ => {
                 let expression = self.parse_expression()?;
                 let position = expression.pos.clone();
@@ -519,10 +862,7 @@ impl<'p> Parser<'p> {
                     if self.current_lexeme() == "=" {
                         self.next()?;
 
-                        Statement::new(
-                            StatementNode::Assignment(expression, self.parse_expression()?),
-                            position,
-                        )
+                        self.parse_assignment_right(expression, position)?
                     } else {
                         Statement::new(StatementNode::Expression(expression), position)
                     }
@@ -535,38 +875,183 @@ impl<'p> Parser<'p> {
         Ok(statement)
     }
 
+    // the body after a `:`, either an indented block on the next lines or one statement on the same line
+    fn parse_block_or_inline(&mut self) -> Result<Vec<Statement>, ()> {
+        if self.current_lexeme() == "\n" {
+            // blank and comment lines before the block don't say how deep it is
+            self.next_newline()?;
+            self.parse_body()
+        } else {
+            Ok(vec!(self.parse_statement()?))
+        }
+    }
+
+    // the indented `case <literal>:` arms of a `match` and its `else:`, which has to come last
+    fn parse_match_arms(&mut self) -> Result<(Vec<(Expression, Vec<Statement>)>, Option<Vec<Statement>>), ()> {
+        if self.current_lexeme() != "\n" {
+            return Err(response!(
+                Wrong("a `match` takes its `case`s on the lines below it"),
+                self.source.file,
+                self.current_position()
+            ))
+        }
+
+        self.next_newline()?;
+
+        let backup_indent = self.indent;
+        self.indent = self.get_indent();
+
+        if backup_indent >= self.indent {
+            return Err(response!(
+                Wrong("expected an indented `case` after `match`"),
+                self.source.file,
+                self.current_position()
+            ))
+        }
+
+        let mut arms = Vec::new();
+        let mut else_ = None;
+
+        while !self.is_dedent() && self.remaining() > 0 {
+            let position = self.current_position();
+
+            if else_.is_some() {
+                return Err(response!(
+                    Wrong("nothing can come after the `else` of a `match`"),
+                    self.source.file,
+                    position
+                ))
+            }
+
+            match self.current_lexeme() {
+                "case" => {
+                    self.next()?;
+
+                    let pattern = self.parse_expression()?;
+
+                    match pattern.node {
+                        ExpressionNode::Int(_) | ExpressionNode::Float(_) | ExpressionNode::Str(_) | ExpressionNode::Bool(_) => (),
+
+                        _ => return Err(response!(
+                            Wrong("a `case` can only match an int, float, string or bool literal"),
+                            self.source.file,
+                            pattern.pos
+                        )),
+                    }
+
+                    self.eat_lexeme(":")?;
+
+                    arms.push((pattern, self.parse_block_or_inline()?))
+                },
+
+                "else" => {
+                    self.next()?;
+                    self.eat_lexeme(":")?;
+
+                    else_ = Some(self.parse_block_or_inline()?)
+                },
+
+                _ => return Err(response!(
+                    Wrong("expected `case` or `else` inside `match`"),
+                    self.source.file,
+                    position
+                )),
+            }
+
+            self.next_newline()?;
+        }
+
+        self.indent = backup_indent;
+
+        if arms.is_empty() {
+            return Err(response!(
+                Wrong("a `match` needs at least one `case`"),
+                self.source.file,
+                self.current_position()
+            ))
+        }
+
+        Ok((arms, else_))
+    }
+
+    // `fun square(x): x * x` returns what its one expression comes to
+    fn parse_function_body(&mut self) -> Result<Vec<Statement>, ()> {
+        let inline = self.current_lexeme() != "\n";
+
+        let mut body = self.parse_block_or_inline()?;
+
+        if inline {
+            if let StatementNode::Expression(ref expression) = body[0].node {
+                body[0] = Statement::new(StatementNode::Return(Some(expression.clone())), expression.pos.clone())
+            }
+        }
+
+        Ok(body)
+    }
+
+    // `check` followed by what can only start a condition, `check(x)` stays a call
+    fn is_check(&self) -> bool {
+        use self::TokenType::*;
+
+        match self.tokens.get(self.index + 1) {
+            Some(token) => match token.token_type {
+                Str | Interpolation | Char | Int | Float | Bool | Identifier => true,
+                Keyword => token.lexeme == "nil",
+                Operator => token.lexeme == "not",
+                _ => false,
+            },
+
+            None => false,
+        }
+    }
+
+    // an identifier directly followed by something that can only start an argument
+    fn is_command_call(&self) -> bool {
+        use self::TokenType::*;
+
+        match self.tokens.get(self.index + 1) {
+            Some(token) => match token.token_type {
+                Str | Int | Float | Bool | Identifier => true,
+                Keyword => token.lexeme == "nil",
+                _ => false,
+            },
+
+            None => false,
+        }
+    }
+
     fn try_parse_compound(&mut self, left: &Expression) -> Result<Option<Statement>, ()> {
+        // `x &&= y` is `x = x and y`, which never gets to `y` once `x` is false, `||=` likewise with `or`
+        let logical = match self.current_lexeme() {
+            "&&=" => Some(Operator::And),
+            "||=" => Some(Operator::Or),
+            _ => None,
+        };
+
+        if let Some(op) = logical {
+            self.next()?;
+
+            return Ok(Some(self.parse_compound_right(left, op)?))
+        }
+
         if self.current_type() != TokenType::Operator {
             return Ok(None)
         }
 
         let backup_index = self.index;
+        let op_position = self.current_position();
 
         let c = self.eat_type(&TokenType::Operator)?;
 
         let mut result = None;
 
         if self::Operator::is_compoundable(&c) {
-            let op = self::Operator::from_str(&c).unwrap().0;
-
-            let position = self.current_position();
+            let op = self.binary_operator(&c, &op_position)?.0;
 
             if self.current_lexeme() == "=" {
                 self.next()?;
 
-                let right = self.parse_expression()?;
-                let ass = Statement::new(
-                    StatementNode::Assignment(
-                        left.clone(),
-                        Expression::new(
-                            ExpressionNode::Binary(Rc::new(left.clone()), op, Rc::new(right)),
-                            self.span_from(position.clone()),
-                        ),
-                    ),
-                    self.span_from(position),
-                );
-
-                result = Some(ass)
+                result = Some(self.parse_compound_right(left, op)?)
             } else {
                 self.index = backup_index
             }
@@ -575,6 +1060,59 @@ impl<'p> Parser<'p> {
         Ok(result)
     }
 
+    // `left = right` from just past the `=`, a chain `a = b = c` binds `c` once and assigns it right to left
+    fn parse_assignment_right(&mut self, left: Expression, position: Pos) -> Result<Statement, ()> {
+        let mut targets = vec!(left);
+        let mut right = self.parse_expression()?;
+
+        while self.current_lexeme() == "=" {
+            self.next()?;
+
+            targets.push(right);
+            right = self.parse_expression()?
+        }
+
+        if targets.len() == 1 {
+            return Ok(Statement::new(StatementNode::Assignment(targets.pop().unwrap(), right), position))
+        }
+
+        // where the chain starts, so a chain in a chain's block can't take the same name
+        let name = format!("$chain-{}-{}", (position.0).0, (position.1).0);
+        let pos = self.span_from(position);
+
+        let value = Expression::new(ExpressionNode::Identifier(name.clone()), right.pos.clone());
+
+        let mut body = vec!(
+            Statement::new(StatementNode::Declaration(name, None, Some(right)), pos.clone())
+        );
+
+        for target in targets.into_iter().rev() {
+            let target_pos = target.pos.clone();
+
+            body.push(Statement::new(StatementNode::Assignment(target, value.clone()), target_pos))
+        }
+
+        Ok(Statement::new(StatementNode::Block(body), pos))
+    }
+
+    // `left op= right` from just past the `=`, as `left = left op right`
+    fn parse_compound_right(&mut self, left: &Expression, op: Operator) -> Result<Statement, ()> {
+        let position = self.current_position();
+
+        let right = self.parse_expression()?;
+
+        Ok(Statement::new(
+            StatementNode::Assignment(
+                left.clone(),
+                Expression::new(
+                    ExpressionNode::Binary(Rc::new(left.clone()), op, Rc::new(right)),
+                    self.span_from(position.clone()),
+                ),
+            ),
+            self.span_from(position),
+        ))
+    }
+
     fn parse_body(&mut self) -> Result<Vec<Statement>, ()> {
         let backup_indent = self.indent;
         self.indent = self.get_indent();
@@ -591,29 +1129,120 @@ impl<'p> Parser<'p> {
             }
         }
 
-        let mut stack = Vec::new();
+        let mut stack = Vec::new();
+
+        while !self.is_dedent() && self.remaining() > 0 {
+            let statement = self.parse_statement()?;
+
+            self.next_newline()?;
+
+            stack.push(statement)
+        }
+
+        self.indent = backup_indent;
+
+        Ok(stack)
+    }
+
+    fn parse_expression(&mut self) -> Result<Expression, ()> {
+        let expression = self.parse_operation()?;
+
+        if self.is_keyword("if") {
+            self.parse_conditional(expression)
+        } else {
+            Ok(expression)
+        }
+    }
+
+    // an expression short of `a if c else b`, the condition of one being such
+    fn parse_operation(&mut self) -> Result<Expression, ()> {
+        let atom = self.parse_atom()?;
+
+        if self.current_type() == TokenType::Operator || self.is_range() {
+            self.parse_binary(atom, 0)
+        } else {
+            Ok(atom)
+        }
+    }
+
+    // `a if c else b` binds looser than any operator, so `a + 1 if c else b - 1` adds on both sides,
+    // and what comes after the `else` may be another one
+    fn parse_conditional(&mut self, then: Expression) -> Result<Expression, ()> {
+        let position = then.pos.clone();
+
+        self.next()?;
+
+        let cond = self.parse_operation()?;
+
+        if !self.is_keyword("else") {
+            return Err(response!(
+                Wrong("a conditional expression needs an `else`, as in `a if c else b`"),
+                self.source.file,
+                self.span_from(position)
+            ))
+        }
+
+        self.next()?;
+
+        let otherwise = self.parse_expression()?;
 
-        while !self.is_dedent() && self.remaining() > 0 {
-            let statement = self.parse_statement()?;
+        Ok(Expression::new(
+            ExpressionNode::Conditional(Rc::new(cond), Rc::new(then), Rc::new(otherwise)),
+            self.span_from(position)
+        ))
+    }
 
-            self.next_newline()?;
+    // `"a ${b} c"` is `"a " ++ b ++ " c"`, the literal gets split into its pieces again to get at the expressions
+    fn parse_interpolation(&mut self) -> Result<Expression, ()> {
+        let token = self.current().clone();
+        let position = self.current_position();
 
-            stack.push(statement)
+        self.next()?;
+
+        let start = (token.line.0, token.slice.0 - 1);
+
+        let mut tokenizer = Tokenizer::new(format!("\"{}\"", token.lexeme).chars().collect(), self.source);
+
+        tokenizer.pos = start;
+        tokenizer.advance();
+
+        let mut parts = Vec::new();
+
+        for piece in StringLiteralMatcher::pieces(&mut tokenizer, false, start)? {
+            parts.push(match piece {
+                Piece::Text(text) => Expression::new(ExpressionNode::Str(text), position.clone()),
+                Piece::Code(code, column) => self.parse_interpolated(&code, (token.line.0, column))?,
+            })
         }
 
-        self.indent = backup_indent;
+        // starting from a string keeps `++` typing the whole thing as one when it opens with an expression
+        let first = match parts[0].node {
+            ExpressionNode::Str(_) => parts.remove(0),
+            _ => Expression::new(ExpressionNode::Str(String::new()), position.clone()),
+        };
 
-        Ok(stack)
+        Ok(parts.into_iter().fold(first, |left, right| {
+            Expression::new(
+                ExpressionNode::Binary(Rc::new(left), Operator::Concat, Rc::new(right)),
+                position.clone()
+            )
+        }))
     }
 
-    fn parse_expression(&mut self) -> Result<Expression, ()> {
-        let atom = self.parse_atom()?;
+    fn parse_interpolated(&self, code: &str, pos: (usize, usize)) -> Result<Expression, ()> {
+        let tokens = Lexer::default(code.chars().collect(), self.source)
+            .with_operators(&self.operators)
+            .starting_at(pos)
+            .collect::<Result<Vec<Token>, ()>>()?;
 
-        if self.current_type() == TokenType::Operator {
-            self.parse_binary(atom, 0)
-        } else {
-            Ok(atom)
-        }
+        let mut parser = Parser::new(tokens, self.source);
+        parser.set_operators(&self.operators);
+
+        let expression = parser.parse_expression()?;
+
+        parser.expect_eof()?;
+
+        Ok(expression)
     }
 
     fn parse_atom(&mut self) -> Result<Expression, ()> {
@@ -629,37 +1258,72 @@ impl<'p> Parser<'p> {
             let position = self.current_position();
 
             let expression = match token_type {
-                Int => Expression::new(
-                    ExpressionNode::Int(self.eat()?.parse::<i32>().unwrap()),
-                    position,
-                ),
+                Int => {
+                    let n = match self.eat()?.parse::<i64>() {
+                        Ok(n) => n,
 
-                Float => Expression::new(
-                    ExpressionNode::Float(self.eat()?.parse::<f64>().unwrap()),
-                    position,
-                ),
+                        Err(_) => return Err(response!(
+                            Wrong("invalid integer literal"),
+                            self.source.file,
+                            position
+                        )),
+                    };
+
+                    let literal = match self.number_suffix()?.as_ref().map(String::as_str) {
+                        Some("f") => Expression::new(ExpressionNode::Float(n as f64), self.span_from(position)),
+                        _ => Expression::new(ExpressionNode::Int(n), self.span_from(position)),
+                    };
+
+                    self.negated_power(literal)?
+                },
+
+                Float => {
+                    let n = self.eat()?.parse::<f64>().unwrap();
+
+                    if self.number_suffix()? == Some("i".to_string()) {
+                        return Err(response!(
+                            Wrong(format!("`{}` has a fraction, it can't be an int", n)),
+                            self.source.file,
+                            self.span_from(position)
+                        ))
+                    }
+
+                    let literal = Expression::new(ExpressionNode::Float(n), self.span_from(position));
+
+                    self.negated_power(literal)?
+                },
 
                 Str => Expression::new(ExpressionNode::Str(self.eat()?), position),
 
+                Interpolation => self.parse_interpolation()?,
+
+                Char => Expression::new(ExpressionNode::Char(self.eat()?.chars().next().unwrap()), position),
+
                 Bool => Expression::new(ExpressionNode::Bool(self.eat()? == "true"), position),
 
                 Identifier => Expression::new(ExpressionNode::Identifier(self.eat()?), position),
 
-                Operator => match self.current_lexeme().as_str() {
+                Operator => match self.current_lexeme() {
+                    // `-` takes only what `^` and `.` bind, `-x ^ 2` is `-(x ^ 2)` and `-x + 1` is `(-x) + 1`
                     "-" => {
                         self.next()?;
 
+                        let operand = self.parse_atom()?;
+
                         Expression::new(
-                            ExpressionNode::Neg(Rc::new(self.parse_expression()?)),
+                            ExpressionNode::Neg(Rc::new(self.parse_binary(operand, UNARY_MINUS_PREC)?)),
                             self.span_from(position),
                         )
                     }
 
+                    // `not` takes comparisons and everything tighter, `not a and b` is `(not a) and b`
                     "not" => {
                         self.next()?;
 
+                        let operand = self.parse_atom()?;
+
                         Expression::new(
-                            ExpressionNode::Not(Rc::new(self.parse_expression()?)),
+                            ExpressionNode::Not(Rc::new(self.parse_binary(operand, UNARY_NOT_PREC)?)),
                             self.span_from(position),
                         )
                     }
@@ -673,7 +1337,7 @@ impl<'p> Parser<'p> {
                     }
                 },
 
-                Symbol => match self.current_lexeme().as_str() {
+                Symbol => match self.current_lexeme() {
                     "(" => {
                         self.next()?;
                         self.next_newline()?;
@@ -686,9 +1350,32 @@ impl<'p> Parser<'p> {
                         } else {
                             let expression = self.parse_expression()?;
 
-                            self.eat_lexeme(")")?;
+                            self.next_newline()?;
+
+                            // a comma makes it a tuple, `(a,)` being the one holding only `a`
+                            if self.current_lexeme() == "," {
+                                let mut content = vec!(expression);
+
+                                while self.current_lexeme() == "," {
+                                    self.next()?;
+                                    self.next_newline()?;
+
+                                    if self.current_lexeme() == ")" {
+                                        break
+                                    }
+
+                                    content.push(self.parse_expression()?);
+                                    self.next_newline()?;
+                                }
 
-                            expression
+                                self.eat_lexeme(")")?;
+
+                                Expression::new(ExpressionNode::Tuple(content), self.span_from(position))
+                            } else {
+                                self.eat_lexeme(")")?;
+
+                                expression
+                            }
                         }
                     }
 
@@ -703,6 +1390,29 @@ impl<'p> Parser<'p> {
                         expr
                     },
 
+                    "@" => {
+                        self.next()?;
+
+                        let label = self.eat_type(&TokenType::Identifier)?;
+
+                        self.eat_lexeme("do")?;
+                        self.eat_lexeme(":")?;
+
+                        let body = self.parse_block_or_inline()?;
+
+                        // the block ate the newline ending the statement around it, that one still needs it
+                        if self.tokens[self.index - 1].lexeme == "\n" {
+                            self.index -= 1
+                        }
+
+                        return Ok(
+                            Expression::new(
+                                ExpressionNode::Labeled(label, body),
+                                self.span_from(position)
+                            )
+                        )
+                    },
+
                     "{" => {
                         let args =
                                 self.parse_block_of(("{", "}"), &Self::_parse_definition_comma)?;
@@ -722,11 +1432,15 @@ impl<'p> Parser<'p> {
                     }
                 },
 
-                Keyword => match self.current_lexeme().as_str() {
-                    "nil" => Expression::new(
-                        ExpressionNode::Nil,
-                        position
-                    ),
+                Keyword => match self.current_lexeme() {
+                    "nil" => {
+                        self.next()?;
+
+                        Expression::new(
+                            ExpressionNode::Nil,
+                            position
+                        )
+                    },
 
                     "fun" => {
                         self.next()?;
@@ -754,12 +1468,7 @@ impl<'p> Parser<'p> {
                         self.eat_lexeme(")")?;
                         self.eat_lexeme(":")?;
     
-                        let body = if self.current_lexeme() == "\n" {
-                            self.next()?;
-                            self.parse_body()?
-                        } else {
-                            vec!(self.parse_statement()?)
-                        };
+                        let body = self.parse_function_body()?;
     
                         return Ok(
                             Expression::new(
@@ -797,6 +1506,23 @@ impl<'p> Parser<'p> {
         }
     }
 
+    // the lexer reads `-2` as one literal, under `^` it still has to mean `-(2 ^ ...)` like `-x ^ 2` does
+    fn negated_power(&mut self, literal: Expression) -> Result<Expression, ()> {
+        if self.remaining() == 0 || self.current_lexeme() != "^" {
+            return Ok(literal)
+        }
+
+        let positive = match literal.node {
+            ExpressionNode::Int(n) if n < 0 => ExpressionNode::Int(-n),
+            ExpressionNode::Float(n) if n < 0.0 => ExpressionNode::Float(-n),
+            _ => return Ok(literal),
+        };
+
+        let power = self.parse_binary(Expression::new(positive, literal.pos.clone()), UNARY_MINUS_PREC)?;
+
+        Ok(Expression::new(ExpressionNode::Neg(Rc::new(power)), self.span_from(literal.pos)))
+    }
+
     fn parse_postfix(&mut self, expression: Expression) -> Result<Expression, ()> {
         let backup_index = self.index;
 
@@ -805,24 +1531,38 @@ impl<'p> Parser<'p> {
         }
 
         match self.current_type() {
-            TokenType::Symbol => match self.current_lexeme().as_str() {
+            TokenType::Symbol => match self.current_lexeme() {
                 "(" => {
                     self.next()?;
                     self.next_newline()?;
 
                     let mut args = Vec::new();
+                    let mut keywords = Vec::new();
+
+                    while !["\n", ")"].contains(&self.current_lexeme()) {
+                        // `name = value` passes by name, the visitor puts it where that param is
+                        if self.current_lexeme() == "..." && keywords.is_empty() {
+                            args.push(self.parse_spread()?)
+                        } else if self.is_keyword_arg() {
+                            let name = self.eat()?;
+                            self.next()?;
 
-                    if ![TokenType::Operator, TokenType::Keyword].contains(&self.current_type())
-                    {
-                        while !["\n", ")"].contains(&self.current_lexeme().as_str()) {
-                            args.push(self.parse_expression()?);
+                            keywords.push((name, self.parse_expression()?))
+                        } else if keywords.is_empty() {
+                            args.push(self.parse_expression()?)
+                        } else {
+                            return Err(response!(
+                                Wrong("positional arguments go before keyword arguments"),
+                                self.source.file,
+                                self.current_position()
+                            ))
+                        }
 
-                            if !["\n", ")"].contains(&self.current_lexeme().as_str())
-                                && self.remaining() > 0
-                            {
-                                self.eat_lexeme(",")?;
-                                self.next_newline()?;
-                            }
+                        if !["\n", ")"].contains(&self.current_lexeme())
+                            && self.remaining() > 0
+                        {
+                            self.eat_lexeme(",")?;
+                            self.next_newline()?;
                         }
                     }
 
@@ -832,7 +1572,7 @@ impl<'p> Parser<'p> {
                     let position = expression.pos.clone();
 
                     let call = Expression::new(
-                        ExpressionNode::Call(Rc::new(expression), args),
+                        ExpressionNode::Call(Rc::new(expression), args, keywords),
                         self.span_from(position),
                     );
 
@@ -842,7 +1582,35 @@ impl<'p> Parser<'p> {
                 "[" => {
                     self.next()?;
 
-                    let expr = self.parse_expression()?;
+                    let start = if self.current_lexeme() == ":" {
+                        None
+                    } else {
+                        Some(self.parse_expression()?)
+                    };
+
+                    // a colon makes it a slice, `xs[a:b]`, `xs[:b]`, `xs[a:]` or `xs[:]`
+                    if self.current_lexeme() == ":" {
+                        self.next()?;
+
+                        let end = if self.current_lexeme() == "]" {
+                            None
+                        } else {
+                            Some(Rc::new(self.parse_expression()?))
+                        };
+
+                        self.eat_lexeme("]")?;
+
+                        let position = expression.pos.clone();
+
+                        let slice = Expression::new(
+                            ExpressionNode::Slice(Rc::new(expression), start.map(Rc::new), end),
+                            self.span_from(position),
+                        );
+
+                        return self.parse_postfix(slice)
+                    }
+
+                    let expr = start.unwrap();
 
                     self.eat_lexeme("]")?;
 
@@ -872,13 +1640,42 @@ impl<'p> Parser<'p> {
 
                     self.parse_postfix(index)
                 }
+
+                "?" => {
+                    self.next()?;
+
+                    let key = if self.current_lexeme() == "[" {
+                        self.next()?;
+
+                        let key = self.parse_expression()?;
+
+                        self.eat_lexeme("]")?;
+
+                        key
+                    } else {
+                        self.eat_lexeme(".")?;
+
+                        let position = self.current_position();
+
+                        Expression::new(ExpressionNode::Str(self.eat()?), position)
+                    };
+
+                    let position = expression.pos.clone();
+
+                    let index = Expression::new(
+                        ExpressionNode::OptionalIndex(Rc::new(expression), Rc::new(key)),
+                        self.span_from(position),
+                    );
+
+                    self.parse_postfix(index)
+                }
                 
                 _ => {
                     Ok(expression)
                 }
             },
 
-            TokenType::Keyword => match self.current_lexeme().as_str() {
+            TokenType::Keyword => match self.current_lexeme() {
                 "with" => {
                     self.next()?;
 
@@ -906,16 +1703,20 @@ impl<'p> Parser<'p> {
         let mut left = left;
         let left_position = left.pos.clone();
 
+        let is_equality = |op: &Operator| *op == Operator::Eq || *op == Operator::NEq;
+        let mut last: Option<Operator> = None; // what `left` was built with, a parenthesized one isn't
+
         while self.current_type() == TokenType::Operator {
-            let index_backup = self.index;
-            let operator = Operator::from_str(self.eat()?.as_str()).unwrap();
+            let position = self.current_position();
+            let operator = self.binary_operator(self.current_lexeme(), &position)?;
 
+            // looser than what's being climbed, the caller gets to it
             if operator.1 < min_prec as u8 {
-                println!("we've reached a bruh moment: {:#?} @ {} {}", operator.0, operator.1, min_prec);
-                self.index = index_backup;
                 break
             }
 
+            self.next()?;
+
             let prec = if !operator.0.is_right_ass() {
                 operator.1 + 1
             } else {
@@ -925,6 +1726,17 @@ impl<'p> Parser<'p> {
             let mut right = self.parse_atom()?;
             right = self.parse_binary(right, prec as usize)?;
 
+            // unlike `<`, `a == b == c` would compare `c` to whether `a == b`
+            if is_equality(&operator.0) && last.as_ref().map_or(false, is_equality) {
+                response!(
+                    Weird("chained equality compares the last value to `true` or `false`, `a == b and b == c` is probably what's meant"),
+                    self.source.file,
+                    self.span_from(left_position.clone())
+                )
+            }
+
+            last = Some(operator.0.clone());
+
             left = Expression::new(
                 ExpressionNode::Binary(
                     Rc::new(left),
@@ -935,14 +1747,168 @@ impl<'p> Parser<'p> {
             );
         }
 
-        println!("next: {}", self.current_lexeme());
+        // ranges bind looser than any operator, `0..n - 1` goes up to `n - 1`
+        if min_prec == 0 && self.is_range() {
+            let inclusive = self.eat()? == "..=";
+
+            let end = self.parse_atom()?;
+            let end = self.parse_binary(end, 1)?;
+
+            if self.is_range() {
+                return Err(response!(
+                    Wrong("can't chain ranges, a range only has a start and an end"),
+                    self.source.file,
+                    self.current_position()
+                ))
+            }
+
+            let node = if inclusive {
+                ExpressionNode::RangeInclusive(Rc::new(left), Rc::new(end))
+            } else {
+                ExpressionNode::Range(Rc::new(left), Rc::new(end))
+            };
+
+            left = Expression::new(node, self.span_from(left_position))
+        }
 
         Ok(left)
     }
 
+    // `a`, `a: int`, and either of those after `const` when the body can't reassign it
+    fn parse_param(&mut self) -> Result<(String, Option<TypeNode>, bool), ()> {
+        let constant = self.current_lexeme() == "const";
+
+        if constant {
+            self.next()?
+        }
+
+        let name = self.eat_type(&TokenType::Identifier)?;
+
+        if self.current_lexeme() == ":" {
+            self.next()?;
+
+            Ok((name, Some(self.parse_type()?), constant))
+        } else {
+            Ok((name, None, constant))
+        }
+    }
+
+    // a type name, or `Array(T)`, `Dict(T)` and `Func(T, ..) -> R` around more of them
+    fn parse_type(&mut self) -> Result<TypeNode, ()> {
+        let position = self.current_position();
+        let name = self.eat_type(&TokenType::Identifier)?;
+
+        if !["Array", "Dict", "Func"].contains(&name.as_str()) {
+            return match TypeNode::from_name(&name) {
+                Some(t) => Ok(t),
+
+                None => Err(response!(
+                    Wrong(format!(
+                        "unknown type `{}`, expected one of `int`, `float`, `str`, `bool`, `char`, `any`, `Array`, `Dict` or `Func`",
+                        name
+                    )),
+                    self.source.file,
+                    position
+                )),
+            }
+        }
+
+        self.eat_lexeme("(")?;
+
+        let mut params = Vec::new();
+
+        while self.current_lexeme() != ")" {
+            params.push(self.parse_type()?);
+
+            if self.current_lexeme() == "," {
+                self.next()?
+            } else {
+                break
+            }
+        }
+
+        self.eat_lexeme(")")?;
+
+        match name.as_str() {
+            "Func" => {
+                // function types only go by arity, so the return type is checked and dropped
+                if self.current_lexeme() == "-" {
+                    self.next()?;
+                    self.eat_lexeme(">")?;
+                    self.parse_type()?;
+                }
+
+                Ok(TypeNode::Func(params.len()))
+            },
+
+            _ if params.len() != 1 => Err(response!(
+                Wrong(format!("`{}` takes exactly one type, like `{}(int)`, but got {}", name, name, params.len())),
+                self.source.file,
+                self.span_from(position)
+            )),
+
+            "Array" => Ok(TypeNode::Array(Box::new(params.pop().unwrap()))),
+            _ => Ok(TypeNode::DictOf(Box::new(params.pop().unwrap()))),
+        }
+    }
+
+    // `5f` is a float and `5i` an int, the suffix lexes as a name right up against the number
+    fn number_suffix(&mut self) -> Result<Option<String>, ()> {
+        if self.remaining() == 0 || self.current_type() != TokenType::Identifier {
+            return Ok(None)
+        }
+
+        let number = &self.tokens[self.index - 1];
+        let suffix = self.current();
+
+        if suffix.line.0 != number.line.0 || suffix.slice.0 != number.slice.1 + 1 {
+            return Ok(None)
+        }
+
+        if !["i", "f"].contains(&suffix.lexeme.as_str()) {
+            return Err(response!(
+                Wrong(format!("unknown number suffix `{}`, only `i` and `f` go there", suffix.lexeme)),
+                self.source.file,
+                self.current_position()
+            ))
+        }
+
+        self.eat().map(Some)
+    }
+
+    fn is_keyword_arg(&self) -> bool {
+        self.remaining() > 1
+            && self.current_type() == TokenType::Identifier
+            && self.tokens[self.index + 1].lexeme == "="
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.remaining() > 0 && self.current_type() == TokenType::Keyword && self.current_lexeme() == keyword
+    }
+
+    fn is_range(&self) -> bool {
+        self.remaining() > 0 && self.current_type() == TokenType::Symbol && ["..", "..="].contains(&self.current_lexeme())
+    }
+
+    fn binary_operator(&self, lexeme: &str, pos: &Pos) -> Result<(Operator, u8), ()> {
+        let custom = || self.operators.iter()
+            .find(|(symbol, _)| symbol == lexeme)
+            .map(|(symbol, prec)| (Operator::Custom(symbol.clone()), *prec));
+
+        match Operator::from_str(lexeme).or_else(custom) {
+            Some(operator) => Ok(operator),
+
+            None => Err(response!(
+                Wrong(format!("`{}` can't be used between two values", lexeme)),
+                self.source.file,
+                pos
+            )),
+        }
+    }
+
     fn new_line(&mut self) -> Result<(), ()> {
         if self.remaining() > 0 {
-            match self.current_lexeme().as_str() {
+            match self.current_lexeme() {
                 "\n" => self.next(),
                 _ => {
                     Err(response!(
@@ -972,12 +1938,61 @@ impl<'p> Parser<'p> {
         self.current().slice.0 - 1
     }
 
-    fn is_dedent(&self) -> bool {
-        self.get_indent() < self.indent && self.current_lexeme() != "\n"
+    fn is_dedent(&mut self) -> bool {
+        let dedent = self.get_indent() < self.indent && self.current_lexeme() != "\n";
+
+        if dedent {
+            let index = self.index;
+
+            if let Some(entry) = self.indents.as_mut().and_then(|indents| indents.get_mut(index)) {
+                entry.1 = true
+            }
+        }
+
+        dedent
+    }
+
+    // line, column, block depth and lexeme of every token as the last parse saw them, `<` marks where
+    // `is_dedent` closed a block, empty unless `set_trace_indents` was on for that parse
+    pub fn dump_indents(&self) -> String {
+        use std::fmt::Write;
+
+        let mut dump = String::new();
+
+        let indents = match self.indents {
+            Some(ref indents) => indents,
+            None => return dump,
+        };
+
+        for (token, &(indent, dedent)) in self.tokens.iter().zip(indents.iter()) {
+            if token.lexeme == "\n" {
+                continue
+            }
+
+            let depth = if self.indent_standard == 0 { 0 } else { indent / self.indent_standard };
+
+            writeln!(
+                dump,
+                "{:>4}:{:<3} {:>2} {} {:?}",
+                token.line.0,
+                token.slice.0 - 1,
+                depth,
+                if dedent { "<" } else { " " },
+                token.lexeme
+            ).unwrap();
+        }
+
+        dump
     }
 
     fn next(&mut self) -> Result<(), ()> {
         if self.index <= self.tokens.len() {
+            let (index, indent) = (self.index, self.indent);
+
+            if let Some(entry) = self.indents.as_mut().and_then(|indents| indents.get_mut(index)) {
+                entry.0 = indent
+            }
+
             self.index += 1;
 
             Ok(())
@@ -1017,16 +2032,17 @@ impl<'p> Parser<'p> {
         )
     }
 
-    fn current(&self) -> Token {
+    // borrowed, a token carries its whole source line and cloning it per peek adds up on long lines
+    fn current(&self) -> &Token {
         if self.index > self.tokens.len() - 1 {
-            self.tokens[self.tokens.len() - 1].clone()
+            &self.tokens[self.tokens.len() - 1]
         } else {
-            self.tokens[self.index].clone()
+            &self.tokens[self.index]
         }
     }
 
     fn eat(&mut self) -> Result<String, ()> {
-        let lexeme = self.current().lexeme;
+        let lexeme = self.current().lexeme.clone();
         self.next()?;
 
         Ok(lexeme)
@@ -1034,7 +2050,7 @@ impl<'p> Parser<'p> {
 
     fn eat_lexeme(&mut self, lexeme: &str) -> Result<String, ()> {
         if self.current_lexeme() == lexeme {
-            let lexeme = self.current().lexeme;
+            let lexeme = self.current().lexeme.clone();
             self.next()?;
 
             Ok(lexeme)
@@ -1070,12 +2086,12 @@ impl<'p> Parser<'p> {
         }
     }
 
-    fn current_lexeme(&self) -> String {
-        self.current().lexeme.clone()
+    fn current_lexeme(&self) -> &str {
+        &self.current().lexeme
     }
 
     fn current_type(&self) -> TokenType {
-        self.current().token_type
+        self.current().token_type.clone()
     }
 
     fn expect_type(&self, token_type: TokenType) -> Result<(), ()> {
@@ -1132,7 +2148,11 @@ impl<'p> Parser<'p> {
             self.next()?
         }
 
-        let expression = Self::_parse_expression(self);
+        let expression = if self.remaining() > 0 && self.current_lexeme() == "..." {
+            self.parse_spread().map(Some)
+        } else {
+            Self::_parse_expression(self)
+        };
 
         if self.remaining() > 0 && self.current_lexeme() == "\n" {
             self.next()?
@@ -1149,7 +2169,7 @@ impl<'p> Parser<'p> {
         expression
     }
 
-    fn _parse_definition_comma(self: &mut Self) -> Result<Option<(String, Expression)>, ()> {
+    fn _parse_definition_comma(self: &mut Self) -> Result<Option<DictEntry>, ()> {
         if self.remaining() > 0 && self.current_lexeme() == "\n" {
             self.next()?
         }
@@ -1160,18 +2180,26 @@ impl<'p> Parser<'p> {
 
         let position = self.current_position();
 
-        let name = self.eat_type(&TokenType::Identifier)?;
+        let param = if self.current_lexeme() == "..." {
+            self.eat_lexeme("...")?;
+
+            let mut source = self.parse_expression()?;
+            source.pos = self.span_from(position);
 
-        self.eat_lexeme(":")?;
+            Some(DictEntry::Spread(source))
+        } else {
+            let name = self.eat_type(&TokenType::Identifier)?;
 
-        let mut value = self.parse_expression()?;
+            self.eat_lexeme(":")?;
 
-        value.pos = position;
+            let mut value = self.parse_expression()?;
+            value.pos = position;
 
-        let param = Some((name, value));
+            Some(DictEntry::Field(name, value))
+        };
 
         if self.remaining() > 0 {
-            if ![",", "\n"].contains(&self.current_lexeme().as_str()) {
+            if ![",", "\n"].contains(&self.current_lexeme()) {
                 return Err(response!(
                     Wrong(format!(
                         "expected `,` or newline, found `{}`",
@@ -1194,6 +2222,20 @@ impl<'p> Parser<'p> {
 
 
 
+    // `...expr`, the collection gets copied into the literal or call around it
+    fn parse_spread(&mut self) -> Result<Expression, ()> {
+        let position = self.current_position();
+
+        self.eat_lexeme("...")?;
+
+        let expression = self.parse_expression()?;
+
+        Ok(Expression::new(
+            ExpressionNode::Spread(Rc::new(expression)),
+            self.span_from(position)
+        ))
+    }
+
     fn parse_block_of<B>(
         &mut self,
         delimeters: (&str, &str),
@@ -1222,7 +2264,7 @@ impl<'p> Parser<'p> {
             if nest_count == 0 {
                 break;
             } else {
-                block_tokens.push(self.current());
+                block_tokens.push(self.current().clone());
 
                 self.next()?;
             }
@@ -1232,6 +2274,8 @@ impl<'p> Parser<'p> {
 
         if !block_tokens.is_empty() {
             let mut parser = Parser::new(block_tokens, self.source);
+            parser.set_operators(&self.operators);
+
             let mut block = Vec::new();
 
             while let Some(element) = parse_with(&mut parser)? {
@@ -0,0 +1,480 @@
+use super::super::error::Response;
+use super::*;
+use super::super::testing::*;
+
+#[test]
+fn no_pos_ignores_whitespace() {
+    let spaced = parse("let x =   1+2\n\nfun f( a ):\n  return a\n").unwrap();
+    let tight = parse("let x = 1 + 2\nfun f(a):\n    return a\n").unwrap();
+
+    assert_ne!(format!("{:?}", spaced), format!("{:?}", tight));
+    assert_eq!(format!("{:?}", NoPos(&spaced)), format!("{:?}", NoPos(&tight)));
+    assert_eq!(format!("{:#?}", NoPos(&spaced)), format!("{:#?}", NoPos(&tight)));
+}
+
+#[test]
+fn no_pos_still_tells_different_programs_apart() {
+    let a = parse("let x = 1 + 2\n").unwrap();
+    let b = parse("let x = 1 - 2\n").unwrap();
+
+    assert_ne!(format!("{:?}", NoPos(&a)), format!("{:?}", NoPos(&b)));
+    assert_eq!(format!("{:?}", NoPos(&Statement::let_("y", Expression::int(1)))), "Declaration(\"y\", None, Some(Int(1)))");
+}
+
+// the parser `parse` would use, for the settings `parse` doesn't take
+fn parser(code: &str) -> Parser<'static> {
+    let source = source(code);
+    let tokens = Lexer::default(code.chars().collect(), source).collect::<Result<Vec<Token>, ()>>().unwrap();
+
+    Parser::new(tokens, source)
+}
+
+#[test]
+fn dump_indents_reports_where_blocks_end() {
+    let mut parser = parser("fun f(a):\n    if a:\n        print(1)\n    print(2)\nf(true)\n");
+
+    parser.set_trace_indents(true);
+    parser.parse().unwrap();
+
+    // the first token of each line, with its depth and whether a block ended before it
+    let mut firsts = Vec::new();
+    let mut line = "";
+
+    for fields in parser.dump_indents().lines().map(|line| line.split_whitespace().collect::<Vec<&str>>()) {
+        let at = fields[0].split(':').next().unwrap();
+
+        if at != line {
+            line = at;
+            firsts.push(fields[1 ..].join(" "))
+        }
+    }
+
+    assert_eq!(firsts, vec!["0 \"fun\"", "1 \"if\"", "2 \"print\"", "1 < \"print\"", "0 < \"f\""]);
+}
+
+#[test]
+fn dump_indents_is_empty_without_tracing() {
+    let mut parser = parser("if true:\n    print(1)\n");
+
+    parser.parse().unwrap();
+
+    assert_eq!(parser.dump_indents(), "");
+}
+
+#[test]
+fn built_trees_compile_and_run() {
+    let ast = vec!(
+        Statement::function("double", &["a"], vec!(
+            Statement::return_(Some(Expression::binary(Expression::identifier("a"), Operator::Mul, Expression::identifier("a")))),
+        )),
+        Statement::let_("x", Expression::call(Expression::identifier("double"), vec!(Expression::int(4)))),
+        Statement::expression(Expression::call(Expression::identifier("print"), vec!(Expression::identifier("x")))),
+        Statement::expression(Expression::call(
+            Expression::identifier("print"),
+            vec!(Expression::dict(vec!(("k", Expression::array(vec!(Expression::bool(true), Expression::str("s"))))))),
+        )),
+    );
+
+    assert_eq!(run_ast(&ast).unwrap(), vec!["16", "{k: [true, \"s\"]}"]);
+}
+
+#[test]
+fn built_trees_parse_the_same_as_their_source() {
+    let built = vec!(Statement::let_("y", Expression::binary(Expression::int(1), Operator::Add, Expression::float(2.5))));
+
+    assert_eq!(format!("{:?}", NoPos(&built)), format!("{:?}", NoPos(&parse("let y = 1 + 2.5\n").unwrap())));
+}
+
+#[test]
+fn every_operator_the_lexer_knows_has_a_precedence() {
+    assert!(Parser::untabled_operators().is_empty(), "{:?}", Parser::untabled_operators());
+}
+
+#[test]
+fn untabled_operators_are_errors_rather_than_panics() {
+    let code = "print(1 + 2)\n";
+    let mut tokens = lex(code).unwrap();
+
+    let plus = tokens.iter_mut().find(|token| token.lexeme == "+").unwrap();
+    plus.lexeme = "<=>".to_string();
+
+    let (result, reported) = reported(|| Parser::new(tokens, source(code)).parse());
+
+    assert!(result.is_err());
+    assert!(reported.contains("`<=>` can't be used between two values"), "{}", reported);
+}
+
+#[test]
+fn recovery_reports_every_broken_statement() {
+    let code = "let = 1\nprint(1)\nlet b = )\nprint(2)\n";
+    let mut parser = parser(code);
+    parser.set_recovery(true);
+
+    let (result, reported) = reported(|| parser.parse());
+
+    assert!(result.is_err());
+    assert_eq!(reported.matches("error").count(), 2, "{}", reported);
+    assert!(reported.contains("let = 1") && reported.contains("let b = )"), "{}", reported);
+
+    assert_eq!(parser.errors.len(), 2);
+
+    for (error, line) in parser.errors.iter().zip(&["let = 1", "let b = )"]) {
+        match *error {
+            Response::Wrong(ref report) => assert!(report.contains(line) && reported.contains(report.as_str()), "{}", report),
+            _ => panic!(),
+        }
+    }
+}
+
+#[test]
+fn partial_parses_keep_the_good_statements() {
+    let mut parser = parser("let = 1\nprint(1)\nprint(2 +)\nprint(2)\n");
+
+    let ast = reported(|| parser.parse_partial()).0;
+
+    assert_eq!(ast.len(), 4);
+    assert_eq!(ast[0].node, StatementNode::Error);
+    assert_eq!(ast[2].node, StatementNode::Error);
+    assert_eq!(parser.errors.len(), 2);
+}
+
+#[test]
+fn dict_spreads_are_their_own_entries() {
+    let ast = parse("let d = {...base, b: 2}\n").unwrap();
+
+    match ast[0].node {
+        StatementNode::Declaration(_, _, Some(ref value)) => match value.node {
+            ExpressionNode::Dict(ref content) => {
+                assert_eq!(content[0], DictEntry::Spread(Expression::new(ExpressionNode::Identifier("base".to_string()), content[0].value().pos.clone())));
+                assert_eq!(content[1], DictEntry::Field("b".to_string(), Expression::new(ExpressionNode::Int(2), content[1].value().pos.clone())));
+            },
+
+            ref node => panic!("{:?}", node),
+        },
+
+        ref node => panic!("{:?}", node),
+    }
+}
+
+#[test]
+fn broken_lets_still_declare_their_name() {
+    let mut parser = parser("let a = )\nprint(a)\n");
+
+    let ast = reported(|| parser.parse_partial()).0;
+
+    assert_eq!(ast.len(), 2);
+    assert_eq!(format!("{:?}", NoPos(&ast[0])), "Declaration(\"a\", None, Some(Error))");
+    assert_eq!(parser.errors.len(), 1);
+
+    // the visitor takes the broken value as nil without reporting it again
+    let (printed, reported) = reported(|| run_ast(&ast));
+
+    assert_eq!(printed.unwrap(), vec!["nil"]);
+    assert!(reported.is_empty(), "{}", reported);
+}
+
+#[test]
+fn tab_indentation_is_an_error_when_forbidden() {
+    let code = "if true:\n\tprint(1)\n";
+
+    let mut forbidding = parser(code);
+    forbidding.set_forbid_tabs(true);
+
+    let (result, reported) = reported(|| forbidding.parse());
+
+    assert!(result.is_err());
+    assert!(reported.contains("indented with a tab") && reported.contains("print(1)"), "{}", reported);
+
+    assert!(parser(code).parse().is_ok());
+}
+
+#[test]
+fn comments_never_reach_the_parser() {
+    let tokens = lex("let a = 1 # one\n# on its own\nprint(a)\n").unwrap();
+
+    assert!(tokens.iter().all(|token| !token.lexeme.contains('#')), "{:?}", tokens);
+}
+
+#[test]
+fn comment_lines_dont_end_blocks() {
+    let code = "\
+fun f():
+    print(1)
+# at the start of the line
+    print(2)
+        # further in
+    print(3) # trailing
+f()
+";
+
+    assert_eq!(run(code).unwrap(), vec!["1", "2", "3"]);
+}
+
+#[test]
+fn check_is_only_a_statement_before_a_condition() {
+    let checked = parse("check n > 1\ncheck not done\n").unwrap();
+
+    assert!(checked.iter().all(|statement| matches!(statement.node, StatementNode::Check(_))), "{:?}", NoPos(&checked));
+
+    let named = parse("let check = 1\ncheck()\nflag &&= check()\nprint(check)\n").unwrap();
+
+    assert!(!named.iter().any(|statement| matches!(statement.node, StatementNode::Check(_))), "{:?}", NoPos(&named));
+}
+
+// the value of the `let` a one-line program is
+fn declared(code: &str) -> ExpressionNode {
+    match parse(code).unwrap()[0].node {
+        StatementNode::Declaration(_, _, Some(ref value)) => value.node.clone(),
+        ref node => panic!("{:?}", node),
+    }
+}
+
+#[test]
+fn number_suffixes_pick_int_or_float() {
+    assert_eq!(declared("let x = 5f\n"), ExpressionNode::Float(5.0));
+    assert_eq!(declared("let x = 5i\n"), ExpressionNode::Int(5));
+    assert_eq!(declared("let x = 5.5f\n"), ExpressionNode::Float(5.5));
+}
+
+#[test]
+fn bad_number_suffixes_are_errors() {
+    for (code, expected) in &[("let x = 5u\n", "unknown number suffix `u`"), ("let x = 5.5i\n", "it can't be an int")] {
+        let (result, reported) = reported(|| parse(code));
+
+        assert!(result.is_err(), "{}", code);
+        assert!(reported.contains(expected), "{}", reported);
+    }
+}
+
+#[test]
+fn without_recovery_parsing_stops_at_the_first_error() {
+    let (result, reported) = reported(|| parser("let = 1\nprint(1)\nlet b = )\n").parse());
+
+    assert!(result.is_err());
+    assert_eq!(reported.matches("error").count(), 1, "{}", reported);
+}
+
+#[test]
+fn recovered_parses_skip_only_the_broken_statement() {
+    let mut parser = parser("print(1)\nlet = 1\nlet b = 2\nprint(b)\n");
+
+    let ast = reported(|| parser.parse_partial()).0;
+
+    assert_eq!(ast.len(), 4);
+    assert_eq!(ast[1].node, StatementNode::Error);
+    assert_eq!(format!("{:?}", NoPos(&ast[2 ..])), format!("{:?}", NoPos(&parse("let b = 2\nprint(b)\n").unwrap())));
+}
+
+#[test]
+fn precedence_and_associativity_are_pinned() {
+    for (code, grouped) in &[
+        ("1 + 2 * 3", "1 + (2 * 3)"),
+        ("1 - 2 - 3", "(1 - 2) - 3"),
+        ("8 / 4 / 2", "(8 / 4) / 2"),
+        ("2 ^ 3 ^ 2", "2 ^ (3 ^ 2)"),
+        ("1 + 2 ^ 3 * 4", "1 + ((2 ^ 3) * 4)"),
+        ("1 + 2 < 3 * 4", "(1 + 2) < (3 * 4)"),
+        ("a < b and b < c or d", "((a < b) and (b < c)) or d"),
+        ("a or b and c", "(a or b) and c"),
+        ("\"a\" ++ \"b\" == s", "(\"a\" ++ \"b\") == s"),
+        ("x.y * 2", "(x.y) * 2"),
+        ("1 +% 2 *% 3", "1 +% (2 *% 3)"),
+    ] {
+        let parsed = parse(&format!("print({})\n", code)).unwrap();
+        let expected = parse(&format!("print({})\n", grouped)).unwrap();
+
+        assert_eq!(format!("{:?}", NoPos(&parsed)), format!("{:?}", NoPos(&expected)), "{}", code);
+    }
+}
+
+#[test]
+fn chained_equality_warns() {
+    for code in &["print(a == b == c)\n", "print(a != b == c)\n"] {
+        let (result, reported) = reported(|| parse(code));
+
+        assert!(result.is_ok(), "{}", code);
+        assert!(reported.contains("chained equality"), "{}", reported);
+    }
+
+    for code in &["print((a == b) == c)\n", "print(a == b and b == c)\n", "print(a < b < c)\n"] {
+        let (_, reported) = reported(|| parse(code));
+
+        assert!(!reported.contains("chained equality"), "{}: {}", code, reported);
+    }
+}
+
+#[test]
+fn leftovers_after_an_interpolated_expression_are_errors() {
+    let (result, reported) = reported(|| parse("let a = \"${1 2}\"\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("unexpected trailing tokens, starting at `2`"), "{}", reported);
+}
+
+#[test]
+fn leftovers_after_a_statement_are_errors() {
+    let mut more = parser("print(1)\n\nprint(2)\n");
+
+    more.parse_statement().unwrap();
+
+    let (result, reported) = reported(|| more.expect_eof());
+
+    assert!(result.is_err());
+    assert!(reported.contains("starting at `print`"), "{}", reported);
+
+    let mut blank = parser("print(1)\n\n\n");
+
+    blank.parse_statement().unwrap();
+
+    assert!(blank.expect_eof().is_ok());
+}
+
+// the tree an expression on its own line parses to, without positions
+fn tree(code: &str) -> String {
+    format!("{:?}", NoPos(&parse(code).unwrap()))
+}
+
+#[test]
+fn unary_operators_bind_tighter_than_and_looser_than_power() {
+    assert_eq!(tree("-2 ^ 2\n"), "[Expression(Neg(Binary(Int(2), Pow, Int(2))))]");
+    assert_eq!(tree("-x ^ 2\n"), "[Expression(Neg(Binary(Identifier(\"x\"), Pow, Int(2))))]");
+    assert_eq!(tree("-x + 1\n"), "[Expression(Binary(Neg(Identifier(\"x\")), Add, Int(1)))]");
+    assert_eq!(tree("not a and b\n"), "[Expression(Binary(Not(Identifier(\"a\")), And, Identifier(\"b\")))]");
+    assert_eq!(tree("not a == b\n"), "[Expression(Not(Binary(Identifier(\"a\"), Eq, Identifier(\"b\"))))]");
+}
+
+#[test]
+fn match_arms_take_literal_patterns_and_an_else() {
+    let code = "match x:\n    case 1: print(1)\n    case \"a\":\n        print(2)\n    else: print(3)\n";
+
+    assert_eq!(tree(code), concat!(
+        "[Match(Identifier(\"x\"), [",
+        "(Int(1), [Expression(Call(Identifier(\"print\"), [Int(1)], []))]), ",
+        "(Str(\"a\"), [Expression(Call(Identifier(\"print\"), [Int(2)], []))])], ",
+        "Some([Expression(Call(Identifier(\"print\"), [Int(3)], []))]))]"
+    ));
+}
+
+#[test]
+fn match_cases_are_only_literals() {
+    for code in ["match x:\n    case y: print(1)\n", "match x:\n    case 1 + 1: print(1)\n"].iter() {
+        let (result, output) = reported(|| parse(code));
+
+        assert!(result.is_err(), "{}", code);
+        assert!(output.contains("a `case` can only match an int, float, string or bool literal"), "{}", output);
+    }
+}
+
+#[test]
+fn interfaces_declare_each_method_once() {
+    let (result, output) = reported(|| parse("interface Shape:\n    fun area(self): 0\n    fun area(self): 1\n"));
+
+    assert!(result.is_err());
+    assert!(output.contains("interface `Shape` already declares `area`"), "{}", output);
+
+    // pointing at the second one
+    assert!(output.contains("3 │     fun area(self): 1"), "{}", output);
+    assert!(!output.contains("2 │"), "{}", output);
+}
+
+// `tree`, with parens-free calls turned on
+fn command_tree(code: &str) -> String {
+    let mut commands = parser(code);
+
+    commands.set_command_calls(true);
+
+    format!("{:?}", NoPos(&commands.parse().unwrap()))
+}
+
+#[test]
+fn command_calls_take_one_argument_without_parens() {
+    assert_eq!(command_tree("print \"hi\"\n"), tree("print(\"hi\")\n"));
+    assert_eq!(command_tree("move x + 1\n"), tree("move(x + 1)\n"));
+    assert_eq!(command_tree("say nil\n"), tree("say(nil)\n"));
+}
+
+#[test]
+fn command_calls_are_off_by_default() {
+    let (result, _) = reported(|| parse("print \"hi\"\n"));
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn command_calls_leave_other_statements_alone() {
+    for code in ["x = 1\n", "a.b = 2\n", "print(\"hi\")\n", "x + 1\n", "x\n", "f(x) + 1\n"].iter() {
+        assert_eq!(command_tree(code), tree(code), "{}", code);
+    }
+}
+
+#[test]
+fn bodies_are_inline_or_blocks_alike() {
+    let pairs = [
+        ("if a: print(1)\n", "if a:\n    print(1)\n"),
+        ("if a:\n    print(1)\nelif b: print(2)\nelse: print(3)\n", "if a:\n    print(1)\nelif b:\n    print(2)\nelse:\n    print(3)\n"),
+        ("unless a: print(1)\n", "unless a:\n    print(1)\n"),
+        ("while a: print(1)\n", "while a:\n    print(1)\n"),
+        ("loop: break\n", "loop:\n    break\n"),
+        // an inline expression is what the function returns
+        ("fun f(x): x * 2\n", "fun f(x):\n    return x * 2\n"),
+        ("fun f(x): let y = x\n", "fun f(x):\n    let y = x\n"),
+        ("interface Shape: fun area(): 1\n", "interface Shape:\n    fun area(): 1\n"),
+        ("interface Shape:\n    fun area(): 1\n", "interface Shape:\n    fun area():\n        return 1\n"),
+    ];
+
+    for (inline, block) in pairs.iter() {
+        assert_eq!(tree(inline), tree(block), "{}", inline);
+    }
+
+    // only a block can be followed by `elif` or `else`
+    assert!(reported(|| parse("if a: print(1)\nelif b: print(2)\n")).0.is_err());
+}
+
+// a guard rather than a benchmark, the limit is far above the fraction of a second this takes
+#[test]
+fn multi_megabyte_strings_parse_in_one_pass() {
+    let long = "a".repeat(4 * 1024 * 1024);
+    let code = format!("let s = \"{}\"\nprint(s)\nprint(s)\n", long);
+
+    let start = std::time::Instant::now();
+    let ast = parse(&code).unwrap();
+
+    assert!(start.elapsed() < std::time::Duration::from_secs(10), "{:?}", start.elapsed());
+
+    match ast[0].node {
+        StatementNode::Declaration(_, _, Some(ref value)) => assert_eq!(value.node, ExpressionNode::Str(long)),
+        ref node => panic!("{:?}", NoPos(node)),
+    }
+}
+
+#[test]
+fn commas_make_tuples_of_parens() {
+    assert_eq!(tree("(a, b)\n"), "[Expression(Tuple([Identifier(\"a\"), Identifier(\"b\")]))]");
+    assert_eq!(tree("(a,)\n"), "[Expression(Tuple([Identifier(\"a\")]))]");
+    assert_eq!(tree("(a)\n"), "[Expression(Identifier(\"a\"))]");
+    assert_eq!(declared("let t = ()\n"), ExpressionNode::Empty);
+}
+
+#[test]
+fn conditionals_bind_looser_than_operators() {
+    assert_eq!(tree("a if c else b\n"), "[Expression(Conditional(Identifier(\"c\"), Identifier(\"a\"), Identifier(\"b\")))]");
+    assert_eq!(tree("a + 1 if c else b\n"), tree("(a + 1) if c else b\n"));
+    assert_eq!(tree("a if c or d else b - 1\n"), tree("a if (c or d) else (b - 1)\n"));
+    assert_eq!(tree("a if c else b if d else e\n"), tree("a if c else (b if d else e)\n"));
+}
+
+#[test]
+fn conditionals_go_in_literals() {
+    assert_eq!(tree("[a if c else b, 1]\n"), "[Expression(Array([Conditional(Identifier(\"c\"), Identifier(\"a\"), Identifier(\"b\")), Int(1)]))]");
+    assert_eq!(tree("{x: a if c else b, y: 1}\n"), tree("{x: (a if c else b), y: 1}\n"));
+}
+
+#[test]
+fn conditionals_need_an_else() {
+    for code in ["let x = a if c\n", "[a if c, 1]\n", "{x: a if c}\n"].iter() {
+        let (result, output) = reported(|| parse(code));
+
+        assert!(result.is_err(), "{}", code);
+        assert!(output.contains("a conditional expression needs an `else`"), "{}", output);
+    }
+}
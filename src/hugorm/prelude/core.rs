@@ -0,0 +1,569 @@
+use super::parser::TypeNode;
+use super::visitor::*;
+use zub::vm::*;
+
+use std::process;
+
+pub fn include_core(visitor: &mut Visitor, vm: &mut VM) {
+    visitor.set_global("zip", TypeNode::Func(2));
+    vm.add_native("zip", zip, 2);
+
+    visitor.set_global("enumerate", TypeNode::Func(1));
+    vm.add_native("enumerate", enumerate, 1);
+
+    // what `map` and `filter` compile down to, unreachable by name from hugorm code
+    visitor.set_global("$len", TypeNode::Func(1));
+    vm.add_native("$len", len, 1);
+
+    visitor.set_global("$push", TypeNode::Func(2));
+    vm.add_native("$push", push, 2);
+
+    // what `range` calls compile down to, with all three of start, end and step
+    visitor.set_global("$range", TypeNode::Func(3));
+    vm.add_native("$range", range, 3);
+
+    // behind `a?.b` and `a?[b]`
+    visitor.set_global("$index_or_nil", TypeNode::Func(2));
+    vm.add_native("$index_or_nil", index_or_nil, 2);
+
+    // what `for` walks, a list as is or the keys of a dict, and the `[key, value]` pairs behind `for (key, value)`
+    visitor.set_global("$elements", TypeNode::Func(1));
+    vm.add_native("$elements", elements, 1);
+
+    visitor.set_global("$entries", TypeNode::Func(1));
+    vm.add_native("$entries", entries, 1);
+
+    // behind `[...xs]` and `{...base}`
+    visitor.set_global("$concat", TypeNode::Func(2));
+    vm.add_native("$concat", concat, 2);
+
+    visitor.set_global("$merge", TypeNode::Func(2));
+    vm.add_native("$merge", merge, 2);
+
+    // behind the string intrinsics
+    vm.add_native("$starts_with", starts_with, 2);
+    vm.add_native("$ends_with", ends_with, 2);
+    vm.add_native("$contains", contains, 2);
+    vm.add_native("$split", split, 2);
+    vm.add_native("$upper", upper, 1);
+    vm.add_native("$lower", lower, 1);
+    vm.add_native("$trim", trim, 1);
+    vm.add_native("$replace", replace, 3);
+
+    // what `++` turns anything that isn't a string into, and so `"${x}"` too
+    visitor.set_global("$stringify", TypeNode::Func(1));
+    vm.add_native("$stringify", stringify, 1);
+
+    // behind `xs[a:b]`, missing bounds are nil
+    visitor.set_global("$slice", TypeNode::Func(3));
+    vm.add_native("$slice", slice, 3);
+
+    // behind `check cond`
+    visitor.set_global("$check", TypeNode::Func(2));
+    vm.add_native("$check", check, 2);
+
+    // what `match` compares its value to each `case` with
+    visitor.set_global("$equals", TypeNode::Func(2));
+    vm.add_native("$equals", equals, 2);
+
+    // behind `x is T`
+    vm.add_native("$is", is, 2);
+
+    // behind `+%`, `-%` and `*%`
+    vm.add_native("$wrapping_add", wrapping_add, 2);
+    vm.add_native("$wrapping_sub", wrapping_sub, 2);
+    vm.add_native("$wrapping_mul", wrapping_mul, 2);
+
+    // behind `abs`, `min` and `max`
+    vm.add_native("$abs", abs, 1);
+    vm.add_native("$min", min, 2);
+    vm.add_native("$max", max, 2);
+}
+
+// reported the way the vm reports its own, ending the program
+pub fn runtime_error(message: &str) -> ! {
+    eprintln!("[error]: {}.", message);
+    process::exit(1)
+}
+
+// what `print` shows, lists and dicts written like their literals
+pub fn display(heap: &Heap<Object>, value: &Value) -> String {
+    let mut out = String::new();
+
+    write_value(heap, value, &mut Vec::new(), false, &mut out);
+
+    out
+}
+
+// `seen` holds the lists and dicts being written, running into one again means it contains itself
+fn write_value(heap: &Heap<Object>, value: &Value, seen: &mut Vec<Handle<Object>>, nested: bool, out: &mut String) {
+    let handle = match value.decode() {
+        Variant::Obj(handle) => handle,
+        _ => return out.push_str(&value.with_heap(heap).to_string()),
+    };
+
+    match unsafe { heap.get_unchecked(handle) } {
+        Object::List(list) => {
+            if seen.contains(&handle) {
+                return out.push_str("[...]")
+            }
+
+            seen.push(handle);
+            out.push('[');
+
+            for (i, element) in list.content.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ")
+                }
+
+                write_value(heap, element, seen, true, out)
+            }
+
+            out.push(']');
+            seen.pop();
+        },
+
+        Object::Dict(dict) => {
+            if seen.contains(&handle) {
+                return out.push_str("{...}")
+            }
+
+            // hash order changes between runs, sorted keys don't
+            let mut fields = dict.content.iter()
+                .map(|(key, value)| (display_key(&key.variant), value))
+                .collect::<Vec<(String, &Value)>>();
+
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+
+            seen.push(handle);
+            out.push('{');
+
+            for (i, (key, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ")
+                }
+
+                out.push_str(key);
+                out.push_str(": ");
+
+                write_value(heap, value, seen, true, out)
+            }
+
+            out.push('}');
+            seen.pop();
+        },
+
+        Object::String(s) if nested => out.push_str(&format!("{:?}", s)),
+
+        _ => out.push_str(&value.with_heap(heap).to_string()),
+    }
+}
+
+fn display_key(key: &HashVariant) -> String {
+    match key {
+        HashVariant::Str(s) => s.clone(),
+        HashVariant::Int(n) => n.to_string(),
+        HashVariant::Bool(b) => b.to_string(),
+        HashVariant::Nil => "nil".to_string(),
+    }
+}
+
+fn list_content(heap: &Heap<Object>, value: &Value, what: &str) -> Vec<Value> {
+    if let Variant::Obj(handle) = value.decode() {
+        if let Some(list) = unsafe { heap.get_unchecked(handle) }.as_list() {
+            return list.content.clone()
+        }
+    }
+
+    runtime_error(&format!("can't {} `{}`, it's not a list", what, display(heap, value)))
+}
+
+fn string_content(heap: &Heap<Object>, value: &Value, what: &str) -> String {
+    if let Variant::Obj(handle) = value.decode() {
+        if let Some(s) = unsafe { heap.get_unchecked(handle) }.as_string() {
+            return s.clone()
+        }
+    }
+
+    runtime_error(&format!("can't {} `{}`, it's not a string", what, display(heap, value)))
+}
+
+fn pair(heap: &mut Heap<Object>, a: Value, b: Value) -> Value {
+    Value::object(heap.insert_temp(Object::List(List::new(vec!(a, b)))))
+}
+
+// `[a[i], b[i]]` pairs, as long as the shorter list
+fn zip(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let a = list_content(heap, &args[1], "zip");
+    let b = list_content(heap, &args[2], "zip");
+
+    let pairs = a.into_iter()
+        .zip(b.into_iter())
+        .map(|(a, b)| pair(heap, a, b))
+        .collect::<Vec<Value>>();
+
+    Value::object(heap.insert_temp(Object::List(List::new(pairs))))
+}
+
+// `[index, element]` pairs
+fn enumerate(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let content = list_content(heap, &args[1], "enumerate");
+
+    let pairs = content.into_iter()
+        .enumerate()
+        .map(|(i, element)| pair(heap, Value::float(i as f64), element))
+        .collect::<Vec<Value>>();
+
+    Value::object(heap.insert_temp(Object::List(List::new(pairs))))
+}
+
+fn len(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    Value::float(list_content(heap, &args[1], "take length of").len() as f64)
+}
+
+fn push(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    if let Variant::Obj(handle) = args[1].decode() {
+        if let Object::List(list) = heap.get_mut_unchecked(handle) {
+            list.push(args[2]);
+
+            return Value::nil()
+        }
+    }
+
+    runtime_error(&format!("can't push to `{}`, it's not a list", display(heap, &args[1])))
+}
+
+fn concat(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let mut content = list_content(heap, &args[1], "spread");
+    content.extend(list_content(heap, &args[2], "spread"));
+
+    Value::object(heap.insert_temp(Object::List(List::new(content))))
+}
+
+fn dict_copy(heap: &Heap<Object>, value: &Value) -> Dict {
+    if let Variant::Obj(handle) = value.decode() {
+        if let Some(dict) = unsafe { heap.get_unchecked(handle) }.as_dict() {
+            return Dict::new(dict.content.clone())
+        }
+    }
+
+    runtime_error(&format!("can't spread `{}`, it's not a dict", display(heap, value)))
+}
+
+// the right dict's fields win
+fn merge(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let mut merged = dict_copy(heap, &args[1]);
+    merged.content.extend(dict_copy(heap, &args[2]).content);
+
+    Value::object(heap.insert_temp(Object::Dict(merged)))
+}
+
+// `[key, value]` pairs of a dict, in no particular order
+fn entries(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let content = match dict_content(heap, &args[1]) {
+        Some(content) => content,
+        None => runtime_error("can't take entries of non-dict"),
+    };
+
+    let pairs = content.into_iter()
+        .map(|(key, value)| {
+            let key = key_value(heap, key);
+            pair(heap, key, value)
+        })
+        .collect::<Vec<Value>>();
+
+    Value::object(heap.insert_temp(Object::List(List::new(pairs))))
+}
+
+fn dict_content(heap: &Heap<Object>, value: &Value) -> Option<Vec<(HashVariant, Value)>> {
+    if let Variant::Obj(handle) = value.decode() {
+        if let Some(dict) = unsafe { heap.get_unchecked(handle) }.as_dict() {
+            return Some(dict.content.iter().map(|(key, value)| (key.variant.clone(), *value)).collect())
+        }
+    }
+
+    None
+}
+
+// the key `d[k] = v` stores `v` under, the vm's own `set_element` truncates numbers to ints
+fn hash_key(heap: &Heap<Object>, key: &Value) -> HashVariant {
+    match key.decode() {
+        Variant::Float(n) => HashVariant::Int(n as i64),
+        variant => variant.to_hash(heap),
+    }
+}
+
+// the value `hash_key` made `key` from
+fn key_value(heap: &mut Heap<Object>, key: HashVariant) -> Value {
+    match key {
+        HashVariant::Str(s) => Value::object(heap.insert_temp(Object::String(s))),
+        HashVariant::Int(n) => Value::float(n as f64),
+        HashVariant::Bool(true) => Value::truelit(),
+        HashVariant::Bool(false) => Value::falselit(),
+        HashVariant::Nil => Value::nil(),
+    }
+}
+
+fn elements(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let keys = if let Variant::Obj(handle) = args[1].decode() {
+        match unsafe { heap.get_unchecked(handle) } {
+            Object::List(_) => return args[1],
+            Object::Dict(dict) => dict.content.keys().map(|key| key.variant.clone()).collect::<Vec<HashVariant>>(),
+            _ => runtime_error(&format!("can't loop over `{}`, only over a list or a dict", display(heap, &args[1]))),
+        }
+    } else {
+        runtime_error(&format!("can't loop over `{}`, only over a list or a dict", display(heap, &args[1])))
+    };
+
+    let keys = keys.into_iter()
+        .map(|key| key_value(heap, key))
+        .collect::<Vec<Value>>();
+
+    Value::object(heap.insert_temp(Object::List(List::new(keys))))
+}
+
+// the element or field, nil when there's no receiver or nothing under the key
+fn index_or_nil(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let handle = match args[1].decode() {
+        Variant::Nil => return Value::nil(),
+        Variant::Obj(handle) => handle,
+        _ => runtime_error(&format!("can't index `{}`", display(heap, &args[1]))),
+    };
+
+    match unsafe { heap.get_unchecked(handle) } {
+        Object::List(list) => match args[2].decode() {
+            Variant::Float(n) if n.fract() == 0.0 && n >= 0.0 => list.content.get(n as usize).cloned().unwrap_or_else(Value::nil),
+            _ => runtime_error("can't index list with non-integer"),
+        },
+
+        Object::Dict(dict) => {
+            let key = HashValue {
+                variant: hash_key(heap, &args[2])
+            };
+
+            dict.get(&key).cloned().unwrap_or_else(Value::nil)
+        },
+
+        _ => runtime_error(&format!("can't index `{}`", display(heap, &args[1]))),
+    }
+}
+
+fn starts_with(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let s = string_content(heap, &args[1], "check start of");
+    s.starts_with(&string_content(heap, &args[2], "check start with")).into()
+}
+
+fn ends_with(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let s = string_content(heap, &args[1], "check end of");
+    s.ends_with(&string_content(heap, &args[2], "check end with")).into()
+}
+
+fn contains(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let s = string_content(heap, &args[1], "search in");
+    s.contains(&string_content(heap, &args[2], "search for")).into()
+}
+
+fn split(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let s = string_content(heap, &args[1], "split");
+    let separator = string_content(heap, &args[2], "split on");
+
+    let parts = s.split(separator.as_str())
+        .map(|part| string(heap, part.to_string()))
+        .collect::<Vec<Value>>();
+
+    Value::object(heap.insert_temp(Object::List(List::new(parts))))
+}
+
+fn string(heap: &mut Heap<Object>, s: String) -> Value {
+    Value::object(heap.insert_temp(Object::String(s)))
+}
+
+fn upper(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let s = string_content(heap, &args[1], "uppercase");
+    string(heap, s.to_uppercase())
+}
+
+fn lower(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let s = string_content(heap, &args[1], "lowercase");
+    string(heap, s.to_lowercase())
+}
+
+fn trim(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let s = string_content(heap, &args[1], "trim");
+    string(heap, s.trim().to_string())
+}
+
+// every occurrence of `from`
+fn replace(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let s = string_content(heap, &args[1], "replace in");
+    let from = string_content(heap, &args[2], "replace");
+    let to = string_content(heap, &args[3], "replace with");
+
+    string(heap, s.replace(from.as_str(), &to))
+}
+
+// shown the way `print` would
+fn stringify(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let shown = display(heap, &args[1]);
+    string(heap, shown)
+}
+
+// `[start, start + step, ...]` up to but not including `end`
+fn range(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let bound = |value: &Value| match value.decode() {
+        Variant::Float(n) if n.fract() == 0.0 => n as i64,
+        _ => runtime_error(&format!("can't range over `{}`, bounds are integers", display(heap, value))),
+    };
+
+    let (start, end, step) = (bound(&args[1]), bound(&args[2]), bound(&args[3]));
+
+    if step <= 0 {
+        runtime_error(&format!("can't range with a step of {}, it has to be at least 1", step))
+    }
+
+    let content = (start .. end)
+        .step_by(step as usize)
+        .map(|n| Value::float(n as f64))
+        .collect::<Vec<Value>>();
+
+    Value::object(heap.insert_temp(Object::List(List::new(content))))
+}
+
+// the elements or characters from `start` up to but not including `end`
+fn slice(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let handle = match args[1].decode() {
+        Variant::Obj(handle) => handle,
+        _ => runtime_error(&format!("can't slice `{}`", display(heap, &args[1]))),
+    };
+
+    let bound = |value: &Value, missing: usize| match value.decode() {
+        Variant::Nil => missing,
+        Variant::Float(n) if n.fract() == 0.0 && n >= 0.0 => n as usize,
+        Variant::Float(n) if n.fract() == 0.0 => runtime_error(&format!("can't slice from a negative index, `{}`", n)),
+        _ => runtime_error(&format!("can't slice with `{}`, bounds are integers", display(heap, value))),
+    };
+
+    let sliced = match unsafe { heap.get_unchecked(handle) } {
+        Object::List(list) => {
+            let (start, end) = (bound(&args[2], 0), bound(&args[3], list.content.len()));
+
+            if start > end || end > list.content.len() {
+                runtime_error(&format!("can't slice {}..{} out of {} elements", start, end, list.content.len()))
+            }
+
+            Object::List(List::new(list.content[start .. end].to_vec()))
+        },
+
+        Object::String(s) => {
+            let chars = s.chars().collect::<Vec<char>>();
+            let (start, end) = (bound(&args[2], 0), bound(&args[3], chars.len()));
+
+            if start > end || end > chars.len() {
+                runtime_error(&format!("can't slice {}..{} out of {} characters", start, end, chars.len()))
+            }
+
+            Object::String(chars[start .. end].iter().collect())
+        },
+
+        _ => runtime_error(&format!("can't slice `{}`", display(heap, &args[1]))),
+    };
+
+    Value::object(heap.insert_temp(sliced))
+}
+
+fn int_operands(heap: &Heap<Object>, args: &[Value]) -> (i64, i64) {
+    match (args[1].decode(), args[2].decode()) {
+        (Variant::Float(a), Variant::Float(b)) if a.fract() == 0.0 && b.fract() == 0.0 => (a as i64, b as i64),
+
+        _ => runtime_error(&format!(
+            "can't do wrapping arithmetic on `{}` and `{}`, only on integers",
+            display(heap, &args[1]),
+            display(heap, &args[2])
+        )),
+    }
+}
+
+fn wrapping_add(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let (a, b) = int_operands(heap, args);
+    Value::float(a.wrapping_add(b) as f64)
+}
+
+fn wrapping_sub(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let (a, b) = int_operands(heap, args);
+    Value::float(a.wrapping_sub(b) as f64)
+}
+
+fn wrapping_mul(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let (a, b) = int_operands(heap, args);
+    Value::float(a.wrapping_mul(b) as f64)
+}
+
+fn number(heap: &Heap<Object>, value: &Value, what: &str) -> f64 {
+    match value.decode() {
+        Variant::Float(n) => n,
+        _ => runtime_error(&format!("`{}` takes numbers, not `{}`", what, display(heap, value))),
+    }
+}
+
+fn abs(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    Value::float(number(heap, &args[1], "abs").abs())
+}
+
+fn min(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    Value::float(number(heap, &args[1], "min").min(number(heap, &args[2], "min")))
+}
+
+fn max(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    Value::float(number(heap, &args[1], "max").max(number(heap, &args[2], "max")))
+}
+
+fn check(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    if !args[1].truthy() {
+        runtime_error(&string_content(heap, &args[2], "fail check with"))
+    }
+
+    Value::nil()
+}
+
+// the vm's `==` only compares numbers, this also knows strings and bools
+fn equals(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let equal = match (args[1].decode(), args[2].decode()) {
+        (Variant::Obj(a), Variant::Obj(b)) => match unsafe { (heap.get_unchecked(a).as_string(), heap.get_unchecked(b).as_string()) } {
+            (Some(a), Some(b)) => a == b,
+            _ => a == b,
+        },
+
+        (a, b) => a == b,
+    };
+
+    equal.into()
+}
+
+// whether a value is of the named type, ints are the floats without a fraction
+fn is(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let name = string_content(heap, &args[2], "test against");
+
+    let matches = match (name.as_str(), args[1].decode()) {
+        ("Any", _) => true,
+        ("Int", Variant::Float(n)) => n.fract() == 0.0,
+        ("Float", Variant::Float(_)) => true,
+        ("Bool", Variant::True) | ("Bool", Variant::False) => true,
+        ("Nil", Variant::Nil) => true,
+
+        (name, Variant::Obj(handle)) => match (name, unsafe { heap.get_unchecked(handle) }) {
+            ("Str", Object::String(_)) => true,
+            ("List", Object::List(_)) => true,
+            ("Dict", Object::Dict(_)) => true,
+            ("Func", Object::Function(_)) | ("Func", Object::Closure(_)) | ("Func", Object::NativeFunction(_)) => true,
+            _ => false,
+        },
+
+        _ => false,
+    };
+
+    if matches {
+        Value::truelit()
+    } else {
+        Value::falselit()
+    }
+}
@@ -1,3 +1,4 @@
+use super::parser::TypeNode;
 use super::visitor::*;
 use zub::ir::*;
 use zub::vm::*;
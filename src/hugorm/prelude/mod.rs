@@ -1,3 +1,5 @@
+pub mod core;
 pub mod math;
 
+use super::parser;
 use super::visitor;
\ No newline at end of file
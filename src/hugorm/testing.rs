@@ -0,0 +1,102 @@
+// compiling and running snippets for the tests next to each module, the way `hugorm file.hug` would
+
+use std::cell::RefCell;
+
+use zub::vm::*;
+
+use super::error;
+use super::lexer::*;
+use super::parser::*;
+use super::prelude::core;
+use super::source::Source;
+use super::visitor::*;
+
+thread_local! {
+    static PRINTED: RefCell<Vec<String>> = RefCell::new(Vec::new());
+}
+
+// leaked, tokens and visitors borrow it for as long as the test runs
+pub fn source(code: &str) -> &'static Source {
+    Box::leak(Box::new(Source::from("<test>", code.lines().map(String::from).collect())))
+}
+
+pub fn lex(code: &str) -> Result<Vec<Token>, ()> {
+    Lexer::default(code.chars().collect(), source(code)).collect()
+}
+
+pub fn parse(code: &str) -> Result<Vec<Statement>, ()> {
+    let source = source(code);
+    let tokens = Lexer::default(code.chars().collect(), source).collect::<Result<Vec<Token>, ()>>()?;
+
+    Parser::new(tokens, source).parse()
+}
+
+// with the core prelude and `print` in scope, `setup` gets to configure the visitor before it lexes and visits
+pub fn compile_with(code: &str, setup: impl FnOnce(&mut Visitor<'static>, &mut VM)) -> Result<(Visitor<'static>, VM), ()> {
+    let source = source(code);
+    let (mut visitor, vm) = prepare(source, setup);
+
+    let tokens = visitor.lexer(code, source).collect::<Result<Vec<Token>, ()>>()?;
+    let ast = visitor.parser(tokens, source).parse()?;
+
+    visitor.visit(&ast)?;
+
+    Ok((visitor, vm))
+}
+
+fn prepare(source: &'static Source, setup: impl FnOnce(&mut Visitor<'static>, &mut VM)) -> (Visitor<'static>, VM) {
+    let mut visitor = Visitor::new(source);
+    let mut vm = VM::new();
+
+    visitor.set_global("print", TypeNode::Func(1));
+    vm.add_native("print", print, 1);
+
+    core::include_core(&mut visitor, &mut vm);
+
+    setup(&mut visitor, &mut vm);
+
+    (visitor, vm)
+}
+
+pub fn compile(code: &str) -> Result<Visitor<'static>, ()> {
+    compile_with(code, |_, _| ()).map(|(visitor, _)| visitor)
+}
+
+// what every `print` showed, in order
+pub fn run_with(code: &str, setup: impl FnOnce(&mut Visitor<'static>, &mut VM)) -> Result<Vec<String>, ()> {
+    execute(compile_with(code, setup)?)
+}
+
+// for trees built in code, positions are synthetic so errors point nowhere
+pub fn run_ast(ast: &[Statement]) -> Result<Vec<String>, ()> {
+    let (mut visitor, vm) = prepare(source(""), |_, _| ());
+
+    visitor.visit(&ast.to_vec())?;
+
+    execute((visitor, vm))
+}
+
+fn execute((visitor, mut vm): (Visitor<'static>, VM)) -> Result<Vec<String>, ()> {
+    PRINTED.with(|printed| printed.borrow_mut().clear());
+
+    vm.exec(&visitor.build(), false);
+
+    Ok(PRINTED.with(|printed| printed.replace(Vec::new())))
+}
+
+pub fn run(code: &str) -> Result<Vec<String>, ()> {
+    run_with(code, |_, _| ())
+}
+
+// everything `response!` wrote while `f` ran, for looking for a message
+pub fn reported<T>(f: impl FnOnce() -> T) -> (T, String) {
+    error::capture(f)
+}
+
+fn print(heap: &mut Heap<Object>, args: &[Value]) -> Value {
+    let shown = core::display(heap, &args[1]);
+
+    PRINTED.with(|printed| printed.borrow_mut().push(shown));
+
+    Value::nil()
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::*;
+
+// splices every function that's called exactly once, and does nothing but return an expression of its
+// parameters, into that one call and drops its declaration, see `Visitor::set_inline`
+pub fn inline_single_use(ast: &[Statement]) -> Vec<Statement> {
+    let mut counter = Inliner::default();
+    counter.statements(ast);
+
+    let inlined = counter.candidates();
+
+    if inlined.is_empty() {
+        return ast.to_vec()
+    }
+
+    let mut inliner = Inliner {
+        inlined,
+        ..Inliner::default()
+    };
+
+    inliner.statements(ast)
+}
+
+#[derive(Default)]
+struct Inliner {
+    inlined: HashMap<String, (Vec<String>, Expression)>, // what gets spliced in the second walk
+    trivial: HashMap<String, (Vec<String>, Expression)>, // params and returned expression
+    bound: HashMap<String, usize>, // by `let`, `fun`, params, loops, anything that could shadow a function
+    mentions: HashMap<String, usize>,
+    calls: HashMap<String, Vec<(usize, bool)>>, // arity, and whether every argument is safe to evaluate anywhere
+}
+
+impl Inliner {
+    // a call is the only mention of the function, and its body can't call anything, itself included
+    fn candidates(self) -> HashMap<String, (Vec<String>, Expression)> {
+        let mut candidates = HashMap::new();
+
+        for (name, (params, body)) in self.trivial.into_iter() {
+            let once = self.bound.get(&name) == Some(&1) && self.mentions.get(&name) == Some(&1);
+
+            let fitting = match self.calls.get(&name) {
+                Some(calls) => calls.len() == 1 && calls[0] == (params.len(), true),
+                None => false,
+            };
+
+            if once && fitting {
+                candidates.insert(name, (params, body));
+            }
+        }
+
+        candidates
+    }
+
+    fn bind(&mut self, name: &str) {
+        *self.bound.entry(name.to_string()).or_insert(0) += 1
+    }
+
+    fn is_inlined(&self, statement: &Statement) -> bool {
+        match statement.node {
+            StatementNode::Function(ref name, ..) => self.inlined.contains_key(name),
+            StatementNode::ConstFunction(ref fun) => self.is_inlined(fun),
+            _ => false,
+        }
+    }
+
+    fn statements(&mut self, body: &[Statement]) -> Vec<Statement> {
+        let mut result = Vec::new();
+
+        for statement in body.iter() {
+            if !self.is_inlined(statement) {
+                result.push(self.statement(statement))
+            }
+        }
+
+        result
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Statement {
+        use self::StatementNode::*;
+
+        let node = match statement.node {
+            Expression(ref e) => Expression(self.expression(e)),
+            Yield(ref e) => Yield(self.expression(e)),
+            Check(ref e) => Check(self.expression(e)),
+
+            Declaration(ref name, ref t, ref right) => {
+                self.bind(name);
+                Declaration(name.clone(), t.clone(), right.as_ref().map(|e| self.expression(e)))
+            },
+
+            Const(ref name, ref right) => {
+                self.bind(name);
+                Const(name.clone(), self.expression(right))
+            },
+
+            ConstFunction(ref fun) => ConstFunction(Rc::new(self.statement(fun))),
+
+            Assignment(ref left, ref right) => Assignment(self.expression(left), self.expression(right)),
+
+            Function(ref name, ref typed, ref body) => {
+                self.bind(name);
+
+                let params = typed.iter().map(|(param, ..)| param.clone()).collect::<Vec<String>>();
+
+                for param in params.iter() {
+                    self.bind(param)
+                }
+
+                if let [Statement { node: Return(Some(ref returned)), .. }] = body.as_slice() {
+                    let distinct = params.iter().enumerate().all(|(i, param)| !params[.. i].contains(param));
+
+                    if distinct && Self::is_trivial(returned, &params) {
+                        self.trivial.insert(name.clone(), (params, returned.clone()));
+                    }
+                }
+
+                Function(name.clone(), typed.clone(), self.statements(body))
+            },
+
+            Return(ref value) => Return(value.as_ref().map(|e| self.expression(e))),
+
+            Interface(ref name, ref body) => {
+                self.bind(name);
+                Interface(name.clone(), self.statements(body))
+            },
+
+            If(ref cond, ref then, ref branches) => {
+                let cond = self.expression(cond);
+                let then = self.statements(then);
+
+                let branches = branches.iter()
+                    .map(|(cond, body)| (cond.as_ref().map(|e| self.expression(e)), self.statements(body)))
+                    .collect();
+
+                If(cond, then, branches)
+            },
+
+            While(ref cond, ref body) => While(self.expression(cond), self.statements(body)),
+
+            WhileLet(ref name, ref right, ref body, ref else_) => {
+                self.bind(name);
+
+                let right = self.expression(right);
+                let body = self.statements(body);
+
+                WhileLet(name.clone(), right, body, else_.as_ref().map(|body| self.statements(body)))
+            },
+
+            Block(ref body) => Block(self.statements(body)),
+            When(ref flag, ref body) => When(flag.clone(), self.statements(body)),
+
+            For(ref name, ref value, ref iterable, ref body) => {
+                self.bind(name);
+
+                if let Some(ref value) = *value {
+                    self.bind(value)
+                }
+
+                For(name.clone(), value.clone(), self.expression(iterable), self.statements(body))
+            },
+
+            Match(ref scrutinee, ref arms, ref else_) => {
+                let scrutinee = self.expression(scrutinee);
+
+                let arms = arms.iter()
+                    .map(|(pattern, body)| (self.expression(pattern), self.statements(body)))
+                    .collect();
+
+                Match(scrutinee, arms, else_.as_ref().map(|body| self.statements(body)))
+            },
+
+            BreakLabel(ref name, ref value) => BreakLabel(name.clone(), value.as_ref().map(|e| self.expression(e))),
+
+            Break => Break,
+            Error => Error,
+        };
+
+        Statement::new(node, statement.pos.clone())
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Expression {
+        use self::ExpressionNode::*;
+
+        let node = match expression.node {
+            Identifier(ref name) => {
+                *self.mentions.entry(name.clone()).or_insert(0) += 1;
+                Identifier(name.clone())
+            },
+
+            Call(ref callee, ref args, ref keywords) => {
+                let args = args.iter().map(|arg| self.expression(arg)).collect::<Vec<_>>();
+                let keywords = keywords.iter().map(|(name, arg)| (name.clone(), self.expression(arg))).collect::<Vec<_>>();
+
+                if let Identifier(ref name) = callee.node {
+                    if let Some((params, body)) = self.inlined.get(name) {
+                        return Self::substitute(body, params, &args, &expression.pos)
+                    }
+
+                    // keywords would have to be put in order first, such a call keeps its function
+                    let pure = keywords.is_empty() && args.iter().all(Self::is_pure);
+                    self.calls.entry(name.clone()).or_insert_with(Vec::new).push((args.len(), pure));
+                }
+
+                Call(Rc::new(self.expression(callee)), args, keywords)
+            },
+
+            Neg(ref e) => Neg(Rc::new(self.expression(e))),
+            Not(ref e) => Not(Rc::new(self.expression(e))),
+            Spread(ref e) => Spread(Rc::new(self.expression(e))),
+
+            Binary(ref left, ref op, ref right) => Binary(Rc::new(self.expression(left)), op.clone(), Rc::new(self.expression(right))),
+
+            Range(ref start, ref end) => Range(Rc::new(self.expression(start)), Rc::new(self.expression(end))),
+            RangeInclusive(ref start, ref end) => RangeInclusive(Rc::new(self.expression(start)), Rc::new(self.expression(end))),
+            With(ref left, ref right) => With(Rc::new(self.expression(left)), Rc::new(self.expression(right))),
+            OptionalIndex(ref left, ref key) => OptionalIndex(Rc::new(self.expression(left)), Rc::new(self.expression(key))),
+            Conditional(ref cond, ref then, ref otherwise) => Conditional(
+                Rc::new(self.expression(cond)),
+                Rc::new(self.expression(then)),
+                Rc::new(self.expression(otherwise))
+            ),
+
+            Slice(ref list, ref start, ref end) => Slice(
+                Rc::new(self.expression(list)),
+                start.as_ref().map(|e| Rc::new(self.expression(e))),
+                end.as_ref().map(|e| Rc::new(self.expression(e)))
+            ),
+
+            Array(ref content) => Array(content.iter().map(|e| self.expression(e)).collect()),
+            Tuple(ref content) => Tuple(content.iter().map(|e| self.expression(e)).collect()),
+            Dict(ref content) => Dict(content.iter().map(|entry| entry.map(|e| self.expression(e))).collect()),
+
+            AnonFunction(ref name, ref params, ref body) => {
+                self.bind(name);
+
+                for param in params.iter() {
+                    self.bind(param)
+                }
+
+                AnonFunction(name.clone(), params.clone(), self.statements(body))
+            },
+
+            Labeled(ref name, ref body) => Labeled(name.clone(), self.statements(body)),
+
+            Nil | Int(_) | Float(_) | Str(_) | Char(_) | Bool(_) | Empty | Error | EOF => expression.node.clone(),
+        };
+
+        Expression::new(node, expression.pos.clone())
+    }
+
+    // only literals, operators and the parameters, so nothing in it can be shadowed at the call site
+    fn is_trivial(expression: &Expression, params: &Vec<String>) -> bool {
+        use self::ExpressionNode::*;
+
+        match expression.node {
+            Nil | Int(_) | Float(_) | Str(_) | Char(_) | Bool(_) => true,
+
+            Identifier(ref name) => params.contains(name),
+
+            Neg(ref e) | Not(ref e) => Self::is_trivial(e, params),
+
+            Binary(ref left, _, ref right) => Self::is_trivial(left, params) && Self::is_trivial(right, params),
+
+            Array(ref content) | Tuple(ref content) => content.iter().all(|e| Self::is_trivial(e, params)),
+            Dict(ref content) => content.iter().all(|entry| Self::is_trivial(entry.value(), params)),
+
+            _ => false,
+        }
+    }
+
+    // arguments end up wherever their parameter is used, as often as it is, so only those without effects qualify
+    fn is_pure(expression: &Expression) -> bool {
+        use self::ExpressionNode::*;
+
+        match expression.node {
+            Nil | Int(_) | Float(_) | Str(_) | Char(_) | Bool(_) | Identifier(_) => true,
+
+            Neg(ref e) | Not(ref e) => Self::is_pure(e),
+
+            Binary(ref left, Operator::Index, ref key) => Self::is_pure(left) && Self::is_pure(key),
+
+            _ => false,
+        }
+    }
+
+    // the body with its parameters swapped for the arguments, errors in it point at the call
+    fn substitute(body: &Expression, params: &Vec<String>, args: &Vec<Expression>, pos: &Pos) -> Expression {
+        use self::ExpressionNode::*;
+
+        let node = match body.node {
+            Identifier(ref name) => {
+                let index = params.iter().position(|param| param == name).unwrap();
+
+                return args[index].clone()
+            },
+
+            Neg(ref e) => Neg(Rc::new(Self::substitute(e, params, args, pos))),
+            Not(ref e) => Not(Rc::new(Self::substitute(e, params, args, pos))),
+
+            Binary(ref left, ref op, ref right) => Binary(
+                Rc::new(Self::substitute(left, params, args, pos)),
+                op.clone(),
+                Rc::new(Self::substitute(right, params, args, pos))
+            ),
+
+            Array(ref content) => Array(content.iter().map(|e| Self::substitute(e, params, args, pos)).collect()),
+            Tuple(ref content) => Tuple(content.iter().map(|e| Self::substitute(e, params, args, pos)).collect()),
+            Dict(ref content) => Dict(content.iter().map(|entry| entry.map(|e| Self::substitute(e, params, args, pos))).collect()),
+
+            ref literal => literal.clone(),
+        };
+
+        Expression::new(node, pos.clone())
+    }
+}
@@ -1,3 +1,5 @@
+pub mod inline;
+pub mod module;
 pub mod symtab;
 pub mod visitor;
 
@@ -5,5 +7,10 @@ use super::lexer::*;
 use super::parser::*;
 use super::source::*;
 
+pub use self::inline::*;
+pub use self::module::*;
 pub use self::symtab::*;
 pub use self::visitor::*;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use zub::ir::{ Binding, ExprNode, IrBuilder };
+
+use super::super::parser::TypeNode;
+use super::visitor::*;
+
+// a compiled program together with the functions its top level defines
+pub struct Module {
+    pub ir: Vec<ExprNode>,
+    pub exports: HashMap<String, TypeNode>,
+}
+
+impl Module {
+    pub fn new(ir: Vec<ExprNode>, exports: HashMap<String, TypeNode>) -> Self {
+        Module {
+            ir,
+            exports,
+        }
+    }
+
+    // the number of parameters an exported function takes
+    pub fn signature(&self, name: &str) -> Option<usize> {
+        match self.exports.get(name) {
+            Some(TypeNode::Func(params)) => Some(*params),
+            _ => None,
+        }
+    }
+
+    // the module's program followed by a call to one of its functions, ready for `VM::exec`
+    pub fn call(&self, name: &str, args: Vec<ExprNode>) -> Result<Vec<ExprNode>, String> {
+        let params = match self.signature(name) {
+            Some(params) => params,
+            None => return Err(format!("module has no function `{}`", name)),
+        };
+
+        if params != args.len() {
+            return Err(format!("wrong amount of arguments to `{}`, expected {} but got {}", name, params, args.len()))
+        }
+
+        let mut builder = IrBuilder::new();
+
+        // top level functions live in the outermost frame of the program
+        let callee = builder.var(Binding::local(name, 0, 0));
+        let call = builder.call(callee, args, None);
+
+        let mut program = self.ir.clone();
+        program.push(call);
+
+        Ok(program)
+    }
+}
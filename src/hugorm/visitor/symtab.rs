@@ -1,25 +1,30 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
 
+use std::mem;
 use std::rc::Rc;
 
 use super::visitor::*;
+use super::super::lexer::Pos;
 
 #[derive(Debug, Clone)]
 pub struct Frame {
     pub table: RefCell<HashMap<String, Type>>,
+    pub locals: RefCell<HashMap<String, (Pos, bool)>>, // declared by `let` here, where, and whether anything read them
 }
 
 impl Frame {
     pub fn new() -> Self {
         Frame {
             table: RefCell::new(HashMap::new()),
+            locals: RefCell::new(HashMap::new()),
         }
     }
 
     pub fn from(table: HashMap<String, Type>) -> Self {
         Frame {
             table: RefCell::new(table),
+            locals: RefCell::new(HashMap::new()),
         }
     }
 
@@ -83,6 +88,14 @@ impl SymTab {
         self.current_frame_mut().assign(name.to_string(), t)
     }
 
+    // changes the type where the name was declared rather than shadowing it in the current frame
+    pub fn retype(&mut self, name: &str, t: Type) {
+        match self.stack.iter().rposition(|frame| frame.get(&name.to_string()).is_some()) {
+            Some(offset) => self.stack[offset].assign(name.to_string(), t),
+            None => self.assign_str(name, t),
+        }
+    }
+
     pub fn fetch(&self, name: &String) -> Option<Type> {
         if self.cache_mode {
             return self.fetch_cache(name)
@@ -111,6 +124,49 @@ impl SymTab {
         }
     }
 
+    // a `let` in the current frame, unread until `mark_read` says otherwise
+    pub fn declare(&mut self, name: &str, pos: Pos) {
+        self.current_frame().locals.borrow_mut().insert(name.to_string(), (pos, false));
+    }
+
+    // marks the innermost declaration of the name as read or not, and tells whether it was before
+    pub fn mark_read(&self, name: &str, read: bool) -> bool {
+        for frame in self.stack.iter().rev() {
+            if let Some(local) = frame.locals.borrow_mut().get_mut(name) {
+                return mem::replace(&mut local.1, read)
+            }
+        }
+
+        true
+    }
+
+    // what the current frame declared and never read, in source order
+    pub fn unread(&self) -> Vec<(String, Pos)> {
+        let mut unread = self.current_frame().locals.borrow().iter()
+            .filter(|(_, (_, read))| !read)
+            .map(|(name, (pos, _))| (name.clone(), pos.clone()))
+            .collect::<Vec<_>>();
+
+        unread.sort_by_key(|(_, pos)| ((pos.0).0, (pos.1).0));
+
+        unread
+    }
+
+    // every name visible from the current frame, innermost first
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+
+        for frame in self.stack.iter().rev() {
+            for name in frame.table.borrow().keys() {
+                if !names.contains(name) {
+                    names.push(name.clone())
+                }
+            }
+        }
+
+        names
+    }
+
     pub fn current_frame(&self) -> &Frame {
         self.stack.last().unwrap()
     }
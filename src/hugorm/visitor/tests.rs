@@ -0,0 +1,1886 @@
+use super::super::error::Response;
+use super::super::lexer::Pos;
+use super::*;
+use super::super::testing::*;
+
+#[test]
+fn when_defined_compiles_its_body_only_with_the_flag() {
+    let code = "when defined(\"loud\"):\n    print(\"on\")\nprint(\"always\")\n";
+
+    assert_eq!(run(code).unwrap(), vec!["always"]);
+    assert_eq!(run_with(code, |visitor, _| visitor.define("loud")).unwrap(), vec!["on", "always"]);
+}
+
+#[test]
+fn when_defined_doesnt_check_the_body_without_the_flag() {
+    let code = "when defined(\"host\"):\n    host_only(1)\n";
+
+    assert!(compile(code).is_ok());
+
+    let (result, reported) = reported(|| compile_with(code, |visitor, _| visitor.define("host")));
+
+    assert!(result.is_err());
+    assert!(reported.contains("host_only"), "{}", reported);
+}
+
+#[test]
+fn ints_up_to_2_pow_53_print_exactly() {
+    assert_eq!(run("print(9007199254740992)\nprint(-9007199254740992)\n").unwrap(), vec!["9007199254740992", "-9007199254740992"]);
+}
+
+#[test]
+fn ints_past_2_pow_53_are_rejected() {
+    let (result, reported) = reported(|| compile("print(9007199254740993)\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("too big to be represented exactly"), "{}", reported);
+}
+
+#[test]
+fn kebab_case_warnings_are_collected() {
+    let (visitor, _) = reported(|| compile("let foo- = 1\nprint(foo-)\n"));
+    let visitor = visitor.unwrap();
+
+    assert_eq!(visitor.warnings().len(), 1);
+    assert!(visitor.warnings()[0].message.contains("kebab-case"));
+    assert_eq!(visitor.warnings()[0].file, "<test>");
+    assert_eq!((visitor.warnings()[0].pos.0).0, 1);
+}
+
+#[test]
+fn emit_warning_reports_and_collects() {
+    let mut visitor = compile("let a = 1\nprint(a)\n").unwrap();
+
+    let pos = Pos((2, "print(a)".to_string()), (1, 5));
+    let (_, reported) = reported(|| visitor.emit_warning("from the host", &pos));
+
+    assert!(reported.contains("from the host"));
+    assert_eq!(visitor.warnings().last().unwrap().message, "from the host");
+    assert_eq!(visitor.warnings().last().unwrap().pos, pos);
+}
+
+#[test]
+fn assignments_evaluate_target_then_index_then_value() {
+    let code = "\
+let xs = [0, 0]
+let d = {x: 0}
+fun target():
+    print(\"target\")
+    return xs
+fun index():
+    print(\"index\")
+    return 1
+fun value():
+    print(\"value\")
+    return 5
+fun dict():
+    print(\"dict\")
+    return d
+target()[index()] = value()
+dict().x = value()
+print(xs)
+print(d)
+";
+
+    assert_eq!(
+        run(code).unwrap(),
+        vec!["target", "index", "value", "dict", "value", "[0, 5]", "{x: 5}"]
+    );
+}
+
+#[test]
+fn functions_declared_in_blocks_are_callable_there() {
+    let code = "\
+if true:
+    fun twice(x):
+        return x * 2
+    print(twice(2))
+else:
+    fun twice(x):
+        return x * 3
+    print(twice(2))
+let n = 0
+while n < 1:
+    fun inc(x): x + 1
+    n = inc(n)
+print(n)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["4", "1"]);
+}
+
+#[test]
+fn functions_declared_in_blocks_arent_in_scope_after() {
+    let (result, reported) = reported(|| compile("if true:\n    fun twice(x):\n        return x * 2\nprint(twice(2))\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("twice"), "{}", reported);
+}
+
+#[test]
+fn functions_declared_in_loops_dont_pile_up() {
+    let code = "\
+let n = 0
+while n < 3:
+    fun inc(x): x + 1
+    n = inc(n)
+let after = \"after\"
+print(n)
+print(after)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["3", "after"]);
+}
+
+#[test]
+fn locals_of_a_branch_that_didnt_run_dont_shift_later_ones() {
+    let code = "\
+fun pick(flag):
+    if flag:
+        let a = 1
+        print(a)
+    else:
+        let b = 2
+        fun shout(x): x * 10
+        print(shout(b))
+    let c = 3
+    return c
+print(pick(true))
+print(pick(false))
+";
+
+    assert_eq!(run(code).unwrap(), vec!["1", "3", "20", "3"]);
+}
+
+#[test]
+fn ints_and_floats_compare_with_each_other() {
+    let code = "\
+print(3 < 2.5)
+print(2.5 < 3)
+print(3 > 2.5)
+print(2.5 > 3)
+print(3 <= 3.0)
+print(2.5 <= 2)
+print(3 >= 3.0)
+print(2 >= 2.5)
+";
+
+    assert_eq!(
+        run(code).unwrap(),
+        vec!["false", "true", "true", "false", "true", "false", "true", "false"]
+    );
+}
+
+#[test]
+fn functions_in_dicts_are_called_straight_off_the_index() {
+    let code = "\
+let funcs = {
+    go: fun(a): return a * 2
+}
+print(funcs[\"go\"](21))
+";
+
+    assert_eq!(run(code).unwrap(), vec!["42"]);
+}
+
+#[test]
+fn calls_straight_off_an_index_are_arity_checked() {
+    let code = "\
+let funcs = {
+    go: fun(a): return a * 2
+}
+funcs[\"go\"](1, 2)
+";
+
+    let (result, reported) = reported(|| compile(code));
+
+    assert!(result.is_err());
+    assert!(reported.contains("expected 1"), "{}", reported);
+}
+
+#[test]
+fn optional_chains_stop_at_the_first_missing_level() {
+    let code = "\
+let config = {
+    server: {
+        port: 8080
+    }
+}
+print(config?.server?.port)
+print(config?.server?.host)
+print(config?.database?.port)
+print(config?[\"server\"]?[\"port\"])
+";
+
+    assert_eq!(run(code).unwrap(), vec!["8080", "nil", "nil", "8080"]);
+}
+
+#[test]
+fn optional_indexes_on_lists_are_nil_past_the_end() {
+    assert_eq!(run("let xs = [1, 2]\nprint(xs?[1])\nprint(xs?[2])\n").unwrap(), vec!["2", "nil"]);
+}
+
+#[test]
+fn optional_chains_evaluate_the_receiver_once() {
+    let code = "\
+let calls = 0
+fun config():
+    calls = calls + 1
+    return { server: { port: 1 } }
+print(config()?.server?.port)
+print(calls)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["1", "1"]);
+}
+
+#[test]
+fn consts_shadow_consts_of_outer_scopes() {
+    let code = "\
+const X = 1
+fun f():
+    const X = 2
+    return X
+print(f())
+print(X)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["2", "1"]);
+}
+
+#[test]
+fn consts_cant_be_redefined_in_the_same_scope() {
+    let (result, reported) = reported(|| compile("const X = 1\nconst X = 2\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("constant `X` is already defined in this scope"), "{}", reported);
+}
+
+#[test]
+fn consts_cant_be_redefined_in_the_same_function_scope() {
+    let (result, reported) = reported(|| compile("fun f():\n    const Y = 1\n    const Y = 2\n    return Y\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("constant `Y` is already defined in this scope"), "{}", reported);
+}
+
+#[test]
+fn for_walks_the_key_value_pairs_of_a_dict() {
+    let code = "\
+let stock = { apples: 3, pears: 4, plums: 5 }
+let total = 0
+for (name, count) in stock:
+    total = total + count
+    if count == 4:
+        print(name)
+print(total)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["pears", "12"]);
+}
+
+#[test]
+fn numeric_dict_keys_come_back_as_the_numbers_they_were_set_with() {
+    let code = "\
+let squares = {}
+squares[2] = 4
+squares[3] = 9
+let total = 0
+for (n, square) in squares:
+    total = total + n * 100 + square
+for n in squares:
+    total = total + n
+print(total)
+print(squares)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["518", "{2: 4, 3: 9}"]);
+}
+
+#[test]
+fn entries_is_no_global() {
+    assert!(reported(|| compile("print(entries({ a: 1 }))\n")).0.is_err());
+}
+
+#[test]
+fn top_level_returns_are_reported_at_the_keyword() {
+    let (result, reported) = reported(|| compile("let x = 1\nreturn x + 1\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("can't return from the top level of a file"), "{}", reported);
+    assert!(reported.contains("│ ^^^^^^\n"), "{}", reported);
+}
+
+#[test]
+fn returns_from_blocks_outside_functions_say_so() {
+    let (result, reported) = reported(|| compile("while true:\n    return 1\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("can't return from a block outside of a function"), "{}", reported);
+}
+
+#[test]
+fn recorded_types_are_looked_up_by_position() {
+    let code = "let n = 1 + 2\nlet f = n * 2 > 5\n";
+
+    let ast = parse(code).unwrap();
+    let (visitor, _) = compile_with(code, |visitor, _| visitor.record_types()).unwrap();
+
+    let (comparison, product) = match ast[1].node {
+        StatementNode::Declaration(_, _, Some(ref comparison)) => match comparison.node {
+            ExpressionNode::Binary(ref product, _, _) => (comparison.clone(), (**product).clone()),
+            _ => panic!("{:?}", comparison),
+        },
+        _ => panic!("{:?}", ast[1]),
+    };
+
+    assert_eq!(visitor.type_at(&comparison.pos).map(|t| t.node), Some(TypeNode::Bool));
+    assert_eq!(visitor.type_at(&product.pos).map(|t| t.node), Some(TypeNode::Int));
+    assert_eq!(compile(code).unwrap().type_at(&comparison.pos), None);
+}
+
+#[test]
+fn near_miss_assignments_suggest_the_name_in_scope() {
+    let (result, reported) = reported(|| compile("let count = 1\ncont = 2\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("can't assign non-existent `cont`, did you mean `count`?"), "{}", reported);
+}
+
+#[test]
+fn near_miss_reads_suggest_the_name_in_scope() {
+    let (result, reported) = reported(|| compile("let count = 1\nprint(coutn)\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("did you mean `count`?"), "{}", reported);
+}
+
+#[test]
+fn names_nothing_is_close_to_suggest_nothing() {
+    let (result, reported) = reported(|| compile("let count = 1\nprint(total)\n"));
+
+    assert!(result.is_err());
+    assert!(!reported.contains("did you mean"), "{}", reported);
+}
+
+#[test]
+fn consts_are_folded_to_literals() {
+    let code = "const N = 2 + 3\nconst HALF = N / 2\nprint(N)\nprint(HALF)\n";
+    let ir = format!("{:?}", compile(code).unwrap().build()).split_whitespace().collect::<String>();
+
+    // uses load the literal instead of reading the binding
+    assert!(!ir.contains("Var(Binding{name:\"N\"") && !ir.contains("Var(Binding{name:\"HALF\""), "{}", ir);
+    assert!(ir.contains("args:[Literal(Number(5.0,),),]"), "{}", ir);
+
+    assert_eq!(run(code).unwrap(), vec!["5", "2.5"]);
+}
+
+#[test]
+fn const_folding_compares_ints_with_floats() {
+    assert_eq!(run("const SMALL = 1 < 2.5\nconst SAME = 3.0 == 3\nprint(SMALL)\nprint(SAME)\n").unwrap(), vec!["true", "true"]);
+}
+
+#[test]
+fn const_arithmetic_mixing_ints_and_floats_is_a_type_error() {
+    let (result, output) = reported(|| compile("const X = 2 + 0.5\n"));
+
+    assert!(result.is_err());
+    assert!(output.contains("can't perform operation `Int + Float`"), "{}", output);
+}
+
+#[test]
+fn const_overflow_is_an_error() {
+    for code in [
+        "const X = 9223372036854775807 + 1\n",
+        "const X = (-9223372036854775807 - 1) % -1\n",
+        "const X = (-9223372036854775807 - 1) / -1\n",
+    ].iter() {
+        let (result, reported) = reported(|| compile(code));
+
+        assert!(result.is_err(), "{}", code);
+        assert!(reported.contains("integer overflow in constant"), "{}", reported);
+    }
+}
+
+#[test]
+fn while_let_runs_its_else_only_when_the_loop_never_ran() {
+    let code = "\
+fun none():
+    return nil
+while let x = none():
+    print(x)
+else:
+    print(\"empty\")
+let left = 2
+fun countdown():
+    if left > 0:
+        left = left - 1
+        return left + 1
+    return nil
+while let n = countdown():
+    print(n)
+else:
+    print(\"never\")
+";
+
+    assert_eq!(run(code).unwrap(), vec!["empty", "2", "1"]);
+}
+
+#[test]
+fn while_let_only_stops_at_nil() {
+    let code = "\
+let s = \"hi\"
+while let c = s:
+    print(c)
+    break
+let items = [\"a\", \"b\"]
+while let it = items[0]:
+    print(it)
+    break
+let flag = false
+while let f = flag:
+    print(f)
+    break
+";
+
+    assert_eq!(run(code).unwrap(), vec!["hi", "a", "false"]);
+}
+
+#[test]
+fn while_let_bindings_end_with_the_loop() {
+    let code = "\
+let x = \"outer\"
+fun none():
+    return nil
+while let x = none():
+    print(x)
+print(x)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["outer"]);
+
+    let (result, reported) = reported(|| compile("fun none():\n    return nil\nwhile let y = none():\n    print(y)\nprint(y)\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("no such variable `y`"), "{}", reported);
+}
+
+#[test]
+fn map_and_filter_build_lists_outside_a_for() {
+    let code = "\
+fun double(x): x * 2
+fun big(x): x > 1
+print(map([1, 2, 3], double))
+print(filter([1, 2, 3], big))
+";
+
+    assert_eq!(run(code).unwrap(), vec!["[2, 4, 6]", "[2, 3]"]);
+}
+
+#[test]
+fn for_over_map_calls_the_function_as_it_goes() {
+    let code = "\
+fun double(x):
+    print(\"doubling\")
+    return x * 2
+for y in map([1, 2, 3, 4], double):
+    print(y)
+    if y == 4:
+        break
+";
+
+    assert_eq!(run(code).unwrap(), vec!["doubling", "2", "doubling", "4"]);
+}
+
+#[test]
+fn for_over_map_of_filter_runs_both_per_element() {
+    let code = "\
+fun odd(x):
+    print(\"testing\")
+    return x % 2 == 1
+fun square(x): x * x
+for y in map(filter([1, 2, 3, 4, 5], odd), square):
+    print(y)
+    if y == 9:
+        break
+";
+
+    assert_eq!(run(code).unwrap(), vec!["testing", "1", "testing", "testing", "9"]);
+}
+
+#[test]
+fn map_and_filter_are_typed_as_lists() {
+    let code = "\
+let kept = filter([1, 2, 3], fun(x):
+    return x > 1
+)
+let halves = map([1, 2], fun(x):
+    return x * 2
+)
+fun double(x): x * 2
+let doubled = map([1, 2], double)
+";
+
+    let ast = parse(code).unwrap();
+    let (visitor, _) = compile_with(code, |visitor, _| visitor.record_types()).unwrap();
+
+    let typed = |i: usize| match ast[i].node {
+        StatementNode::Declaration(_, _, Some(ref value)) => visitor.type_at(&value.pos).map(|t| t.node),
+        _ => panic!("{:?}", ast[i]),
+    };
+
+    assert_eq!(typed(0), Some(TypeNode::Array(Box::new(TypeNode::Int))));
+    assert_eq!(typed(1), Some(TypeNode::Array(Box::new(TypeNode::Int))));
+    assert_eq!(typed(3), Some(TypeNode::Array(Box::new(TypeNode::Any))));
+}
+
+#[test]
+fn wrapping_operators_wrap_past_the_int_range() {
+    let code = "\
+let big = 9007199254740992
+let top = big *% 1024
+print(top)
+print(top *% 2)
+print(top -% 1)
+print(2 +% 3)
+";
+
+    // wrapped to -2^63 and back up to 2^63 - 1, which the float the vm keeps them in rounds
+    assert_eq!(run(code).unwrap(), vec!["-9223372036854776000", "0", "9223372036854776000", "5"]);
+}
+
+#[test]
+fn wrapping_operators_only_take_ints() {
+    let (result, reported) = reported(|| compile("print(1.5 +% 1)\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("Float"), "{}", reported);
+}
+
+#[test]
+fn generators_give_back_everything_they_yielded() {
+    let code = "\
+fun counted(n):
+    let i = 0
+    while i < n:
+        yield i
+        i = i + 1
+for x in counted(3):
+    print(x)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["0", "1", "2"]);
+}
+
+#[test]
+fn generators_run_to_the_end_before_their_caller_sees_anything() {
+    let code = "\
+fun noisy():
+    print(\"a\")
+    yield 1
+    print(\"b\")
+    yield 2
+for x in noisy():
+    print(x)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["a", "b", "1", "2"]);
+}
+
+#[test]
+fn yielding_from_an_endless_loop_is_rejected() {
+    let code = "\
+fun forever():
+    loop:
+        yield 1
+";
+
+    let (result, reported) = reported(|| compile(code));
+
+    assert!(result.is_err());
+    assert!(reported.contains("never ends"), "{}", reported);
+}
+
+#[test]
+fn yielding_from_a_loop_that_breaks_is_fine() {
+    let code = "\
+fun firsts():
+    let i = 0
+    loop:
+        yield i
+        i = i + 1
+        if i == 2:
+            break
+for x in firsts():
+    print(x)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["0", "1"]);
+}
+
+#[test]
+fn yielding_outside_a_function_is_rejected() {
+    let (result, reported) = reported(|| compile("yield 1\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("outside of a function"), "{}", reported);
+}
+
+#[test]
+fn loop_locals_arent_in_scope_after_the_loop() {
+    let code = "\
+for i in [1, 2]:
+    let inner = i
+print(inner)
+";
+
+    assert!(reported(|| compile(code)).0.is_err());
+}
+
+#[test]
+fn loop_locals_dont_clobber_an_outer_one() {
+    let code = "\
+let x = \"outer\"
+fun f():
+    for i in [1, 2]:
+        let x = i
+        print(x)
+    print(\"done\")
+f()
+print(x)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["1", "2", "done", "outer"]);
+}
+
+#[test]
+fn loop_locals_keep_their_type() {
+    let code = "\
+for i in [1, 2]:
+    let n = 1
+    print(n + \"text\")
+";
+
+    let (result, reported) = reported(|| compile(code));
+
+    assert!(result.is_err());
+    assert!(reported.contains("Int"), "{}", reported);
+}
+
+fn loop_warnings(code: &str) -> Vec<String> {
+    let (visitor, _) = reported(|| compile(code));
+
+    visitor.unwrap().warnings().iter().map(|warning| warning.message.clone()).filter(|message| message.contains("never ends")).collect()
+}
+
+#[test]
+fn loops_with_no_way_out_warn() {
+    assert_eq!(loop_warnings("loop:\n    print(1)\n").len(), 1);
+    assert_eq!(loop_warnings("while true:\n    print(1)\n").len(), 1);
+}
+
+#[test]
+fn loops_that_break_dont_warn() {
+    assert!(loop_warnings("let done = true\nloop:\n    if done:\n        break\n").is_empty());
+    assert!(loop_warnings("fun f():\n    loop:\n        return 1\nprint(f())\n").is_empty());
+}
+
+#[test]
+fn breaks_of_nested_loops_dont_count_for_the_outer_one() {
+    let code = "\
+loop:
+    while true:
+        break
+";
+
+    assert_eq!(loop_warnings(code).len(), 1);
+}
+
+fn imported(code: &str) -> (&'static super::super::source::Source, Vec<Statement>) {
+    let source = Box::leak(Box::new(super::super::source::Source::from("lib.hug", code.lines().map(String::from).collect())));
+
+    (source, parse(code).unwrap())
+}
+
+#[test]
+fn errors_in_other_files_are_reported_against_them() {
+    let mut visitor = compile("let a = 1\nprint(a)\n").unwrap();
+    let (source, ast) = imported("print(missing)\n");
+
+    let (result, reported) = reported(|| visitor.visit_source(source, &ast));
+
+    assert!(result.is_err());
+    assert!(reported.contains("lib.hug") && !reported.contains("<test>"), "{}", reported);
+}
+
+#[test]
+fn warnings_in_other_files_carry_their_name() {
+    let mut visitor = compile("let a = 1\nprint(a)\n").unwrap();
+    let (source, ast) = imported("let foo- = 1\nprint(foo-)\n");
+
+    reported(|| visitor.visit_source(source, &ast)).0.unwrap();
+
+    assert_eq!(visitor.warnings().last().unwrap().file, "lib.hug");
+}
+
+#[test]
+fn const_function_calls_on_constants_are_folded() {
+    let code = "const fun sq(x): return x * x\n\nprint(sq(4))\n";
+    let ir = format!("{:?}", compile(code).unwrap().build()).split_whitespace().collect::<String>();
+
+    assert!(ir.contains("args:[Literal(Number(16.0,),),]"), "{}", ir);
+    assert_eq!(run(code).unwrap(), vec!["16"]);
+}
+
+#[test]
+fn const_function_calls_on_variables_run_normally() {
+    assert_eq!(run("const fun sq(x): return x * x\n\nlet n = 3\nprint(sq(n))\n").unwrap(), vec!["9"]);
+}
+
+#[test]
+fn const_functions_only_use_constants() {
+    let (result, reported) = reported(|| compile("let k = 2\nconst fun f(x): return x * k\n\nprint(f(1))\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("isn't a constant"), "{}", reported);
+}
+
+#[test]
+fn const_functions_cant_be_rebound() {
+    let (result, reported) = reported(|| compile("const fun sq(x): return x * x\n\nsq = 1\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("can't reassign constant `sq`") && reported.contains("sq = 1"), "{}", reported);
+
+    assert_eq!(run("fun sq(x): return x * x\n\nsq = 1\nprint(sq)\n").unwrap(), vec!["1"]);
+}
+
+#[test]
+fn modules_export_their_functions_signatures() {
+    let code = "\
+fun add(a, b):
+    return a + b
+fun neg(a):
+    return -a
+let unexported = 1
+print(unexported)
+";
+
+    let module = compile(code).unwrap().into_module();
+
+    assert_eq!(module.signature("add"), Some(2));
+    assert_eq!(module.signature("neg"), Some(1));
+    assert_eq!(module.signature("unexported"), None);
+    assert!(module.call("add", vec!()).is_err());
+    assert!(module.call("missing", vec!()).is_err());
+}
+
+#[test]
+fn labeled_blocks_break_out_of_nested_loops_with_a_value() {
+    let code = "\
+let grid = [[3, 1, 4], [1, 5, 9]]
+
+let found = @search do:
+    for row in grid:
+        for cell in row:
+            if cell > 4:
+                break @search cell
+
+print(found)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["5"]);
+}
+
+#[test]
+fn plain_breaks_in_labeled_blocks_leave_the_loop_around_them() {
+    let code = "\
+let i = 0
+while i < 5:
+    i = i + 1
+    let found = @search do:
+        if i == 3:
+            break
+        break @search i
+    print(found)
+print(\"after\")
+";
+
+    assert_eq!(run(code).unwrap(), vec!["1", "2", "after"]);
+}
+
+#[test]
+fn plain_breaks_in_labeled_blocks_need_a_loop() {
+    let code = "\
+let found = @search do:
+    break
+print(found)
+";
+
+    let (result, reported) = reported(|| compile(code));
+
+    assert!(result.is_err());
+    assert!(reported.contains("need a loop"), "{}", reported);
+}
+
+#[test]
+fn overloads_are_picked_by_arity() {
+    let code = "\
+fun area(side):
+    return side * side
+
+fun area(width, height):
+    return width * height
+
+print(area(3))
+print(area(2, 5))
+";
+
+    assert_eq!(run(code).unwrap(), vec!["9", "10"]);
+}
+
+#[test]
+fn calls_matching_no_overload_are_rejected() {
+    let code = "\
+fun area(side):
+    return side * side
+
+fun area(width, height):
+    return width * height
+
+print(area(1, 2, 3))
+";
+
+    let (result, reported) = reported(|| compile(code));
+
+    assert!(result.is_err());
+    assert!(!reported.is_empty());
+}
+
+#[test]
+fn folding_constants_shrinks_the_ir() {
+    let folded = compile("const N = 2 * 3 + 4\nprint(N)\n").unwrap().ir_size();
+    let unfolded = compile("let n = 2\nprint(n * 3 + 4)\n").unwrap().ir_size();
+
+    assert!(folded < unfolded, "{} against {}", folded, unfolded);
+}
+
+#[test]
+fn spreads_copy_into_list_and_dict_literals() {
+    let code = "\
+let xs = [1, 2, 3]
+let base = {a: 1, b: 1}
+print([...xs, 4])
+print({...base, b: 2})
+print(xs)
+print(base)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["[1, 2, 3, 4]", "{a: 1, b: 2}", "[1, 2, 3]", "{a: 1, b: 1}"]);
+}
+
+#[test]
+fn spreads_only_take_their_own_kind_of_collection() {
+    let (result, reported) = reported(|| compile("let xs = [1]\nprint({...xs})\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("can only spread a dict into a dict"), "{}", reported);
+}
+
+#[test]
+fn spreads_fill_the_arguments_of_a_call() {
+    let code = "\
+fun point(x, y, z):
+    return x * 100 + y * 10 + z
+fun apply(f, args):
+    return f(...args)
+let coords = [1, 2, 3]
+print(point(...coords))
+print(point(9, ...[8], 7))
+print(apply(point, [4, 5, 6]))
+";
+
+    assert_eq!(run(code).unwrap(), vec!["123", "987", "456"]);
+}
+
+#[test]
+fn spreads_pick_an_overload_by_length() {
+    let code = "fun pick(a):\n    return a\nfun pick(a, b):\n    return b\nprint(pick(...[1]))\nprint(pick(...[1, 2]))\n";
+
+    assert_eq!(run(code).unwrap(), vec!["1", "2"]);
+}
+
+#[test]
+fn spread_calls_run_their_arguments_in_order_and_where_written() {
+    let code = "\
+fun said(x):
+    print(x)
+    return x
+fun pair(a, b):
+    return a + b
+fun rest():
+    print(\"rest\")
+    return [2]
+print(pair(said(1), ...rest()))
+print(false and pair(...rest()))
+let xs = [0, 2]
+while pair(...xs) < 4:
+    xs[0] = xs[0] + 1
+print(xs[0])
+";
+
+    assert_eq!(run(code).unwrap(), vec!["1", "rest", "3", "false", "2"]);
+}
+
+#[test]
+fn spreads_cant_leave_too_many_arguments() {
+    let (result, reported) = reported(|| compile("fun f(a):\n    return a\nprint(f(1, 2, ...[3]))\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("`f` takes 1 arguments"), "{}", reported);
+}
+
+#[test]
+fn arithmetic_on_dicts_blames_the_dict() {
+    let (result, reported) = reported(|| compile("print({a: 1} + 1)\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("can't perform `+` on a dict"), "{}", reported);
+}
+
+#[test]
+fn constant_expressions_evaluate_to_literals() {
+    let visitor = compile("let x = 1\nprint(x)\n").unwrap();
+    let expression = |code: &str| match parse(code).unwrap()[0].node {
+        StatementNode::Expression(ref expression) => expression.clone(),
+        ref node => panic!("{:?}", node),
+    };
+
+    // zub's literals don't compare
+    let evaluated = |code: &str| format!("{:?}", visitor.eval_const(&expression(code)));
+
+    assert_eq!(evaluated("2 + 3 * 4\n"), "Ok(Number(14.0))");
+    assert_eq!(evaluated("\"a\" ++ \"b\"\n"), "Ok(String(\"ab\"))");
+    assert_eq!(evaluated("nil\n"), "Ok(Nil)");
+}
+
+#[test]
+fn variables_arent_constant() {
+    let visitor = compile("let x = 1\nprint(x)\n").unwrap();
+    let ast = parse("x + 1\n").unwrap();
+
+    let expression = match ast[0].node {
+        StatementNode::Expression(ref expression) => expression,
+        ref node => panic!("{:?}", node),
+    };
+
+    let (result, reported) = reported(|| visitor.eval_const(expression));
+
+    assert!(result.is_err());
+    assert!(reported.contains("`x`"), "{}", reported);
+}
+
+#[test]
+fn strict_mode_rejects_arithmetic_on_any() {
+    let code = "fun inc(x):\n    return x + 1\nprint(inc(1))\n";
+
+    assert_eq!(run(code).unwrap(), vec!["2"]);
+
+    let (result, reported) = reported(|| compile_with(code, |visitor, _| visitor.set_strict(true)));
+
+    assert!(result.is_err());
+    assert!(reported.contains("strict mode"), "{}", reported);
+}
+
+#[test]
+fn strict_mode_accepts_narrowed_values() {
+    let code = "fun inc(x):\n    if x is Int:\n        return x + 1\n    return 0\nprint(inc(1))\n";
+
+    assert_eq!(run_with(code, |visitor, _| visitor.set_strict(true)).unwrap(), vec!["2"]);
+}
+
+#[test]
+fn split_gives_a_list_of_strings() {
+    let code = "let parts = split(\"a,b,c\", \",\")\nprint(parts)\n";
+
+    let ast = parse(code).unwrap();
+    let (visitor, _) = compile_with(code, |visitor, _| visitor.record_types()).unwrap();
+
+    let value = match ast[0].node {
+        StatementNode::Declaration(_, _, Some(ref value)) => value,
+        ref node => panic!("{:?}", node),
+    };
+
+    assert_eq!(visitor.type_at(&value.pos).map(|t| t.node), Some(TypeNode::Array(Box::new(TypeNode::Str))));
+    assert_eq!(run(code).unwrap(), vec!["[\"a\", \"b\", \"c\"]"]);
+}
+
+#[test]
+fn split_parts_cant_be_used_as_numbers() {
+    let (result, reported) = reported(|| compile("let parts = split(\"1,2\", \",\")\nprint(parts[0] + 1)\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("Str"), "{}", reported);
+}
+
+#[test]
+fn text_intrinsics_transform_strings() {
+    let code = "\
+print(upper(\"ab\"))
+print(lower(\"AB\"))
+print(trim(\"  padded \"))
+print(replace(\"aaa\", \"a\", \"b\"))
+";
+
+    assert_eq!(run(code).unwrap(), vec!["AB", "ab", "padded", "bbb"]);
+}
+
+#[test]
+fn text_intrinsics_only_take_strings() {
+    let (result, reported) = reported(|| compile("print(upper(1))\n"));
+
+    assert!(result.is_err());
+    assert!(!reported.is_empty());
+}
+
+#[test]
+fn counted_loops_run_their_count_and_stop_on_break() {
+    let code = "\
+loop 3:
+    print(\"again\")
+let seen = 0
+loop 10:
+    seen = seen + 1
+    if seen == 4:
+        break
+print(seen)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["again", "again", "again", "4"]);
+}
+
+#[test]
+fn compilations_expose_every_stage() {
+    let code = "let a = 1\nprint(a)\n";
+    let compilation = super::super::compilation::Compilation::new(source(code), &[("print", TypeNode::Func(1))]).unwrap();
+
+    assert_eq!(compilation.tokens().iter().map(|token| token.lexeme.as_str()).collect::<Vec<_>>(), vec!["let", "a", "=", "1", "\n", "print", "(", "a", ")", "\n"]);
+    assert_eq!(compilation.ast(), parse(code).unwrap().as_slice());
+    assert!(!compilation.ir().is_empty());
+}
+
+#[test]
+fn interpolation_shows_values_the_way_print_does() {
+    let code = "\
+let flag = true
+let point = {x: 1}
+let ratio = 2.5
+fun f(): 1
+
+print(\"${flag} ${point} ${ratio} ${[1, 2]} ${1 + 2}\")
+print(\"f is ${f}\" ++ \"!\")
+";
+
+    let printed = run(code).unwrap();
+
+    assert_eq!(printed[0], "true {x: 1} 2.5 [1, 2] 3");
+    assert!(printed[1].starts_with("f is ") && printed[1].ends_with("!"), "{:?}", printed);
+}
+
+#[test]
+fn concatenating_leaves_strings_as_they_are() {
+    let code = "let name = \"hugorm\"\nprint(\"hi \" ++ name ++ '!')\nprint(\"${name}\" ++ 1)\n";
+
+    assert_eq!(run(code).unwrap(), vec!["hi hugorm!", "hugorm1"]);
+}
+
+#[test]
+fn ranges_are_lists_of_ints() {
+    let code = "\
+let a = range(3)
+let b = range(1, 4)
+let c = 0..2
+let d = 0..=2
+print(a)
+print(b)
+print(c)
+print(d)
+";
+
+    let ast = parse(code).unwrap();
+    let (visitor, _) = compile_with(code, |visitor, _| visitor.record_types()).unwrap();
+
+    for statement in ast[.. 4].iter() {
+        match statement.node {
+            StatementNode::Declaration(_, _, Some(ref value)) => assert_eq!(visitor.type_at(&value.pos).map(|t| t.node), Some(TypeNode::Array(Box::new(TypeNode::Int)))),
+            ref node => panic!("{:?}", node),
+        }
+    }
+
+    assert_eq!(run(code).unwrap(), vec!["[0, 1, 2]", "[1, 2, 3]", "[0, 1]", "[0, 1, 2]"]);
+}
+
+#[test]
+fn arithmetic_on_ranges_is_rejected_through_variables_too() {
+    for code in ["print((0..3) + 1)\n", "let r = range(3)\nprint(r * 2)\n"].iter() {
+        let (result, reported) = reported(|| compile(code));
+
+        assert!(result.is_err(), "{}", code);
+        assert!(reported.contains("on a list"), "{}", reported);
+    }
+}
+
+#[test]
+fn ranges_need_a_positive_step() {
+    let (result, reported) = reported(|| compile("print(range(0, 3, 0))\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("step above 0"), "{}", reported);
+}
+
+#[test]
+fn compound_boolean_assignments_short_circuit() {
+    let code = "\
+fun check(result):
+    print(\"checked\")
+    return result
+let flag = false
+flag &&= check(true)
+print(flag)
+let other = true
+other ||= check(false)
+print(other)
+flag ||= check(true)
+print(flag)
+";
+
+    assert_eq!(run(code).unwrap(), vec!["false", "true", "checked", "true"]);
+}
+
+#[test]
+fn compound_boolean_assignments_need_bools() {
+    let (result, reported) = reported(|| compile("let n = 1\nn &&= true\n"));
+
+    assert!(result.is_err());
+    assert!(!reported.is_empty());
+}
+
+#[test]
+fn interface_functions_are_called_on_conforming_receivers() {
+    let code = "\
+interface Shape:
+    fun area():
+        return self.width * self.height
+
+    fun describe():
+        print(self.area())
+
+let rect = { width: 3, height: 4 }
+print(rect.area())
+rect.describe()
+";
+
+    assert_eq!(run(code).unwrap(), vec!["12", "12"]);
+}
+
+#[test]
+fn interface_functions_leave_other_receivers_alone() {
+    let code = "\
+interface Shape:
+    fun area():
+        return self.width * self.height
+
+let circle = { radius: 1, area: fun(): return 3 }
+print(circle.area())
+";
+
+    assert_eq!(run(code).unwrap(), vec!["3"]);
+}
+
+#[test]
+fn interface_functions_are_methods() {
+    let visitor = compile("interface Shape:\n    fun area():\n        return self.width\n").unwrap();
+    let compiled = format!("{:?}", visitor.build());
+
+    assert!(compiled.contains("method: true"), "{}", compiled);
+}
+
+#[test]
+fn interface_functions_cant_be_init() {
+    let (result, reported) = reported(|| compile("interface Shape:\n    fun init():\n        return self.width\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("`init`"), "{}", reported);
+}
+
+#[test]
+fn empty_arrays_are_typed_from_their_annotation() {
+    let code = "let xs: Array(int) = []\nfun first(ys: Array(str)):\n    return ys\nfirst([])\n";
+
+    let ast = parse(code).unwrap();
+    let (visitor, _) = compile_with(code, |visitor, _| visitor.record_types()).unwrap();
+
+    let declared = match ast[0].node {
+        StatementNode::Declaration(_, _, Some(ref value)) => value.pos.clone(),
+        _ => panic!("{:?}", ast[0]),
+    };
+
+    let passed = match ast[2].node {
+        StatementNode::Expression(Expression { node: ExpressionNode::Call(_, ref args, _), .. }) => args[0].pos.clone(),
+        _ => panic!("{:?}", ast[2]),
+    };
+
+    assert_eq!(visitor.type_at(&declared).map(|t| t.node), Some(TypeNode::Array(Box::new(TypeNode::Int))));
+    assert_eq!(visitor.type_at(&passed).map(|t| t.node), Some(TypeNode::Array(Box::new(TypeNode::Str))));
+}
+
+#[test]
+fn anonymous_functions_are_checked_against_their_annotation() {
+    let (result, reported) = reported(|| compile("let f: Func(int) = fun(a, b): return a\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("expected a function of 1 argument, but this one takes 2"), "{}", reported);
+}
+
+#[test]
+fn arguments_are_checked_against_annotated_params() {
+    let code = "\
+fun apply(x: int, f: Func(int)):
+    return f(x)
+print(apply(2, fun(n):
+    return n + 1
+))
+";
+
+    assert_eq!(run(code).unwrap(), vec!["3"]);
+
+    for (code, expected) in &[
+        ("fun apply(x: int, f: Func(int)):\n    return f(x)\napply(2, fun(a, b):\n    return a\n)\n", "takes 2"),
+        ("fun twice(x: int):\n    return x * 2\ntwice(\"two\")\n", "`x` is annotated as `Int`, but got `Str`"),
+        ("fun twice(x: int):\n    return x * 2\nlet y = twice(\"two\")\n", "`x` is annotated as `Int`, but got `Str`"),
+        ("fun twice(x: int):\n    return x * 2\nlet y = twice(1, 2)\n", "expected 1 but got 2"),
+    ] {
+        let (result, reported) = reported(|| compile(code));
+
+        assert!(result.is_err(), "{}", code);
+        assert!(reported.contains(expected), "{}", reported);
+    }
+}
+
+#[test]
+fn number_suffixes_decide_the_type() {
+    let code = "let x = 5f\nlet y = 5i\n";
+
+    let ast = parse(code).unwrap();
+    let (visitor, _) = compile_with(code, |visitor, _| visitor.record_types()).unwrap();
+
+    let typed = |i: usize| match ast[i].node {
+        StatementNode::Declaration(_, _, Some(ref value)) => visitor.type_at(&value.pos).map(|t| t.node),
+        _ => panic!("{:?}", ast[i]),
+    };
+
+    assert_eq!(typed(0), Some(TypeNode::Float));
+    assert_eq!(typed(1), Some(TypeNode::Int));
+}
+
+#[test]
+fn recovery_records_every_failing_statement() {
+    let code = "let a = 1 + \"x\"\nprint(1)\nlet b = nope\n";
+
+    let mut visitor = Visitor::new(source(code));
+    visitor.set_global("print", TypeNode::Func(1));
+    visitor.set_recovery(true);
+
+    let ast = parse(code).unwrap();
+    let (result, reported) = reported(|| visitor.visit(&ast));
+
+    assert!(result.is_err());
+    assert_eq!(visitor.errors.len(), 2);
+
+    for (error, line) in visitor.errors.iter().zip(&["let a", "let b"]) {
+        match *error {
+            Response::Wrong(ref report) => assert!(report.contains(line) && reported.contains(report.as_str()), "{}", report),
+            _ => panic!(),
+        }
+    }
+}
+
+#[test]
+fn recovery_forgets_what_a_failing_statement_declared() {
+    let code = "fun f():\n    return 1 + \"x\"\nprint(f)\nlet foo- = 1 + \"x\"\n";
+
+    let mut visitor = Visitor::new(source(code));
+    visitor.set_global("print", TypeNode::Func(1));
+    visitor.set_recovery(true);
+
+    let ast = parse(code).unwrap();
+    assert!(reported(|| visitor.visit(&ast)).0.is_err());
+    assert_eq!(visitor.errors.len(), 3);
+    assert!(visitor.warnings().is_empty());
+
+    match visitor.errors[1] {
+        Response::Wrong(ref report) => assert!(report.contains("`f`"), "{}", report),
+        _ => panic!(),
+    }
+}
+
+fn spaceship(_: &mut zub::vm::Heap<zub::vm::Object>, args: &[zub::vm::Value]) -> zub::vm::Value {
+    let (a, b) = (args[1].as_float(), args[2].as_float());
+
+    zub::vm::Value::float(if a < b { -1.0 } else if a > b { 1.0 } else { 0.0 })
+}
+
+#[test]
+fn registered_operators_lex_parse_and_call_their_native() {
+    let code = "print(1 <> 2)\nprint(2 + 1 <> 2)\n/* still a comment */\n";
+
+    let printed = run_with(code, |visitor, vm| visitor.register_operator(vm, "<>", 1, spaceship)).unwrap();
+
+    assert_eq!(printed, vec!["-1", "1"]);
+}
+
+#[test]
+fn registered_operators_dont_take_comments_away() {
+    let printed = run_with("/* a */ print(1)\n", |visitor, vm| visitor.register_operator(vm, "/*", 5, spaceship)).unwrap();
+
+    assert_eq!(printed, vec!["1"]);
+}
+
+#[test]
+fn annotated_lets_are_nil_until_assigned() {
+    assert_eq!(run("let x: int\nprint(x)\nx = 5\nprint(x + 1)\n").unwrap(), vec!["nil", "6"]);
+
+    let (result, reported) = reported(|| compile("let x: int\nprint(x + 1)\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("Nil"), "{}", reported);
+}
+
+#[test]
+fn assignments_have_to_fit_the_annotation() {
+    for code in &["let x: int = \"s\"\n", "let x: int\nx = \"s\"\n", "let x: int = 1\nx = \"s\"\n"] {
+        let (result, reported) = reported(|| compile(code));
+
+        assert!(result.is_err(), "{}", code);
+        assert!(reported.contains("`x` is annotated as `Int`, but got `Str`"), "{}", reported);
+    }
+
+    assert_eq!(run("let x: int = 1\nlet y = x\ny = \"s\"\nprint(y)\n").unwrap(), vec!["s"]);
+}
+
+// how many functions a program compiles to, with or without inlining
+fn functions(code: &str, inline: bool) -> usize {
+    let (visitor, _) = compile_with(code, |visitor, _| visitor.set_inline(inline)).unwrap();
+
+    format!("{:?}", visitor.build()).matches("IrFunction {").count()
+}
+
+#[test]
+fn single_use_functions_are_inlined() {
+    let code = "fun double(x): return x * 2\n\nprint(double(21))\n";
+
+    assert_eq!(functions(code, false), 1);
+    assert_eq!(functions(code, true), 0);
+    assert_eq!(run_with(code, |visitor, _| visitor.set_inline(true)).unwrap(), vec!["42"]);
+}
+
+#[test]
+fn only_single_use_non_recursive_functions_are_inlined() {
+    for code in &[
+        "fun double(x): return x * 2\n\nprint(double(1))\nprint(double(2))\n",
+        "fun down(n): return down(n - 1)\n\nlet f = down\n",
+        "fun down(n): return down(n - 1)\n\nprint(1)\n",
+    ] {
+        assert_eq!(functions(code, true), functions(code, false), "{}", code);
+    }
+}
+
+#[test]
+fn annotated_params_are_typed_in_the_body() {
+    let (result, reported) = reported(|| compile("fun f(a: str, b):\n    return a - b\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("Str"), "{}", reported);
+
+    assert_eq!(run("fun f(a: int, b):\n    return a - b\nprint(f(3, 1))\n").unwrap(), vec!["2"]);
+    assert_eq!(run("fun g(a, b):\n    return a ++ b\nprint(g(\"x\", \"y\"))\n").unwrap(), vec!["xy"]);
+}
+
+#[test]
+fn unknown_param_types_are_errors() {
+    let (result, reported) = reported(|| parse("fun f(a: num):\n    return a\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("unknown type `num`"), "{}", reported);
+}
+
+#[test]
+fn embed_inlines_a_file_read_once() {
+    let path = std::env::temp_dir().join(format!("hugorm-embed-{}.txt", std::process::id()));
+    std::fs::write(&path, "hello").unwrap();
+
+    let code = format!("const TEXT = embed(\"{0}\")\nprint(TEXT)\nprint(embed(\"{0}\"))\n", path.display());
+
+    assert_eq!(run(&code).unwrap(), vec!["hello", "hello"]);
+
+    let (mut visitor, _) = compile_with(&code, |_, _| ()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(visitor.visit(&parse(&format!("print(embed(\"{}\"))\n", path.display())).unwrap()).is_ok());
+}
+
+#[test]
+fn embedding_a_missing_file_is_an_error() {
+    let (result, reported) = reported(|| compile("print(embed(\"/no/such/hugorm/file.txt\"))\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("can't embed `/no/such/hugorm/file.txt`"), "{}", reported);
+}
+
+#[test]
+fn element_assignments_in_blocks_retype_the_outer_list() {
+    let code = "let xs = [1, 2]\nlet d = { a: 1 }\nif true:\n    xs[0] = \"a\"\n    d.a = \"b\"\nlet ys = xs\nlet e = d\nprint(ys)\nprint(e)\n";
+
+    let ast = parse(code).unwrap();
+    let (visitor, _) = compile_with(code, |visitor, _| visitor.record_types()).unwrap();
+
+    let copied = |i: usize| match ast[i].node {
+        StatementNode::Declaration(_, _, Some(ref value)) => visitor.type_at(&value.pos).map(|t| t.node),
+        _ => panic!("{:?}", ast[i]),
+    };
+
+    assert_eq!(copied(3), Some(TypeNode::Array(Box::new(TypeNode::Any))));
+    assert_eq!(copied(4), Some(TypeNode::Dict(vec!(("a".to_string(), TypeNode::Str)))));
+}
+
+#[test]
+fn nil_guards_narrow_only_their_branch() {
+    let guarded = "\
+let found
+if found != nil:
+    print(found + 1)
+if found == nil:
+    print(\"nothing\")
+else:
+    print(found * 2)
+";
+
+    assert_eq!(run(guarded).unwrap(), vec!["nothing"]);
+
+    for code in &[
+        "let found\nprint(found + 1)\n",
+        "let found\nif found != nil:\n    print(1)\nprint(found + 1)\n",
+        "let found\nif found == nil:\n    print(found + 1)\n",
+    ] {
+        let (result, reported) = reported(|| compile(code));
+
+        assert!(result.is_err(), "{}", code);
+        assert!(reported.contains("Nil"), "{}", reported);
+    }
+}
+
+#[test]
+fn nil_guards_narrow_annotated_lets_to_their_annotation() {
+    let code = "let n: int\nif n != nil:\n    let m = n\n    print(m)\n";
+
+    let ast = parse(code).unwrap();
+    let (visitor, _) = compile_with(code, |visitor, _| visitor.record_types()).unwrap();
+
+    let guarded = match ast[1].node {
+        StatementNode::If(_, ref body, _) => match body[0].node {
+            StatementNode::Declaration(_, _, Some(ref value)) => value.pos.clone(),
+            ref node => panic!("{:?}", node),
+        },
+        ref node => panic!("{:?}", node),
+    };
+
+    assert_eq!(visitor.type_at(&guarded).map(|t| t.node), Some(TypeNode::Int));
+}
+
+#[test]
+fn dict_reads_are_typed_by_their_fields() {
+    let code = "\
+let d = { a: 1, b: 2 }
+let key = \"b\"
+let x = d.a
+let y = d[key]
+let mixed = { a: 1, b: \"s\" }
+let z = mixed[key]
+let w = mixed.b
+";
+
+    let ast = parse(code).unwrap();
+    let (visitor, _) = compile_with(code, |visitor, _| visitor.record_types()).unwrap();
+
+    let typed = |i: usize| match ast[i].node {
+        StatementNode::Declaration(_, _, Some(ref value)) => visitor.type_at(&value.pos).map(|t| t.node),
+        _ => panic!("{:?}", ast[i]),
+    };
+
+    assert_eq!(typed(2), Some(TypeNode::Int));
+    assert_eq!(typed(3), Some(TypeNode::Int));
+    assert_eq!(typed(5), Some(TypeNode::Any));
+    assert_eq!(typed(6), Some(TypeNode::Str));
+}
+
+#[test]
+fn nil_dict_fields_are_refused() {
+    let (result, reported) = reported(|| compile("let d = { a: nil }\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("can't put nil in a dict"), "{}", reported);
+}
+
+#[test]
+fn reset_keeps_globals_but_not_the_last_files_variables() {
+    let mut visitor = compile("let first = 1\nfun helper():\n    return first\nprint(helper())\n").unwrap();
+
+    visitor.reset(true);
+
+    let (result, output) = reported(|| visitor.visit(&parse("print(first)\n").unwrap()));
+
+    assert!(result.is_err());
+    assert!(output.contains("first"), "{}", output);
+
+    visitor.reset(true);
+
+    assert!(visitor.visit(&parse("print(abs(-1))\n").unwrap()).is_ok());
+
+    visitor.reset(false);
+
+    let (result, output) = reported(|| visitor.visit(&parse("print(1)\n").unwrap()));
+
+    assert!(result.is_err());
+    assert!(output.contains("print"), "{}", output);
+}
+
+#[test]
+fn keyword_arguments_mix_with_positional_ones() {
+    let code = "fun greet(greeting, name, mark):\n    return greeting ++ \", \" ++ name ++ mark\nprint(greet(\"hi\", mark = \"!\", name = \"you\"))\nprint(greet(\"hi\", \"me\", mark = \"?\"))\n";
+
+    assert_eq!(run(code).unwrap(), vec!["hi, you!", "hi, me?"]);
+}
+
+#[test]
+fn unknown_keyword_arguments_are_errors() {
+    let (result, reported) = reported(|| compile("fun f(a, b):\n    return a\nf(1, c = 2)\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("`f` has no param `c`, only `a`, `b`"), "{}", reported);
+}
+
+#[test]
+fn keyword_arguments_run_in_the_order_theyre_written() {
+    let code = "fun said(x):\n    print(x)\n    return x\nfun pair(a, b):\n    return a - b\nprint(pair(b = said(1), a = said(2)))\n";
+
+    assert_eq!(run(code).unwrap(), vec!["1", "2", "1"]);
+}
+
+// what each warning said, in order
+fn warned(code: &str) -> Vec<String> {
+    let (visitor, _) = reported(|| compile(code));
+
+    visitor.unwrap().warnings().iter().map(|warning| warning.message.clone()).collect()
+}
+
+#[test]
+fn kebab_case_inside_names_warns_wherever_a_name_is_bound() {
+    let suggested = "kebab-case `foo-bar` looks like a subtraction, `foo_bar` doesn't";
+
+    assert_eq!(warned("let foo-bar = 1\nprint(foo-bar)\n"), vec![suggested]);
+    assert_eq!(warned("fun foo-bar():\n    return 1\nprint(foo-bar())\n"), vec![suggested]);
+    assert_eq!(warned("fun f(foo-bar):\n    return foo-bar\nprint(f(1))\n"), vec![suggested]);
+    assert_eq!(warned("print(map([1], fun(foo-bar):\n    return foo-bar\n))\n"), vec![suggested]);
+    assert_eq!(warned("fun f(foo-):\n    return foo-\nprint(f(1))\n"), vec!["kebab-case at identifier end is not cool"]);
+}
+
+#[test]
+fn kebab_case_assignment_targets_warn() {
+    let (visitor, _) = reported(|| compile("let foo-bar = 1\nfoo-bar = 2\nprint(foo-bar)\n"));
+    let visitor = visitor.unwrap();
+
+    assert_eq!(visitor.warnings().len(), 2);
+    assert!(visitor.warnings()[1].message.contains("`foo_bar`"));
+    assert_eq!((visitor.warnings()[1].pos.0).0, 2);
+}
+
+#[test]
+fn names_starting_with_a_dash_are_errors() {
+    let (result, _) = reported(|| parse("let -foo = 1\n"));
+
+    assert!(result.is_err());
+
+    let (result, output) = reported(|| run_ast(&[Statement::let_("-foo", Expression::int(1))]));
+
+    assert!(result.is_err());
+    assert!(output.contains("`-foo` can't start with `-`"), "{}", output);
+
+    let (result, output) = reported(|| run_ast(&[Statement::function("f", &["-foo"], Vec::new())]));
+
+    assert!(result.is_err());
+    assert!(output.contains("`-foo` can't start with `-`"), "{}", output);
+}
+
+// the warnings with unused results checked
+fn unused(code: &str) -> Vec<String> {
+    let (compiled, _) = reported(|| compile_with(code, |visitor, _| visitor.set_unused_results(true)));
+
+    compiled.unwrap().0.warnings().iter().map(|warning| warning.message.clone()).collect()
+}
+
+#[test]
+fn unused_results_warn_for_bare_arithmetic() {
+    assert_eq!(unused("1 + 1\n"), vec!["result of expression is unused"]);
+    assert_eq!(unused("let a = 2\na * 3\n"), vec!["result of expression is unused"]);
+    assert!(warned("1 + 1\n").is_empty());
+}
+
+#[test]
+fn unused_results_dont_warn_for_calls() {
+    assert!(unused("fun one():\n    return 1\none()\nprint(1)\n").is_empty());
+    assert!(unused("nil\n").is_empty());
+}
+
+#[test]
+fn unused_locals_warn_when_their_scope_ends() {
+    assert_eq!(warned("fun f():\n    let x = 1\n    return 2\nprint(f())\n"), vec!["unused variable `x`"]);
+    assert_eq!(warned("fun f():\n    let a = 1\n    let b = 2\n    if true:\n        let c = 3\n    return a\nprint(f())\n"), vec!["unused variable `c`", "unused variable `b`"]);
+
+    // writing to it isn't using it
+    assert_eq!(warned("fun f():\n    let x = 1\n    x = 2\n    return 2\nprint(f())\n"), vec!["unused variable `x`"]);
+
+    assert!(warned("fun f():\n    let x = 1\n    return x\nprint(f())\n").is_empty());
+}
+
+#[test]
+fn unused_locals_starting_with_an_underscore_dont_warn() {
+    assert!(warned("fun f():\n    let _x = 1\n    return 2\nprint(f())\n").is_empty());
+}
+
+#[test]
+fn locals_only_read_by_the_type_check_are_used() {
+    // a release build drops the `check` after checking its type, which still reads `x`
+    let code = "fun f():\n    let x = 1\n    check x > 0\n    return 2\nprint(f())\n";
+    let (compiled, _) = reported(|| compile_with(code, |visitor, _| visitor.set_release(true)));
+
+    assert!(compiled.unwrap().0.warnings().is_empty());
+}
+
+#[test]
+fn numeric_builtins_compute_and_keep_their_type() {
+    assert_eq!(run("print(abs(-3))\nprint(max(1, 2))\nprint(min(1, 2))\nprint(abs(-2.5))\n").unwrap(), vec!["3", "2", "1", "2.5"]);
+
+    let code = "let a = abs(-3)\nlet b = max(1.5, 2.5)\n";
+    let (visitor, _) = compile_with(code, |visitor, _| visitor.record_types()).unwrap();
+    let ast = parse(code).unwrap();
+
+    let types = ast.iter().map(|statement| match statement.node {
+        StatementNode::Declaration(_, _, Some(ref value)) => visitor.type_at(&value.pos).map(|t| t.node),
+        _ => None,
+    }).collect::<Vec<_>>();
+
+    assert_eq!(types, vec![Some(TypeNode::Int), Some(TypeNode::Float)]);
+}
+
+#[test]
+fn numeric_builtins_take_only_numbers() {
+    let (result, reported) = reported(|| compile("print(abs(\"x\"))\n"));
+
+    assert!(result.is_err());
+    assert!(reported.contains("`abs` needs a number, but got `Str`"), "{}", reported);
+}
+
+#[test]
+fn chained_assignments_assign_every_target() {
+    let code = "let a = 0\nlet b = 0\nlet c = 0\na = b = c = 4\nprint([a, b, c])\nlet row = [0, 0]\nrow[1] = a = 7\nprint([row, a])\n";
+
+    assert_eq!(run(code).unwrap(), vec!["[4, 4, 4]", "[[0, 7], 7]"]);
+}
+
+#[test]
+fn blocks_keep_their_names_to_themselves() {
+    let block = Statement::from(StatementNode::Block(vec!(Statement::let_("x", Expression::int(1)))));
+    let print = |name: &str| Statement::expression(Expression::call(Expression::identifier("print"), vec!(Expression::identifier(name))));
+
+    let (result, reported) = reported(|| run_ast(&[block.clone(), print("x")]));
+
+    assert!(result.is_err());
+    assert!(reported.contains("no such variable `x`"), "{}", reported);
+
+    assert_eq!(run_ast(&[Statement::let_("x", Expression::int(2)), block, print("x")]).unwrap(), vec!["2"]);
+}
+
+#[test]
+fn chained_assignments_evaluate_the_value_once() {
+    let code = "\
+let calls = [0]
+fun next():
+    calls[0] = calls[0] + 1
+    return calls[0] * 10
+let a = 0
+let b = 0
+let c = 0
+a = b = c = next()
+print([a, b, c])
+print(calls[0])
+";
+
+    assert_eq!(run(code).unwrap(), vec!["[10, 10, 10]", "1"]);
+}
+
+#[test]
+fn chained_assignments_work_in_loops_and_functions() {
+    let code = "\
+let a = 0
+let b = 0
+let i = 0
+while i < 3:
+    a = b = i * 2
+    i = i + 1
+print([a, b])
+fun f():
+    let x = 0
+    let y = 0
+    x = y = 5
+    return x + y
+print(f())
+";
+
+    assert_eq!(run(code).unwrap(), vec!["[4, 4]", "10"]);
+}
+
+#[test]
+fn slices_take_either_bound_or_neither() {
+    let code = "let arr = [1, 2, 3, 4]\nprint(arr[:2])\nprint(arr[2:])\nprint(arr[:])\nprint(arr[1:3])\nprint(arr[1])\n";
+
+    assert_eq!(run(code).unwrap(), vec!["[1, 2]", "[3, 4]", "[1, 2, 3, 4]", "[2, 3]", "2"]);
+}
+
+#[test]
+fn slices_of_strings_are_strings() {
+    let code = "let s = \"hello\"\nprint(s[1:3])\nprint(s[:2])\nprint(s[3:])\nlet t = s[1:]\nprint(t ++ \"!\")\n";
+
+    assert_eq!(run(code).unwrap(), vec!["el", "he", "lo", "ello!"]);
+}
+
+#[test]
+fn match_runs_the_first_equal_case_or_else() {
+    let code = "\
+fun name(n):
+    match n:
+        case 1: return \"one\"
+        case 2.5: return \"two and a half\"
+        else: return \"many\"
+print(name(1))
+print(name(2.5))
+print(name(7))
+match \"b\":
+    case \"a\": print(\"a\")
+    case \"b\":
+        print(\"b\")
+match true:
+    case false: print(\"no\")
+";
+
+    assert_eq!(run(code).unwrap(), vec!["one", "two and a half", "many", "b"]);
+}
+
+#[test]
+fn match_evaluates_its_value_once() {
+    let code = "\
+fun next():
+    print(\"called\")
+    return 3
+match next():
+    case 1: print(\"one\")
+    case 2: print(\"two\")
+    case 3: print(\"three\")
+";
+
+    assert_eq!(run(code).unwrap(), vec!["called", "three"]);
+}
+
+#[test]
+fn match_cases_are_checked_against_the_value() {
+    let (result, output) = reported(|| compile("let x = 1\nmatch x:\n    case \"a\": print(1)\n"));
+
+    assert!(result.is_err());
+    assert!(output.contains("can't match `Int` against a `Str` case"), "{}", output);
+
+    assert!(compile("let x = 1\nmatch x:\n    case 1.5: print(1)\n").is_ok());
+}
+
+#[test]
+fn tuples_are_indexed_like_lists() {
+    let code = "let t = (1, \"a\", 2.5)\nprint(t[0])\nprint(t[1])\nprint(t[2])\nprint(t)\nprint((7,)[0])\nprint((4) + 1)\n";
+
+    assert_eq!(run(code).unwrap(), vec!["1", "a", "2.5", "[1, \"a\", 2.5]", "7", "5"]);
+}
+
+#[test]
+fn break_in_a_match_case_leaves_the_loop_around_it() {
+    let code = "\
+for x in [1, 0, 2, 3, 4]:
+    match x:
+        case 0: print(\"zero\")
+        case 3: break
+        else: print(x)
+fun f():
+    let i = 0
+    while true:
+        i = i + 1
+        match i:
+            case 3: break
+            else: print(i)
+    return i
+print(f())
+";
+
+    assert_eq!(run(code).unwrap(), vec!["1", "zero", "2", "1", "2", "3"]);
+
+    // a `match` isn't something to break out of
+    let (result, output) = reported(|| compile("match 1:\n    case 1: break\n"));
+
+    assert!(result.is_err());
+    assert!(output.contains("you need a loop to break out of here"), "{}", output);
+}
+
+#[test]
+fn conditionals_evaluate_only_the_branch_they_pick() {
+    let code = "\
+fun loud(x):
+    print(\"evaluated\")
+    return x
+let c = true
+print(1 if c else loud(2))
+print(loud(1) if not c else 2)
+print({x: 1 if c else 2, y: \"no\" if c else \"yes\"})
+print([3 if c else 4, 1])
+";
+
+    assert_eq!(run(code).unwrap(), vec!["1", "2", "{x: 1, y: \"no\"}", "[3, 1]"]);
+}
+
+#[test]
+fn conditionals_are_typed_by_their_branches() {
+    let code = "let a = 1 if true else 2\nlet b = 1 if true else \"one\"\n";
+    let (visitor, _) = compile_with(code, |visitor, _| visitor.record_types()).unwrap();
+    let ast = parse(code).unwrap();
+
+    let types = ast.iter().map(|statement| match statement.node {
+        StatementNode::Declaration(_, _, Some(ref value)) => visitor.type_at(&value.pos).map(|t| t.node),
+        _ => None,
+    }).collect::<Vec<_>>();
+
+    assert_eq!(types, vec![Some(TypeNode::Int), Some(TypeNode::Any)]);
+
+    let (result, output) = reported(|| compile("let x = [1 if 2 else 3]\n"));
+
+    assert!(result.is_err());
+    assert!(output.contains("a conditional expression needs a `Bool` condition, but got `Int`"), "{}", output);
+}
+
+#[test]
+fn constant_conditionals_fold() {
+    assert_eq!(run("const N = 10 if 1 < 2 else 20\nprint(N)\n").unwrap(), vec!["10"]);
+}
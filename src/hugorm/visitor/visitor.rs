@@ -1,7 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{self, Display, Formatter, Write};
 use std::rc::Rc;
 
+use super::super::error::{self, Response};
 use super::super::error::Response::*;
 use std::cell::RefCell;
 
@@ -9,25 +10,50 @@ use super::*;
 
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::mem;
 
 use zub::ir::{ IrBuilder, ExprNode, Binding, IrFunctionBody, IrFunction, Expr, TypeInfo, BinaryOp, Literal };
+use zub::ir::Type as IrType;
+use zub::vm::{ VM, Heap, Object, Value };
 
 pub type VarPos = Binding;
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum TypeNode {
-    Int,
-    Float,
-    Bool,
-    Str,
-    Any,
-    Char,
-    Nil,
-    Func(usize),
+// zub keeps every value as an f64, whose 53 bit mantissa holds no bigger int exactly
+const MAX_EXACT_INT: i64 = 1 << 53;
+
+// how far a `const fun` may recurse and loop when it runs at compile time
+const MAX_CONST_DEPTH: usize = 256;
+const MAX_CONST_STEPS: usize = 1_000_000;
+
+// the most arguments the vm passes in one call
+const MAX_ARGS: usize = 8;
+
+const COMPARISONS: [Operator; 6] = [
+    Operator::Lt, Operator::Gt, Operator::LtEq, Operator::GtEq, Operator::Eq, Operator::NEq,
+];
+
+// string functions compiled into calls of the `$`-prefixed native of the same name,
+// with their arity and what they give back, a function since `split`'s list of strings can't be a constant
+fn string_intrinsics() -> [(&'static str, usize, TypeNode); 8] {
+    [
+        ("starts_with", 2, TypeNode::Bool),
+        ("ends_with", 2, TypeNode::Bool),
+        ("contains", 2, TypeNode::Bool),
+        ("split", 2, TypeNode::Array(Box::new(TypeNode::Str))),
+        ("upper", 1, TypeNode::Str),
+        ("lower", 1, TypeNode::Str),
+        ("trim", 1, TypeNode::Str),
+        ("replace", 3, TypeNode::Str),
+    ]
 }
 
+const NUMERIC_INTRINSICS: [(&str, usize); 3] = [
+    ("abs", 1),
+    ("min", 2),
+    ("max", 2),
+];
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TypeMode {
     Undeclared,
@@ -39,7 +65,12 @@ pub enum TypeMode {
 pub struct Type {
     pub node: TypeNode,
     pub mode: TypeMode,
-    pub meta: Option<VarPos>
+    pub meta: Option<VarPos>,
+    pub value: Option<ExpressionNode>, // the folded literal of a constant
+    pub overloads: Vec<(usize, VarPos)>, // arity and binding of every `fun` of this name, once there's more than one
+    pub params: Vec<String>, // what a `fun`'s params are called, for passing them by name
+    pub annotations: Vec<Option<TypeNode>>, // and what they're annotated as, for checking what a call passes
+    pub annotation: Option<TypeNode>, // what a `let` was annotated as, everything assigned to it has to fit
 }
 
 impl Type {
@@ -48,6 +79,11 @@ impl Type {
             node,
             mode,
             meta: None,
+            value: None,
+            overloads: Vec::new(),
+            params: Vec::new(),
+            annotations: Vec::new(),
+            annotation: None,
         }
     }
 
@@ -60,9 +96,35 @@ impl Type {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub message: String,
+    pub file: String,
+    pub pos: Pos,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ConstFlow {
+    Next,
+    Break,
+    Return(ExpressionNode),
+}
+
+// a labeled block being compiled, `looping` once the loops inside it have to check `done` after they end
+#[derive(Debug, Clone)]
+struct Label {
+    name: String,
+    result: String,
+    done: String,
+    broke: String,
+    rerouted: bool,
+    looping: bool,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Inside {
     Loop,
+    Block,
     Function,
     Nothing,
 }
@@ -75,6 +137,21 @@ pub struct Visitor<'a> {
     pub symtab: SymTab,
     pub builder: IrBuilder,
     pub repl: bool,
+    pub flags: HashSet<String>,
+    warnings: Vec<Warning>,
+    types: Option<HashMap<Pos, Type>>,
+    hoisted: HashMap<(String, usize), Binding>, // slots of loop and branch locals bound before their block, by name and function depth
+    const_functions: HashMap<String, Rc<(Vec<String>, Vec<Statement>)>>,
+    labels: Vec<Label>,
+    methods: HashMap<String, Vec<String>>, // declared by interfaces, called as `x.name()`, with the fields they read off `self`
+    strict: bool,
+    release: bool,
+    inline: bool,
+    recovery: bool,
+    unused_results: bool,
+    pub errors: Vec<Response<String>>, // what each top level statement the last visit skipped reported
+    operators: Vec<(String, u8)>, // registered by the host, with their precedence
+    embedded: RefCell<HashMap<PathBuf, String>>, // files read by `embed`, which is typed, folded and compiled
 }
 
 impl<'a> Visitor<'a> {
@@ -87,6 +164,21 @@ impl<'a> Visitor<'a> {
             function_depth: 0,
             builder: IrBuilder::new(),
             repl: false,
+            flags: HashSet::new(),
+            warnings: Vec::new(),
+            types: None,
+            hoisted: HashMap::new(),
+            const_functions: HashMap::new(),
+            labels: Vec::new(),
+            methods: HashMap::new(),
+            strict: false,
+            release: false,
+            inline: false,
+            recovery: false,
+            unused_results: false,
+            errors: Vec::new(),
+            operators: Vec::new(),
+            embedded: RefCell::new(HashMap::new()),
         }
     }
 
@@ -98,7 +190,22 @@ impl<'a> Visitor<'a> {
             depth: 0,
             function_depth: 0,
             builder: IrBuilder::new(),
-            repl: false
+            repl: false,
+            flags: HashSet::new(),
+            warnings: Vec::new(),
+            types: None,
+            hoisted: HashMap::new(),
+            const_functions: HashMap::new(),
+            labels: Vec::new(),
+            methods: HashMap::new(),
+            strict: false,
+            release: false,
+            inline: false,
+            recovery: false,
+            unused_results: false,
+            errors: Vec::new(),
+            operators: Vec::new(),
+            embedded: RefCell::new(HashMap::new()),
         }
     }
 
@@ -106,22 +213,261 @@ impl<'a> Visitor<'a> {
         self.assign(name.to_string(), Type::from(t))
     }
 
+    // back to how `new` left it, to compile another file with the same visitor after pointing `source` at it,
+    // settings from the `set_*` methods and `define` stay, globals and operators only if `keep_globals`
+    pub fn reset(&mut self, keep_globals: bool) {
+        let globals = if keep_globals {
+            self.symtab.stack[0].table.borrow().clone()
+        } else {
+            self.operators.clear();
+
+            HashMap::new()
+        };
+
+        self.symtab = SymTab::from(globals);
+        self.builder = IrBuilder::new();
+        self.inside.clear();
+        self.depth = 0;
+        self.function_depth = 0;
+        self.warnings.clear();
+        self.hoisted.clear();
+        self.const_functions.clear();
+        self.labels.clear();
+        self.methods.clear();
+        self.embedded.borrow_mut().clear();
+        self.errors.clear();
+
+        if let Some(ref mut types) = self.types {
+            types.clear()
+        }
+    }
+
+    pub fn define(&mut self, flag: &str) {
+        self.flags.insert(flag.to_string());
+    }
+
+    pub fn is_defined(&self, flag: &str) -> bool {
+        self.flags.contains(flag)
+    }
+
     pub fn visit(&mut self, ast: &Vec<Statement>) -> Result<(), ()> {
+        let inlined;
+
+        let ast = if self.inline {
+            inlined = inline_single_use(ast);
+            &inlined
+        } else {
+            ast
+        };
+
         self.symtab.push();
 
+        self.errors.clear();
+
         for statement in ast.iter() {
-            self.visit_statement(&statement)?
+            if self.recovery {
+                self.visit_recovering(statement)
+            } else {
+                self.visit_statement(&statement)?
+            }
         }
 
         self.symtab.pop();
 
-        Ok(())
+        if self.errors.is_empty() { Ok(()) } else { Err(()) }
+    }
+
+    // a statement can fail halfway into a function or loop, whatever it left behind is thrown out with it,
+    // what it declared in the frame it started in included
+    fn visit_recovering(&mut self, statement: &Statement) {
+        let builder = self.builder.clone();
+        let frames = self.symtab.stack.len();
+        let frame = self.symtab.stack.last().cloned();
+        let hoisted = self.hoisted.clone();
+        let const_functions = self.const_functions.clone();
+        let methods = self.methods.clone();
+        let warnings = self.warnings.len();
+        let (depth, function_depth) = (self.depth, self.function_depth);
+
+        let (result, report) = error::capture(|| self.visit_statement(statement));
+
+        error::write_response(format_args!("{}", report));
+
+        if result.is_err() {
+            self.errors.push(Wrong(report));
+
+            self.builder = builder;
+            self.symtab.stack.truncate(frames);
+
+            if let Some(frame) = frame {
+                *self.symtab.stack.last_mut().unwrap() = frame
+            }
+
+            self.hoisted = hoisted;
+            self.const_functions = const_functions;
+            self.methods = methods;
+            self.warnings.truncate(warnings);
+            self.depth = depth;
+            self.function_depth = function_depth;
+            self.inside.clear();
+            self.labels.clear();
+        }
+    }
+
+    // compiles another file into the same program, its top level becomes visible to what's visited after
+    // and its diagnostics are reported against that file rather than this visitor's own
+    pub fn visit_source(&mut self, source: &'a Source, ast: &Vec<Statement>) -> Result<(), ()> {
+        let own = mem::replace(&mut self.source, source);
+
+        let mut result = Ok(());
+
+        for statement in ast.iter() {
+            result = self.visit_statement(statement);
+
+            if result.is_err() {
+                break
+            }
+        }
+
+        self.source = own;
+
+        result
     }
 
     pub fn build(&self) -> Vec<ExprNode> {
         self.builder.build()
     }
 
+    // how many IR nodes the program compiled to so far, function bodies included
+    pub fn ir_size(&self) -> usize {
+        self.builder.build().iter().map(count_nodes).sum()
+    }
+
+    // packages a visited program for hosts that call into it, its top level functions are the exports
+    pub fn into_module(self) -> Module {
+        let ir = self.build();
+
+        let mut exports = HashMap::new();
+
+        for (name, t) in self.symtab.last.table.borrow().iter() {
+            if let TypeNode::Func(_) = t.node {
+                if !name.starts_with('$') {
+                    exports.insert(name.clone(), t.node.clone());
+                }
+            }
+        }
+
+        Module::new(ir, exports)
+    }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    pub fn emit_warning<T: Display>(&mut self, message: T, pos: &Pos) {
+        let message = message.to_string();
+
+        response!(
+            Weird(&message),
+            self.source.file,
+            pos
+        );
+
+        let file = self.source.file.0.clone();
+
+        self.warnings.push(Warning { message, file, pos: pos.clone() })
+    }
+
+    // refuses arithmetic, comparisons and calls on values typed `Any` until they're narrowed with `is`
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict
+    }
+
+    // a binary operator the language doesn't have, `a <> b` calls `native(a, b)`,
+    // only what `lexer` and `parser` make can read it
+    pub fn register_operator(&mut self, vm: &mut VM, symbol: &str, prec: u8, native: fn(&mut Heap<Object>, &[Value]) -> Value) {
+        let name = Self::operator_native(symbol);
+
+        vm.add_native(&name, native, 2);
+        self.set_global(&name, TypeNode::Func(2));
+
+        self.operators.retain(|(registered, _)| registered != symbol);
+        self.operators.push((symbol.to_string(), prec))
+    }
+
+    // lexes with the registered operators, so `<>` is one token rather than `<` and `>`
+    pub fn lexer<'s>(&self, code: &str, source: &'s Source) -> Lexer<'s> {
+        Lexer::default(code.chars().collect(), source).with_operators(&self.operators)
+    }
+
+    // parses with the registered operators at their precedence
+    pub fn parser<'s>(&self, tokens: Vec<Token>, source: &'s Source) -> Parser<'s> {
+        let mut parser = Parser::new(tokens, source);
+        parser.set_operators(&self.operators);
+
+        parser
+    }
+
+    fn operator_native(symbol: &str) -> String {
+        format!("$operator{}", symbol)
+    }
+
+    // warns about expression statements that make a value nothing uses, like `1 + 1` on its own line,
+    // calls are made for what they do so they never count
+    pub fn set_unused_results(&mut self, enabled: bool) {
+        self.unused_results = enabled
+    }
+
+    // keeps visiting the top level after a statement fails so every error gets reported, the visit still fails
+    pub fn set_recovery(&mut self, enabled: bool) {
+        self.recovery = enabled
+    }
+
+    // leaves out every `check`, their conditions are still type checked
+    pub fn set_release(&mut self, release: bool) {
+        self.release = release
+    }
+
+    // splices functions called just once into their call, when all they do is return an expression of their
+    // params, the function itself is gone afterwards so this is only for programs the host doesn't call into
+    pub fn set_inline(&mut self, inline: bool) {
+        self.inline = inline
+    }
+
+    // remembers the inferred type of every expression typed from here on, for tooling
+    pub fn record_types(&mut self) {
+        if self.types.is_none() {
+            self.types = Some(HashMap::new())
+        }
+    }
+
+    pub fn type_at(&self, pos: &Pos) -> Option<Type> {
+        self.types.as_ref().and_then(|types| types.get(pos).cloned())
+    }
+
+    // a ", did you mean `x`?" hint for the closest name in scope, if any is close enough
+    fn suggest(&self, name: &str) -> String {
+        let mut best: Option<(usize, String)> = None;
+
+        for candidate in self.symtab.names() {
+            // mangled slots aren't something the user can write
+            if candidate.contains('$') || candidate == name {
+                continue
+            }
+
+            let distance = edit_distance(name, &candidate);
+
+            if distance <= ((name.len() + 1) / 3).max(1) && best.as_ref().map_or(true, |(d, _)| distance < *d) {
+                best = Some((distance, candidate))
+            }
+        }
+
+        match best {
+            Some((_, candidate)) => format!(", did you mean `{}`?", candidate),
+            None => String::new(),
+        }
+    }
+
     pub fn visit_statement(&mut self, statement: &Statement) -> Result<(), ()> {
         use self::StatementNode::*;
 
@@ -131,6 +477,14 @@ impl<'a> Visitor<'a> {
             Expression(ref expr) => {
                 self.visit_expression(expr)?;
 
+                let unused = self.unused_results
+                    && !Self::is_call(expr)
+                    && ![TypeNode::Nil, TypeNode::Any].contains(&self.type_expression(expr)?.node);
+
+                if unused {
+                    self.emit_warning("result of expression is unused", &expr.pos)
+                }
+
                 let ir = self.compile_expression(expr)?;
                 self.builder.emit(ir);
 
@@ -141,11 +495,35 @@ impl<'a> Visitor<'a> {
             Declaration(..) => self.visit_variable(&statement.node, &statement.pos),
             Assignment(..) => self.visit_ass(&statement.node, &statement.pos),
 
+            // what a block declares is gone after it, in slots of its own at this depth, like an if's
             Block(ref body) => {
+                let hoisted = self.hoist_locals(&[body], &position);
+
+                self.push_scope();
+                self.depth -= 1;
+
                 for element in body.iter() {
                     self.visit_statement(element)?
                 }
 
+                self.depth += 1;
+                self.pop_scope();
+
+                for local in hoisted {
+                    self.hoisted.remove(&local);
+                }
+
+                Ok(())
+            }
+
+            When(ref flag, ref body) => {
+                // undefined flags skip the body entirely, so it may reference host symbols that don't exist
+                if self.is_defined(flag) {
+                    for element in body.iter() {
+                        self.visit_statement(element)?
+                    }
+                }
+
                 Ok(())
             }
 
@@ -163,20 +541,45 @@ impl<'a> Visitor<'a> {
 
                     Ok(())
                 } else {
+                    let Pos(ref line, (start, _)) = statement.pos;
+                    let keyword = Pos(line.clone(), (start, start + "return".len() - 1));
+
+                    let message = if self.inside.is_empty() {
+                        "can't return from the top level of a file"
+                    } else {
+                        "can't return from a block outside of a function"
+                    };
+
                     return Err(response!(
-                        Wrong("can't return outside of function"),
+                        Wrong(message),
                         self.source.file,
-                        statement.pos
+                        keyword
                     ));
                 }
             },
 
             Function(ref name, ref params, ref body) => {
-                let mut t = Type::from(TypeNode::Func(params.len()));
+                self.check_name(name, &position)?;
 
-                let mut binding = Binding::local(name, self.depth, self.function_depth);
+                for (param, ..) in params.iter() {
+                    self.check_name(param, &position)?
+                }
 
-                t.set_offset(binding.clone());
+                let (mut t, mut binding) = self.overload(name, params.len());
+
+                // declared in a loop or branch, the slot is already there to put the function in
+                let slot = self.hoisted.get(&(binding.name().to_string(), self.function_depth)).cloned();
+
+                if let Some(ref slot) = slot {
+                    binding = slot.clone();
+                    t.set_offset(slot.clone())
+                }
+
+                // an overloaded name doesn't say which params a keyword would go to
+                if t.overloads.is_empty() {
+                    t.params = params.iter().map(|(param, ..)| param.clone()).collect();
+                    t.annotations = params.iter().map(|(_, annotation, _)| annotation.clone()).collect()
+                }
 
                 self.assign(name.to_owned(), t);
 
@@ -187,18 +590,27 @@ impl<'a> Visitor<'a> {
                 self.push_scope();
                 self.inside.push(Inside::Function);
 
-                for param in params.iter() {
-                    let mut t = Type::from(TypeNode::Any);
+                let labels = mem::replace(&mut self.labels, Vec::new());
+
+                for (param, annotation, constant) in params.iter() {
+                    let mut t = Type::from(annotation.clone().unwrap_or(TypeNode::Any));
+
+                    if *constant {
+                        t.mode = TypeMode::Immutable
+                    }
+
                     t.set_offset(Binding::local(param.as_str(), self.depth, self.function_depth));
 
                     self.assign(param.clone(), t)
                 }
 
-                for statement in body.iter() {
+                for statement in self.lower_generator(body)?.iter() {
                     self.visit_statement(statement)?;
                 }
 
 
+                self.labels = labels;
+
                 self.inside.pop();
                 self.pop_scope();
                 self.function_depth -= 1;
@@ -210,61 +622,88 @@ impl<'a> Visitor<'a> {
                 self.builder = old_current;
 
                 let func_body = IrFunctionBody {
-                    params: params.iter().cloned().map(|x|
+                    params: params.iter().map(|(x, ..)|
                         Binding::local(x.as_str(), binding.depth.unwrap_or(0) + 1, binding.function_depth + 1)).collect::<Vec<Binding>>(),
-                    method: false,
+                    method: self.methods.contains_key(name) && params.first().map_or(false, |(param, ..)| param == "self"),
                     inner: body
                 };
 
                 let ir_func = IrFunction {
-                    var: binding,
+                    var: binding.clone(),
                     body: Rc::new(RefCell::new(func_body))
                 };
 
-                self.builder.emit(Expr::Function(ir_func).node(TypeInfo::nil()));
-                
+                if slot.is_some() {
+                    let function = Expr::AnonFunction(ir_func).node(TypeInfo::nil());
+
+                    self.bind_local(binding, function, true)
+                } else {
+                    self.builder.emit(Expr::Function(ir_func).node(TypeInfo::nil()));
+                }
+
                 Ok(())
             },
 
             Interface(_, ref content) => {
+                // what a receiver needs for the interface's functions to work on it, its functions come with the interface
+                let mut fields = Vec::new();
+
                 for fun in content.iter() {
-                    self.visit_statement(fun)?
+                    if let Function(_, _, ref body) = fun.node {
+                        Self::self_fields(body, &mut fields)
+                    }
                 }
 
-                Ok(())
-            }
-
-            While(ref cond, ref body) => {
-                self.visit_expression(cond)?;
+                for fun in content.iter() {
+                    if let Function(ref name, ..) = fun.node {
+                        fields.retain(|field| field != name)
+                    }
+                }
 
-                if [TypeNode::Bool, TypeNode::Any].contains(&self.type_expression(cond)?.node) {
-                    let cond = self.compile_expression(cond)?;
+                // every interface function takes its receiver as an implicit first `self`
+                for fun in content.iter() {
+                    if let Function(ref name, ref params, ref body) = fun.node {
+                        if params.iter().any(|(param, ..)| param == "self") {
+                            return Err(response!(
+                                Wrong(format!("`{}` already gets `self` from its interface", name)),
+                                self.source.file,
+                                fun.pos
+                            ))
+                        }
 
-                    let old_current = self.builder.clone();
-                    self.builder = IrBuilder::new();
+                        // the vm hands a method called `init` back its receiver instead of what it returns
+                        if name == "init" {
+                            return Err(response!(
+                                Wrong("an interface function can't be called `init`"),
+                                self.source.file,
+                                fun.pos
+                            ))
+                        }
 
-                    self.push_scope();
-                    self.depth -= 1; // brother bruh
+                        let mut params = params.clone();
+                        params.insert(0, ("self".to_string(), None, false));
 
-                    self.inside.push(Inside::Loop);
+                        self.methods.insert(name.clone(), fields.clone());
 
-                    for statement in body.iter() {
-                        self.visit_statement(statement)?;
+                        self.visit_statement(&Statement::new(Function(name.clone(), params, body.clone()), fun.pos.clone()))?
                     }
+                }
 
-                    self.inside.pop();
-
-                    self.depth += 1; // hehe
-                    self.pop_scope();
+                Ok(())
+            }
 
+            While(ref cond, ref body) => {
+                self.visit_expression(cond)?;
 
-                    let body = Expr::Block(self.builder.build()).node(TypeInfo::nil());
+                if [TypeNode::Bool, TypeNode::Any].contains(&self.type_expression(cond)?.node) {
+                    let endless = cond.node == ExpressionNode::Bool(true) && !Self::exits(body);
+                    let cond = self.compile_expression(cond)?;
 
-                    self.builder = old_current;
+                    if endless {
+                        self.emit_warning("this loop never ends, nothing in it breaks or returns", &position)
+                    }
 
-                    self.builder.emit(
-                        Expr::While(cond, body).node(TypeInfo::nil())
-                    );
+                    self.visit_loop(cond, body, Inside::Loop, &position)?;
 
                     Ok(())
                 } else {
@@ -276,18 +715,42 @@ impl<'a> Visitor<'a> {
                 }
             }
 
+            Yield(_) => Err(response!(
+                Wrong("can't yield outside of a function"),
+                self.source.file,
+                position
+            )),
+
+            For(ref name, ref value, ref iterable, ref body) => self.visit_for(name, value, iterable, body, &position),
+            Match(ref scrutinee, ref arms, _) => self.visit_match(statement, scrutinee, arms),
+            Check(ref cond) => self.visit_check(cond, &position),
+
+            WhileLet(ref name, ref right, ref body, ref else_) => self.visit_while_let(name, right, body, else_, &position),
+
             If(ref cond, ref body, ref else_) => {
                 self.visit_expression(cond)?;
 
                 if [TypeNode::Bool, TypeNode::Any].contains(&self.type_expression(cond)?.node) {
+                    let if_cond = cond;
                     let cond = self.compile_expression(cond)?;
 
+                    // a branch that doesn't run never pushes the slots of its locals, so every branch gets them up front
+                    let mut bodies = vec!(body);
+                    bodies.extend(else_.iter().map(|(_, body)| body));
+
+                    let hoisted = self.hoist_locals(&bodies, &position);
+
                     let old_current = self.builder.clone();
                     self.builder = IrBuilder::new();
 
                     self.push_scope();
                     self.depth -= 1; // brother bruh
 
+                    // `if x is Int:` lets the body treat `x` as an int, the scope takes it back afterwards
+                    if let Some((name, t)) = self.narrowing(if_cond, true) {
+                        self.assign(name, t)
+                    }
+
                     for statement in body.iter() {
                         self.visit_statement(statement)?;
                     }
@@ -299,15 +762,21 @@ impl<'a> Visitor<'a> {
 
                     self.builder = old_current;
 
-                    let mut else_blocks = Expr::Literal(Literal::Nil);
+                    // a nil literal as the else would stay on the stack every time the condition fails
+                    let mut else_blocks = None;
 
                     for (i, els) in else_.iter().enumerate() {
                         let old_current = self.builder.clone();
                         self.builder = IrBuilder::new();
 
                         self.push_scope();
+                        self.depth -= 1; // same deal as the body above
+
+                        if let Some((name, t)) = self.narrowing(if_cond, false) {
+                            self.assign(name, t)
+                        }
 
-                        if let Some(ref cond) = els.0 {
+                        let is_elif = if let Some(ref cond) = els.0 {
                             let pos = cond.pos.clone();
 
                             let elif = Statement::new(
@@ -317,26 +786,34 @@ impl<'a> Visitor<'a> {
 
                             self.visit_statement(&elif)?;
 
-                            self.pop_scope();
-
-                            break // 9000 IQ
-
+                            true
                         } else {
                             for statement in els.1.iter() {
                                 self.visit_statement(statement)?;
                             }
-                        }
 
+                            false
+                        };
+
+                        self.depth += 1;
                         self.pop_scope();
 
                         let body = self.builder.build();
 
                         self.builder = old_current;
 
-                        else_blocks = Expr::Block(body);
+                        else_blocks = Some(Expr::Block(body).node(TypeInfo::nil()));
+
+                        if is_elif {
+                            break // 9000 IQ, the nested if handles the rest of the chain
+                        }
                     }
 
-                    self.builder.emit(Expr::If(cond, body, Some(else_blocks.node(TypeInfo::nil()))).node(TypeInfo::nil() ));
+                    self.builder.emit(Expr::If(cond, body, else_blocks).node(TypeInfo::nil() ));
+
+                    for local in hoisted {
+                        self.hoisted.remove(&local);
+                    }
 
                     Ok(())
 
@@ -350,7 +827,13 @@ impl<'a> Visitor<'a> {
             }
 
             Break => {
-                if self.inside.contains(&Inside::Loop) {
+                let innermost = self.inside.iter().rev().position(|inside| *inside != Inside::Block);
+
+                if innermost.map(|i| self.inside[self.inside.len() - 1 - i] == Inside::Loop).unwrap_or(false) {
+                    if self.inside.last() == Some(&Inside::Block) {
+                        self.reroute_break(&position)?
+                    }
+
                     self.builder.break_();
 
                     Ok(())
@@ -363,17 +846,43 @@ impl<'a> Visitor<'a> {
                 }
             }
 
-            Const(..) => return Err(response!(
-                Wrong("constants are not implemented yet"),
-                self.source.file,
-                position
-            )),
+            BreakLabel(ref name, ref value) => self.visit_break_label(name, value, &position),
 
-            ConstFunction(ref fun) => return Err(response!(
-                Wrong("constants are not implemented yet"),
-                self.source.file,
-                position
-            )),
+            Const(ref name, ref right) => self.visit_const(name, right, &statement.pos),
+
+            // already reported by the parser
+            Error => Ok(()),
+
+            // calls with constant arguments get evaluated right here and replaced by their result
+            ConstFunction(ref fun) => {
+                if let Function(ref name, ref params, ref body) = fun.node {
+                    let params = params.iter().map(|(param, ..)| param.clone()).collect::<Vec<String>>();
+
+                    self.const_functions.insert(name.clone(), Rc::new((params.clone(), body.clone())));
+
+                    if let Err((message, pos)) = self.check_const_body(&params, body) {
+                        self.const_functions.remove(name);
+
+                        return Err(response!(
+                            Wrong(message),
+                            self.source.file,
+                            pos
+                        ))
+                    }
+                }
+
+                self.visit_statement(fun)?;
+
+                // same ir as any function, but the name can't be rebound
+                if let Function(ref name, ..) = fun.node {
+                    if let Some(mut t) = self.symtab.fetch(name) {
+                        t.mode = TypeMode::Immutable;
+                        self.assign(name.to_owned(), t)
+                    }
+                }
+
+                Ok(())
+            },
 
             _ => {
                 return Err(response!(
@@ -390,14 +899,44 @@ impl<'a> Visitor<'a> {
 
         let result = match expression.node {
             Float(ref n) => self.builder.number(*n),
-            Int(ref n) => self.builder.number(*n as f64),
+            Int(ref n) => {
+                // ints aren't exact at runtime, they're f64 like every other number, so a literal past 2^53 is
+                // rejected rather than silently rounded
+                if n.unsigned_abs() > MAX_EXACT_INT as u64 {
+                    return Err(response!(
+                        Wrong(format!("integer `{}` is too big to be represented exactly, ints go up to 2^53", n)),
+                        self.source.file,
+                        expression.pos
+                    ))
+                }
+
+                Expr::Literal(Literal::Number(*n as f64)).node(TypeInfo::new(IrType::Int))
+            },
             Str(ref s) => self.builder.string(s),
+            Char(ref c) => self.builder.string(&c.to_string()), // the vm has no chars, only strings of one
             Bool(ref b) => self.builder.bool(*b),
+            Nil | Error => Expr::Literal(Literal::Nil).node(TypeInfo::nil()),
 
             Identifier(ref n) =>  {
+                self.symtab.mark_read(n, true);
+
                 if let Some(binding) = self.symtab.fetch(n) {
+                    if let Some(ref value) = binding.value {
+                        return self.compile_expression(&Expression::new(value.clone(), expression.pos.clone()))
+                    }
+
+                    if !binding.overloads.is_empty() {
+                        let arities = binding.overloads.iter().map(|(arity, _)| arity.to_string()).collect::<Vec<String>>();
+
+                        return Err(response!(
+                            Wrong(format!("`{}` is overloaded, call it with {} arguments to pick one", n, arities.join(" or "))),
+                            self.source.file,
+                            expression.pos
+                        ))
+                    }
+
                     if let Some(mut binding) = binding.meta {
-                        binding = Binding::local(n, self.depth, binding.function_depth);
+                        binding = Binding::local(binding.name(), self.depth, binding.function_depth);
 
                         self.builder.var(binding)
                     } else {
@@ -408,24 +947,126 @@ impl<'a> Visitor<'a> {
 
                 } else {
                     return Err(response!(
-                        Wrong(format!("no such variable `{}`", n)),
+                        Wrong(format!("no such variable `{}`{}", n, self.suggest(n))),
                         self.source.file,
                         expression.pos
                     ));
                 }
             }
 
-            Call(ref callee, ref args) => {
-                let mut args_ir = Vec::new();
+            Call(ref callee, ref args, _) => {
+                if let Some(call) = self.method_call(expression)? {
+                    return self.compile_expression(&call)
+                }
 
-                for arg in args.iter() {
-                    args_ir.push(self.compile_expression(arg)?)
+                if let Some(call) = self.spread_call(expression)? {
+                    return self.compile_expression(&call)
                 }
 
-                let callee_ir = self.compile_expression(callee)?;
+                match self.keyword_call_in_order(expression) {
+                    Ok(Some(call)) => return self.compile_expression(&call),
+                    Err((message, pos)) => return Err(response!(Wrong(message), self.source.file, pos)),
+                    Ok(None) => (),
+                }
 
-                self.builder.call(callee_ir, args_ir, None)
-            }
+                if let Some(name) = self.intrinsic(callee) {
+                    return self.compile_expression(&Self::lower_intrinsic(name, args, &expression.pos))
+                }
+
+                if self.is_range(callee) {
+                    return self.compile_expression(&Self::lower_range(args, &expression.pos))
+                }
+
+                if self.is_embed(callee) {
+                    return match self.embed(args, &callee.pos) {
+                        Ok(content) => Ok(self.builder.string(&content)),
+                        Err((message, pos)) => Err(response!(Wrong(message), self.source.file, pos)),
+                    }
+                }
+
+                let native = self.string_intrinsic(callee).map(|(name, ..)| name)
+                    .or_else(|| self.numeric_intrinsic(callee).map(|(name, _)| name));
+
+                if let Some(name) = native {
+                    let mut args_ir = Vec::new();
+
+                    for arg in args.iter() {
+                        args_ir.push(self.compile_expression(arg)?)
+                    }
+
+                    let native = self.builder.var(Binding::global(&format!("${}", name)));
+
+                    return Ok(self.builder.call(native, args_ir, None))
+                }
+
+                if let Identifier(ref name) = callee.node {
+                    let foldable = self.const_functions.contains_key(name)
+                        && args.iter().all(|arg| self.fold(arg, &HashMap::new(), 0).is_ok());
+
+                    if foldable {
+                        let value = self.fold_constant(expression)?;
+
+                        return self.compile_expression(&Expression::new(value, expression.pos.clone()))
+                    }
+                }
+
+                let mut args_ir = Vec::new();
+
+                for arg in args.iter() {
+                    args_ir.push(self.compile_expression(arg)?)
+                }
+
+                let callee_ir = match self.overloads(callee) {
+                    Some((name, overloads)) => {
+                        let binding = self.resolve_overload(&name, &overloads, args.len(), &callee.pos)?;
+
+                        self.builder.var(Binding::local(binding.name(), self.depth, binding.function_depth))
+                    },
+
+                    None => self.compile_expression(callee)?,
+                };
+
+                self.builder.call(callee_ir, args_ir, None)
+            }
+
+            Binary(ref left, Operator::Custom(ref symbol), ref right) => {
+                let args = vec!(self.compile_expression(left)?, self.compile_expression(right)?);
+                let native = self.builder.var(Binding::global(&Self::operator_native(symbol)));
+
+                self.builder.call(native, args, None)
+            }
+
+            // the vm's `==` only compares numbers
+            Binary(ref left, ref op, ref right) if Self::nil_compared(left, op, right).is_some() => {
+                let args = vec!(self.compile_expression(left)?, self.compile_expression(right)?);
+                let native = self.builder.var(Binding::global("$equals"));
+
+                let equal = self.builder.call(native, args, None);
+
+                if op == &Operator::NEq {
+                    Expr::Not(equal).node(TypeInfo::nil())
+                } else {
+                    equal
+                }
+            }
+
+            // the vm only adds strings to strings, the rest is shown as `print` would first
+            Binary(ref left, ref op, ref right) if op == &Operator::Concat => {
+                let left_ir = self.compile_textual(left)?;
+                let right_ir = self.compile_textual(right)?;
+
+                self.builder.binary(left_ir, BinaryOp::Add, right_ir)
+            }
+
+            Binary(ref left, ref op, ref right) if op == &Operator::Is => {
+                let value = self.compile_expression(left)?;
+                let name = self.type_test(right)?.0;
+
+                let native = self.builder.var(Binding::global("$is"));
+                let name = Expr::Literal(Literal::String(name)).node(TypeInfo::nil());
+
+                self.builder.call(native, vec!(value, name), None)
+            }
 
             Binary(ref left, ref op, ref right) => {
                 let left_ir = self.compile_expression(left)?;
@@ -462,13 +1103,75 @@ impl<'a> Visitor<'a> {
                     GtEq  => BinaryOp::GtEqual,
                     Index => BinaryOp::Index,
                     Pow   => BinaryOp::Pow, 
-                    Concat => BinaryOp::Add, // :)
+                    Concat | Is | Custom(_) => unreachable!(),
+
+                    // the vm only does float arithmetic, so the wrapping happens natively on i64
+                    WrapAdd | WrapSub | WrapMul => {
+                        let name = match op {
+                            WrapAdd => "$wrapping_add",
+                            WrapSub => "$wrapping_sub",
+                            _       => "$wrapping_mul",
+                        };
+
+                        let native = self.builder.var(Binding::global(name));
+
+                        return Ok(self.builder.call(native, vec!(left_ir, right_ir), None))
+                    },
                 };
 
                 self.builder.binary(left_ir, op_ir, right_ir)
             }
 
-            Array(ref content) => {
+            Array(ref content) if content.iter().any(Self::is_spread) => {
+                for element in content.iter() {
+                    if let Spread(ref source) = element.node {
+                        self.check_spread(source, false)?
+                    }
+                }
+
+                return self.compile_expression(&Self::lower_spread(content.clone(), "$concat", Array, &expression.pos))
+            }
+
+            Dict(ref content) if content.iter().any(|entry| entry.spread().is_some()) => {
+                for entry in content.iter() {
+                    if let DictEntry::Spread(ref source) = *entry {
+                        self.check_spread(source, true)?
+                    }
+                }
+
+                return self.compile_expression(&Self::lower_spread(content.clone(), "$merge", Dict, &expression.pos))
+            }
+
+            // missing bounds go in as nil, the native fills them in
+            Slice(ref target, ref start, ref end) => {
+                let bound = |bound: &Option<Rc<Expression>>| match *bound {
+                    Some(ref bound) => (**bound).clone(),
+                    None => Expression::new(Nil, expression.pos.clone()),
+                };
+
+                let native = Expression::new(Identifier("$slice".to_string()), expression.pos.clone());
+                let call = Call(Rc::new(native), vec!((**target).clone(), bound(start), bound(end)), Vec::new());
+
+                return self.compile_expression(&Expression::new(call, expression.pos.clone()))
+            },
+
+            Range(ref start, ref end) => {
+                let args = vec!((**start).clone(), (**end).clone());
+
+                return self.compile_expression(&Self::lower_range(&args, &expression.pos))
+            },
+
+            // `start..=end` is `start..end + 1`
+            RangeInclusive(ref start, ref end) => {
+                let one = Expression::new(Int(1), end.pos.clone());
+                let end = Expression::new(Binary(end.clone(), Operator::Add, Rc::new(one)), end.pos.clone());
+
+                let args = vec!((**start).clone(), end);
+
+                return self.compile_expression(&Self::lower_range(&args, &expression.pos))
+            },
+
+            Array(ref content) | Tuple(ref content) => {
                 let mut cont_ir = Vec::new();
 
                 for element in content.iter() {
@@ -482,7 +1185,12 @@ impl<'a> Visitor<'a> {
                 let mut keys = Vec::new();
                 let mut vals = Vec::new();
 
-                for (key, val) in content.iter() {
+                for entry in content.iter() {
+                    let (key, val) = match *entry {
+                        DictEntry::Field(ref key, ref val) => (key, val),
+                        DictEntry::Spread(_) => unreachable!(), // lowered to `$merge` above
+                    };
+
                     keys.push(
                         Expr::Literal(
                             Literal::String(key.clone())
@@ -495,9 +1203,11 @@ impl<'a> Visitor<'a> {
             }
 
             AnonFunction(ref name, ref params, ref body) => {
-                let mut t = Type::from(TypeNode::Func(params.len()));
+                for param in params.iter() {
+                    self.check_name(param, &expression.pos)?
+                }
 
-                println!("{}", params.len());
+                let mut t = Type::from(TypeNode::Func(params.len()));
 
                 let binding = Binding::local(name, self.depth, self.function_depth);
                 t.set_offset(binding.clone());
@@ -511,6 +1221,8 @@ impl<'a> Visitor<'a> {
                 self.push_scope();
                 self.inside.push(Inside::Function);
 
+                let labels = mem::replace(&mut self.labels, Vec::new());
+
                 for param in params.iter() {
                     let mut t = Type::from(TypeNode::Any);
                     t.set_offset(Binding::local(param.as_str(), self.depth, self.function_depth));
@@ -518,11 +1230,13 @@ impl<'a> Visitor<'a> {
                     self.assign(param.clone(), t)
                 }
 
-                for statement in body.iter() {
+                for statement in self.lower_generator(body)?.iter() {
                     self.visit_statement(statement)?;
                 }
 
 
+                self.labels = labels;
+
                 self.inside.pop();
                 self.pop_scope();
                 self.function_depth -= 1;
@@ -548,8 +1262,29 @@ impl<'a> Visitor<'a> {
                 Expr::AnonFunction(ir_func).node(TypeInfo::nil())
             },
 
+            // the receiver is evaluated once, as the native's argument, and a nil one or a missing key gives nil
+            OptionalIndex(ref left, ref key) => {
+                let receiver = self.compile_expression(left)?;
+                let key = self.compile_expression(key)?;
+
+                let native = self.builder.var(Binding::global("$index_or_nil"));
+
+                self.builder.call(native, vec!(receiver, key), None)
+            },
+
+            // only the branch the condition picks gets evaluated
+            Conditional(ref cond, ref then, ref otherwise) => {
+                let cond = self.compile_expression(cond)?;
+                let then = self.compile_expression(then)?;
+                let otherwise = self.compile_expression(otherwise)?;
+
+                self.builder.ternary(cond, then, Some(otherwise))
+            },
+
             EOF => { Expr::Return(None).node(TypeInfo::nil()) },
 
+            Labeled(ref name, ref body) => self.visit_labeled(name, body, &expression.pos)?,
+
             Not(ref expr) => {
                 let ir = self.compile_expression(expr)?;
                 Expr::Not(ir).node(TypeInfo::nil())
@@ -570,7 +1305,62 @@ impl<'a> Visitor<'a> {
         use self::ExpressionNode::*;
 
         match expression.node {
-            Call(ref caller, ref args) => {
+            Call(ref caller, ref args, _) => {
+                if let Some(call) = self.method_call(expression)? {
+                    return self.visit_expression(&call)
+                }
+
+                if let Some(call) = self.spread_call(expression)? {
+                    return self.visit_expression(&call)
+                }
+
+                match self.keyword_call(expression) {
+                    Ok(Some(call)) => return self.visit_expression(&call),
+                    Err((message, pos)) => return Err(response!(Wrong(message), self.source.file, pos)),
+                    Ok(None) => (),
+                }
+
+                if let Some(name) = self.intrinsic(caller) {
+                    return self.visit_intrinsic(name, args, &caller.pos)
+                }
+
+                if self.is_embed(caller) {
+                    return self.type_expression(expression).map(|_| ())
+                }
+
+                if self.string_intrinsic(caller).is_some() || self.numeric_intrinsic(caller).is_some() || self.is_range(caller) {
+                    for arg in args.iter() {
+                        self.visit_expression(arg)?
+                    }
+
+                    return self.type_expression(expression).map(|_| ())
+                }
+
+                let (params, annotations) = match caller.node {
+                    Identifier(ref name) => self.symtab.fetch(name).map(|t| (t.params, t.annotations)).unwrap_or_default(),
+                    _ => (Vec::new(), Vec::new()),
+                };
+
+                for (index, arg) in args.iter().enumerate() {
+                    if let (Some(param), Some(Some(ref expected))) = (params.get(index), annotations.get(index)) {
+                        let t = self.visit_with_expected_type(arg, expected)?;
+
+                        if !t.node.fits(expected) {
+                            return Err(response!(
+                                Wrong(format!("`{}` is annotated as `{:?}`, but got `{:?}`", param, expected, t.node)),
+                                self.source.file,
+                                arg.pos
+                            ))
+                        }
+                    } else {
+                        self.visit_expression(arg)?
+                    }
+                }
+
+                if let Some((name, overloads)) = self.overloads(caller) {
+                    return self.resolve_overload(&name, &overloads, args.len(), &caller.pos).map(|_| ())
+                }
+
                 let caller_t = self.type_expression(caller)?.node;
 
                 if let TypeNode::Func(ref params) = caller_t {
@@ -594,7 +1384,7 @@ impl<'a> Visitor<'a> {
                 Ok(())
             },
 
-            Array(ref content) => {
+            Array(ref content) | Tuple(ref content) => {
                 for element in content.iter() {
                     self.visit_expression(element)?
                 }
@@ -602,288 +1392,2856 @@ impl<'a> Visitor<'a> {
                 Ok(())
             },
 
+            Range(ref start, ref end) | RangeInclusive(ref start, ref end) => {
+                self.visit_expression(start)?;
+                self.visit_expression(end)?;
+
+                self.type_expression(expression).map(|_| ())
+            },
+
+            Slice(ref target, ref start, ref end) => {
+                self.visit_expression(target)?;
+
+                for bound in start.iter().chain(end.iter()) {
+                    self.visit_expression(bound)?
+                }
+
+                self.type_expression(expression).map(|_| ())
+            },
+
             Dict(ref content) => {
-                for (_, value) in content.iter() {
-                    self.visit_expression(value)?
+                for entry in content.iter() {
+                    self.visit_expression(entry.value())?
                 }
 
                 Ok(())
             },
 
+            Spread(ref source) => self.visit_expression(source),
+
+            Binary(ref left, _, ref right) => {
+                self.visit_expression(left)?;
+                self.visit_expression(right)?;
+
+                self.type_expression(expression).map(|_| ())
+            },
+
+            Conditional(ref cond, ref then, ref otherwise) => {
+                for e in [cond, then, otherwise].iter() {
+                    self.visit_expression(e)?
+                }
+
+                self.type_expression(expression).map(|_| ())
+            },
+
             _ => Ok(())
         }
     }
 
-    pub fn type_expression(&mut self, expression: &Expression) -> Result<Type, ()> {
-        use self::ExpressionNode::*;
-
-        let t = match expression.node {
-            Str(_) => Type::from(TypeNode::Str),
-            Bool(_) => Type::from(TypeNode::Bool),
-            Int(_) => Type::from(TypeNode::Int),
-            Float(_) => Type::from(TypeNode::Float),
-            Binary(ref left, ref op, ref right) => {
-                use self::Operator::*;
+    // the right side of `is`, a type name and what it narrows to
+    fn type_test(&self, expression: &Expression) -> Result<(String, TypeNode), ()> {
+        if let ExpressionNode::Identifier(ref name) = expression.node {
+            let t = match name.as_str() {
+                "Int"   => Some(TypeNode::Int),
+                "Float" => Some(TypeNode::Float),
+                "Str"   => Some(TypeNode::Str),
+                "Bool"  => Some(TypeNode::Bool),
+                "Nil"   => Some(TypeNode::Nil),
+                "Dict"  => Some(TypeNode::Dict(Vec::new())),
+                "List"  => Some(TypeNode::Array(Box::new(TypeNode::Any))),
+                "Func" | "Any" => Some(TypeNode::Any),
+                _ => None,
+            };
+
+            if let Some(t) = t {
+                return Ok((name.clone(), t))
+            }
+        }
 
-                if op == &Index {
-                    let a = self.type_expression(left)?.node;
-                    let b = self.type_expression(right)?.node;
+        Err(response!(
+            Wrong("expected a type name like `Int`, `Float`, `Str`, `Bool`, `Nil`, `Func`, `List`, `Dict` or `Any`"),
+            self.source.file,
+            expression.pos
+        ))
+    }
 
-                    let valid = [TypeNode::Any, TypeNode::Str, TypeNode::Int];
+    // the variable an `x is T` condition tests, with its type narrowed to `T`
+    fn narrowing(&self, cond: &Expression, taken: bool) -> Option<(String, Type)> {
+        if let ExpressionNode::Binary(ref left, ref op, ref right) = cond.node {
+            if *op == Operator::Is && taken {
+                if let ExpressionNode::Identifier(ref name) = left.node {
+                    let mut t = self.symtab.fetch(name)?;
 
-                    if !valid.contains(&a) && !valid.contains(&b) {
-                        return Err(response!(
-                            Wrong(format!(
-                                "can't index like this `{:?} {} {:?}`",
-                                a, op, b
-                            )),
-                            self.source.file,
-                            expression.pos
-                        ))
+                    if t.node == TypeNode::Any {
+                        t.node = self.type_test(right).ok()?.1;
                     }
 
-                    return Ok(Type::from(TypeNode::Any))
+                    return Some((name.clone(), t))
                 }
+            }
 
-                match (
-                    self.type_expression(left)?.node,
-                    op,
-                    self.type_expression(right)?.node,
-                ) {
-                    (ref a, ref op, ref b) => match **op {
-                        Add | Sub | Mul | Div | Mod => {
-                            if [a, b] != [&TypeNode::Nil, &TypeNode::Nil] {
-                                // real hack here
-                                if a == b || [a, b].contains(&&TypeNode::Any) {
-                                    match a {
-                                        TypeNode::Float | TypeNode::Int | TypeNode::Any => match b {
-                                            TypeNode::Float | TypeNode::Int | TypeNode::Any => {
-                                                Type::from(a.clone())
-                                            }
-
-                                            _ => {
-                                                return Err(response!(
-                                                    Wrong(format!(
-                                                        "can't perform operation `{:?} {} {:?}`",
-                                                        a, op, b
-                                                    )),
-                                                    self.source.file,
-                                                    expression.pos
-                                                ))
-                                            }
-                                        },
+            // past `x != nil`, or in the else of `x == nil`, something typed nil could be anything else
+            if let Some(compared) = Self::nil_compared(left, op, right) {
+                if let ExpressionNode::Identifier(ref name) = compared.node {
+                    if taken == (*op == Operator::NEq) {
+                        let mut t = self.symtab.fetch(name)?;
 
-                                        _ => {
-                                            return Err(response!(
-                                                Wrong(format!(
-                                                    "can't perform operation `{:?} {} {:?}`",
-                                                    a, op, b
-                                                )),
-                                                self.source.file,
-                                                expression.pos
-                                            ))
-                                        }
-                                    }
-                                } else {
-                                    return Err(response!(
-                                        Wrong(format!(
-                                            "can't perform operation `{:?} {} {:?}`",
-                                            a, op, b
-                                        )),
-                                        self.source.file,
-                                        expression.pos
-                                    ));
-                                }
-                            } else {
-                                return Err(response!(
-                                    Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
-                                    self.source.file,
-                                    expression.pos
-                                ));
-                            }
+                        // an annotated `let` is what it was annotated as
+                        if t.node == TypeNode::Nil {
+                            t.node = t.annotation.clone().unwrap_or(TypeNode::Any)
                         }
 
-                        Pow => match a {
-                            TypeNode::Float | TypeNode::Int | TypeNode::Any => match b {
-                                TypeNode::Float | TypeNode::Int | TypeNode::Any => Type::from(a.clone()),
+                        return Some((name.clone(), t))
+                    }
+                }
+            }
+        }
 
-                                _ => {
-                                    return Err(response!(
-                                        Wrong(format!(
-                                            "can't perform operation `{:?} {} {:?}`",
-                                            a, op, b
-                                        )),
-                                        self.source.file,
-                                        expression.pos
-                                    ))
-                                }
-                            },
+        None
+    }
 
-                            _ => {
-                                return Err(response!(
-                                    Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
-                                    self.source.file,
-                                    expression.pos
-                                ))
-                            }
-                        },
+    // what `x == nil` or `x != nil`, either way around, compares to nil
+    fn nil_compared<'e>(left: &'e Expression, op: &Operator, right: &'e Expression) -> Option<&'e Expression> {
+        if *op != Operator::Eq && *op != Operator::NEq {
+            return None
+        }
 
-                        And | Or => {
-                            if a == b && *a == TypeNode::Bool || *a == TypeNode::Any {
-                                Type::from(TypeNode::Bool)
-                            } else {
-                                return Err(response!(
-                                    Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
-                                    self.source.file,
-                                    expression.pos
-                                ));
-                            }
+        match (&left.node, &right.node) {
+            (ExpressionNode::Nil, _) => Some(right),
+            (_, ExpressionNode::Nil) => Some(left),
+            _ => None,
+        }
+    }
+
+    // `map` and `filter` take a function, which the vm can't call back from a native,
+    // so they're compiled into loops unless the name has been rebound
+    fn intrinsic(&self, callee: &Expression) -> Option<&'static str> {
+        if let ExpressionNode::Identifier(ref name) = callee.node {
+            for intrinsic in ["map", "filter"].iter() {
+                if name == intrinsic && self.symtab.fetch(name).is_none() {
+                    return Some(intrinsic)
+                }
+            }
+        }
+
+        None
+    }
+
+    // `f(a, ...xs)` calls `f` with `a` and however many elements `xs` has, through a function taking the arguments
+    // and the list in the order they're written, which picks the call by `len(xs)` when it runs:
+    // `fun($arg0, $list): if $len($list) == 2: return f($arg0, $list[0], $list[1])`. only the lengths
+    // some arity of `f` takes get a call, any other length fails the run
+    fn spread_call(&mut self, expression: &Expression) -> Result<Option<Expression>, ()> {
+        use self::ExpressionNode::*;
+
+        let (callee, args, keywords) = match expression.node {
+            Call(ref callee, ref args, ref keywords) if args.iter().any(Self::is_spread) => (callee, args, keywords),
+            _ => return Ok(None),
+        };
+
+        let pos = &expression.pos;
+        let at = |node: ExpressionNode| Expression::new(node, pos.clone());
+        let id = |name: &str| at(Identifier(name.to_string()));
+        let statement = |node: StatementNode| Statement::new(node, pos.clone());
+
+        if args.iter().filter(|arg| Self::is_spread(arg)).count() > 1 {
+            return Err(response!(
+                Wrong("only one list can be spread into a call, there's no telling how to split the arguments between them"),
+                self.source.file,
+                pos
+            ))
+        }
+
+        let index = args.iter().position(Self::is_spread).unwrap();
+
+        let source = match args[index].node {
+            Spread(ref source) => (**source).clone(),
+            _ => unreachable!(),
+        };
+
+        self.check_spread(&source, false)?;
+
+        let fixed = args.len() - 1 + keywords.len();
+
+        let arities = match self.overloads(callee) {
+            Some((_, overloads)) => overloads.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+
+            None => match self.type_expression(callee)?.node {
+                TypeNode::Func(arity) => vec!(arity),
+                _ => (fixed ..= MAX_ARGS).collect(),
+            },
+        };
+
+        let name = match callee.node {
+            Identifier(ref name) => name.clone(),
+            _ => String::from("this"),
+        };
+
+        let takes = arities.iter().map(|arity| arity.to_string()).collect::<Vec<_>>().join(" or ");
+
+        if arities.iter().all(|&arity| arity < fixed) {
+            return Err(response!(
+                Wrong(format!("`{}` takes {} arguments, that's fewer than there are without the spread list", name, takes)),
+                self.source.file,
+                pos
+            ))
+        }
+
+        // a name is read where the call is made, anything else is evaluated first, like any callee
+        let (function, mut params, mut written) = match callee.node {
+            Identifier(_) => ((**callee).clone(), Vec::new(), Vec::new()),
+            _ => (id("$f"), vec!("$f".to_string()), vec!((**callee).clone())),
+        };
+
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+
+        for (i, arg) in args.iter().enumerate() {
+            let name = format!("$arg{}", i);
+
+            if i == index {
+                params.push("$list".to_string());
+                written.push(source.clone());
+
+                continue
+            }
+
+            params.push(name.clone());
+            written.push(arg.clone());
+
+            if i < index { before.push(id(&name)) } else { after.push(id(&name)) }
+        }
+
+        let mut passed = Vec::new();
+
+        for (i, (keyword, value)) in keywords.iter().enumerate() {
+            let name = format!("$keyword{}", i);
+
+            params.push(name.clone());
+            written.push(value.clone());
+            passed.push((keyword.clone(), id(&name)));
+        }
+
+        // no locals, they'd break a function declared in another
+        let mut body = Vec::new();
+        let count = at(Call(Rc::new(id("$len")), vec!(id("$list")), Vec::new()));
+
+        for arity in arities.iter().filter(|&&arity| arity >= fixed) {
+            let length = arity - fixed;
+
+            let mut unpacked = before.clone();
+
+            for i in 0 .. length {
+                unpacked.push(at(Binary(Rc::new(id("$list")), Operator::Index, Rc::new(at(Int(i as i64))))))
+            }
+
+            unpacked.extend(after.iter().cloned());
+
+            body.push(statement(StatementNode::If(
+                at(Binary(Rc::new(count.clone()), Operator::Eq, Rc::new(at(Int(length as i64))))),
+                vec!(statement(StatementNode::Return(Some(at(Call(Rc::new(function.clone()), unpacked, passed.clone())))))),
+                Vec::new()
+            )))
+        }
+
+        let message = format!("`{}` takes {} arguments, the list spread into it doesn't make up the difference", name, takes);
+
+        body.push(statement(StatementNode::Expression(at(Call(Rc::new(id("$check")), vec!(at(Bool(false)), at(Str(message))), Vec::new())))));
+
+        let apply = at(AnonFunction(Self::lowered_name("spread", pos), params, body));
+
+        Ok(Some(at(Call(Rc::new(apply), written, Vec::new()))))
+    }
+
+    // `f(1, c = 3, b = 2)` is `f(1, 2, 3)`, every keyword goes where the param of its name is
+    fn keyword_call(&self, expression: &Expression) -> Result<Option<Expression>, (String, Pos)> {
+        let (callee, written, order) = match self.keyword_order(expression)? {
+            Some(ordering) => ordering,
+            None => return Ok(None),
+        };
+
+        let ordered = order.iter().map(|&i| written[i].clone()).collect();
+
+        Ok(Some(Expression::new(ExpressionNode::Call(callee, ordered, Vec::new()), expression.pos.clone())))
+    }
+
+    // what `keyword_call` gives, but with the arguments still run in the order they're written in, `f(c = g(), a = h())`
+    // is `fun($keyword0, $keyword1): return f($keyword1, $keyword0)` called with `g()` and `h()`
+    fn keyword_call_in_order(&self, expression: &Expression) -> Result<Option<Expression>, (String, Pos)> {
+        use self::ExpressionNode::*;
+
+        let (callee, written, order) = match self.keyword_order(expression)? {
+            Some(ordering) => ordering,
+            None => return Ok(None),
+        };
+
+        let pos = &expression.pos;
+        let at = |node: ExpressionNode| Expression::new(node, pos.clone());
+
+        if order.windows(2).all(|pair| pair[0] < pair[1]) || written.iter().all(Self::is_pure) {
+            let ordered = order.iter().map(|&i| written[i].clone()).collect();
+
+            return Ok(Some(at(Call(callee, ordered, Vec::new()))))
+        }
+
+        let names = (0 .. written.len())
+            .map(|i| Self::lowered_name(&format!("keyword{}", i), pos))
+            .collect::<Vec<_>>();
+
+        let ordered = order.iter().map(|&i| at(Identifier(names[i].clone()))).collect();
+
+        let body = vec!(
+            Statement::new(StatementNode::Return(Some(at(Call(callee, ordered, Vec::new())))), pos.clone()),
+        );
+
+        let function = at(AnonFunction(Self::lowered_name("keywords", pos), names, body));
+
+        Ok(Some(at(Call(Rc::new(function), written, Vec::new()))))
+    }
+
+    // the callee, the arguments as written with the positional ones first, and which of them goes to each param
+    fn keyword_order(&self, expression: &Expression) -> Result<Option<(Rc<Expression>, Vec<Expression>, Vec<usize>)>, (String, Pos)> {
+        use self::ExpressionNode::*;
+
+        let (callee, args, keywords) = match expression.node {
+            Call(ref callee, ref args, ref keywords) if !keywords.is_empty() => (callee, args, keywords),
+            _ => return Ok(None),
+        };
+
+        let (name, params) = match callee.node {
+            Identifier(ref name) => (name.clone(), self.symtab.fetch(name).map(|t| t.params).unwrap_or_default()),
+            _ => (String::from("this"), Vec::new()),
+        };
+
+        if params.is_empty() {
+            return Err((
+                format!("`{}` doesn't take keyword arguments, only a `fun` that isn't overloaded does", name),
+                keywords[0].1.pos.clone()
+            ))
+        }
+
+        let mut slots = (0 .. args.len()).map(Some).collect::<Vec<_>>();
+        slots.resize(params.len().max(args.len()), None);
+
+        for (i, (keyword, arg)) in keywords.iter().enumerate() {
+            match params.iter().position(|param| param == keyword) {
+                Some(index) if slots[index].is_none() => slots[index] = Some(args.len() + i),
+
+                Some(_) => return Err((
+                    format!("`{}` is passed to `{}` more than once", keyword, name),
+                    arg.pos.clone()
+                )),
+
+                None => return Err((
+                    format!("`{}` has no param `{}`, only `{}`", name, keyword, params.join("`, `")),
+                    arg.pos.clone()
+                )),
+            }
+        }
+
+        let mut order = Vec::new();
+
+        for (param, slot) in params.iter().zip(slots.iter()) {
+            match *slot {
+                Some(index) => order.push(index),
+
+                None => return Err((
+                    format!("`{}` is missing an argument for `{}`", name, param),
+                    expression.pos.clone()
+                )),
+            }
+        }
+
+        // too many positional args are left to the arity check
+        order.extend(slots.into_iter().skip(params.len()).flatten());
+
+        let mut written = args.clone();
+        written.extend(keywords.iter().map(|(_, arg)| arg.clone()));
+
+        Ok(Some((callee.clone(), written, order)))
+    }
+
+    // `x.name(args)` is `name(x, args)` when an interface declares `name` and `x` has every field the interface
+    // reads off `self`, even if `x` has a field by that name. on anything else it calls the field
+    fn method_call(&mut self, expression: &Expression) -> Result<Option<Expression>, ()> {
+        use self::ExpressionNode::*;
+
+        if let Call(ref callee, ref args, ref keywords) = expression.node {
+            if let Binary(ref receiver, Operator::Index, ref method) = callee.node {
+                if let Str(ref name) = method.node {
+                    let required = match self.methods.get(name) {
+                        Some(fields) if self.symtab.fetch(name).is_some() => fields.clone(),
+                        _ => return Ok(None),
+                    };
+
+                    let conforms = match self.type_expression(receiver)?.node {
+                        TypeNode::Dict(ref fields) => required.iter().all(|field| fields.iter().any(|(key, _)| key == field)),
+                        TypeNode::DictOf(_) | TypeNode::Any => true,
+                        _ => false,
+                    };
+
+                    if conforms {
+                        let mut args = args.clone();
+                        args.insert(0, (**receiver).clone());
+
+                        let method = Expression::new(Identifier(name.clone()), method.pos.clone());
+
+                        return Ok(Some(Expression::new(Call(Rc::new(method), args, keywords.clone()), expression.pos.clone())))
+                    }
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    // every field read off `self` in a body, anonymous functions included
+    fn self_fields(body: &[Statement], fields: &mut Vec<String>) {
+        use self::StatementNode::*;
+
+        for statement in body.iter() {
+            match statement.node {
+                Expression(ref e) | Return(Some(ref e)) | Yield(ref e) | Check(ref e) | Const(_, ref e)
+                | Declaration(_, _, Some(ref e)) | BreakLabel(_, Some(ref e)) => Self::self_fields_of(e, fields),
+
+                Assignment(ref target, ref value) => {
+                    Self::self_fields_of(target, fields);
+                    Self::self_fields_of(value, fields)
+                },
+
+                If(ref cond, ref then, ref elses) => {
+                    Self::self_fields_of(cond, fields);
+                    Self::self_fields(then, fields);
+
+                    for (cond, body) in elses.iter() {
+                        if let Some(ref cond) = *cond {
+                            Self::self_fields_of(cond, fields)
+                        }
+
+                        Self::self_fields(body, fields)
+                    }
+                },
+
+                While(ref e, ref body) | For(_, _, ref e, ref body) | WhileLet(_, ref e, ref body, None) => {
+                    Self::self_fields_of(e, fields);
+                    Self::self_fields(body, fields)
+                },
+
+                WhileLet(_, ref e, ref body, Some(ref else_)) => {
+                    Self::self_fields_of(e, fields);
+                    Self::self_fields(body, fields);
+                    Self::self_fields(else_, fields)
+                },
+
+                Match(ref e, ref arms, ref else_) => {
+                    Self::self_fields_of(e, fields);
+
+                    for (_, body) in arms.iter() {
+                        Self::self_fields(body, fields)
+                    }
+
+                    if let Some(ref else_) = *else_ {
+                        Self::self_fields(else_, fields)
+                    }
+                },
+
+                Block(ref body) | When(_, ref body) => Self::self_fields(body, fields),
+
+                _ => (),
+            }
+        }
+    }
+
+    fn self_fields_of(expression: &Expression, fields: &mut Vec<String>) {
+        use self::ExpressionNode::*;
+
+        match expression.node {
+            Binary(ref receiver, Operator::Index, ref key) if receiver.node == Identifier("self".to_string()) => {
+                if let Str(ref field) = key.node {
+                    if !fields.contains(field) {
+                        fields.push(field.clone())
+                    }
+                }
+            },
+
+            Binary(ref left, _, ref right) | With(ref left, ref right) | OptionalIndex(ref left, ref right)
+            | Range(ref left, ref right) | RangeInclusive(ref left, ref right) => {
+                Self::self_fields_of(left, fields);
+                Self::self_fields_of(right, fields)
+            },
+
+            Call(ref callee, ref args, ref keywords) => {
+                Self::self_fields_of(callee, fields);
+
+                for arg in args.iter().chain(keywords.iter().map(|(_, arg)| arg)) {
+                    Self::self_fields_of(arg, fields)
+                }
+            },
+
+            Neg(ref e) | Not(ref e) | Spread(ref e) => Self::self_fields_of(e, fields),
+
+            Conditional(ref cond, ref then, ref otherwise) => for e in [cond, then, otherwise].iter() {
+                Self::self_fields_of(e, fields)
+            },
+
+            Array(ref content) | Tuple(ref content) => for e in content.iter() {
+                Self::self_fields_of(e, fields)
+            },
+
+            Dict(ref content) => for entry in content.iter() {
+                Self::self_fields_of(entry.value(), fields)
+            },
+
+            Slice(ref list, ref start, ref end) => {
+                Self::self_fields_of(list, fields);
+
+                for bound in start.iter().chain(end.iter()) {
+                    Self::self_fields_of(bound, fields)
+                }
+            },
+
+            AnonFunction(_, _, ref body) | Labeled(_, ref body) => Self::self_fields(body, fields),
+
+            _ => (),
+        }
+    }
+
+    // like `intrinsic`, string functions can be shadowed too
+    fn string_intrinsic(&self, callee: &Expression) -> Option<(&'static str, usize, TypeNode)> {
+        if let ExpressionNode::Identifier(ref name) = callee.node {
+            if self.symtab.fetch(name).is_none() {
+                return string_intrinsics().iter().find(|(intrinsic, ..)| name == intrinsic).cloned()
+            }
+        }
+
+        None
+    }
+
+    fn type_string_intrinsic(&mut self, name: &str, arity: usize, result: TypeNode, args: &Vec<Expression>, pos: &Pos) -> Result<Type, ()> {
+        if args.len() != arity {
+            return Err(response!(
+                Wrong(format!("wrong amount of arguments, expected {} but got {}", arity, args.len())),
+                self.source.file,
+                pos
+            ))
+        }
+
+        for arg in args.iter() {
+            match self.type_expression(arg)?.node {
+                TypeNode::Str | TypeNode::Any => (),
+
+                t => return Err(response!(
+                    Wrong(format!("`{}` needs a string, but got `{:?}`", name, t)),
+                    self.source.file,
+                    arg.pos
+                )),
+            }
+        }
+
+        Ok(Type::from(result))
+    }
+
+    // `abs`, `min` and `max`, shadowable like the rest
+    fn numeric_intrinsic(&self, callee: &Expression) -> Option<(&'static str, usize)> {
+        if let ExpressionNode::Identifier(ref name) = callee.node {
+            if self.symtab.fetch(name).is_none() {
+                return NUMERIC_INTRINSICS.iter().find(|(intrinsic, _)| name == intrinsic).cloned()
+            }
+        }
+
+        None
+    }
+
+    // the type of the operands, which have to agree like they do for arithmetic, `Any` goes with either
+    fn type_numeric_intrinsic(&mut self, name: &str, arity: usize, args: &Vec<Expression>, pos: &Pos) -> Result<Type, ()> {
+        if args.len() != arity {
+            return Err(response!(
+                Wrong(format!("wrong amount of arguments, expected {} but got {}", arity, args.len())),
+                self.source.file,
+                pos
+            ))
+        }
+
+        let mut result: Option<TypeNode> = None;
+
+        for arg in args.iter() {
+            let t = self.type_expression(arg)?.node;
+
+            if ![TypeNode::Int, TypeNode::Float, TypeNode::Any].contains(&t) {
+                return Err(response!(
+                    Wrong(format!("`{}` needs a number, but got `{:?}`", name, t)),
+                    self.source.file,
+                    arg.pos
+                ))
+            }
+
+            result = match result {
+                None => Some(t),
+                Some(TypeNode::Any) => Some(TypeNode::Any),
+                Some(_) if t == TypeNode::Any => Some(TypeNode::Any),
+                Some(ref previous) if *previous == t => Some(t),
+
+                Some(previous) => return Err(response!(
+                    Wrong(format!("`{}` can't compare `{:?}` to `{:?}`, convert one of them first", name, previous, t)),
+                    self.source.file,
+                    pos
+                )),
+            }
+        }
+
+        Ok(Type::from(result.unwrap_or(TypeNode::Any)))
+    }
+
+    fn is_range(&self, callee: &Expression) -> bool {
+        match callee.node {
+            ExpressionNode::Identifier(ref name) => name == "range" && self.symtab.fetch(name).is_none(),
+            _ => false,
+        }
+    }
+
+    // `range(end)` and `range(start, end)` fill in a start of 0 and a step of 1
+    fn lower_range(args: &Vec<Expression>, pos: &Pos) -> Expression {
+        let at = |node: ExpressionNode| Expression::new(node, pos.clone());
+
+        let mut filled = args.clone();
+
+        if filled.len() == 1 {
+            filled.insert(0, at(ExpressionNode::Int(0)))
+        }
+
+        if filled.len() == 2 {
+            filled.push(at(ExpressionNode::Int(1)))
+        }
+
+        at(ExpressionNode::Call(Rc::new(at(ExpressionNode::Identifier("$range".to_string()))), filled, Vec::new()))
+    }
+
+    fn type_range(&mut self, args: &Vec<Expression>, pos: &Pos) -> Result<Type, ()> {
+        if args.is_empty() || args.len() > 3 {
+            return Err(response!(
+                Wrong(format!("`range` takes 1 to 3 arguments, but got {}", args.len())),
+                self.source.file,
+                pos
+            ))
+        }
+
+        for arg in args.iter() {
+            match self.type_expression(arg)?.node {
+                TypeNode::Int | TypeNode::Any => (),
+
+                t => return Err(response!(
+                    Wrong(format!("`range` needs integer bounds, but got `{:?}`", t)),
+                    self.source.file,
+                    arg.pos
+                )),
+            }
+        }
+
+        // a step that isn't known until runtime is checked by `$range`
+        if let Some(step) = args.get(2) {
+            if let Ok(ExpressionNode::Int(n)) = self.fold(step, &HashMap::new(), 0) {
+                if n <= 0 {
+                    return Err(response!(
+                        Wrong(format!("`range` needs a step above 0, but got {}", n)),
+                        self.source.file,
+                        step.pos
+                    ))
+                }
+            }
+        }
+
+        Ok(Type::from(TypeNode::Array(Box::new(TypeNode::Int))))
+    }
+
+    fn is_embed(&self, callee: &Expression) -> bool {
+        match callee.node {
+            ExpressionNode::Identifier(ref name) => name == "embed" && self.symtab.fetch(name).is_none(),
+            _ => false,
+        }
+    }
+
+    // `embed("data.txt")` is the contents of that file, read while compiling, next to the source embedding it
+    fn embed(&self, args: &Vec<Expression>, pos: &Pos) -> Result<String, (String, Pos)> {
+        let path = match args.as_slice() {
+            [Expression { node: ExpressionNode::Str(ref path), .. }] => path,
+
+            _ => return Err((
+                "`embed` takes a single string literal, the path of the file to embed".to_string(),
+                pos.clone()
+            )),
+        };
+
+        let full = Path::new(&self.source.file.0).parent()
+            .map_or(Path::new(path).to_path_buf(), |dir| dir.join(path));
+
+        if let Some(content) = self.embedded.borrow().get(&full) {
+            return Ok(content.clone())
+        }
+
+        let mut content = String::new();
+
+        match File::open(&full).and_then(|mut file| file.read_to_string(&mut content)) {
+            Ok(_) => {
+                self.embedded.borrow_mut().insert(full, content.clone());
+
+                Ok(content)
+            },
+
+            Err(err) => Err((format!("can't embed `{}`: {}", full.display(), err), args[0].pos.clone())),
+        }
+    }
+
+    fn visit_intrinsic(&mut self, name: &str, args: &Vec<Expression>, pos: &Pos) -> Result<(), ()> {
+        if args.len() != 2 {
+            return Err(response!(
+                Wrong(format!("wrong amount of arguments, expected 2 but got {}", args.len())),
+                self.source.file,
+                pos
+            ))
+        }
+
+        self.visit_expression(&args[0])?;
+
+        match self.visit_with_expected_type(&args[1], &TypeNode::Func(1))?.node {
+            TypeNode::Func(1) | TypeNode::Any => Ok(()),
+
+            TypeNode::Func(n) => Err(response!(
+                Wrong(format!("`{}` needs a function of 1 argument, but this one takes {}", name, n)),
+                self.source.file,
+                args[1].pos
+            )),
+
+            t => Err(response!(
+                Wrong(format!("`{}` needs a function, but got `{:?}`", name, t)),
+                self.source.file,
+                args[1].pos
+            )),
+        }
+    }
+
+    // `map(list, f)` becomes
+    //
+    //     fun($list, $f):
+    //         let $out = []
+    //         let $i = 0
+    //         while $i < $len($list):
+    //             $push($out, $f($list[$i]))
+    //             $i = $i + 1
+    //         return $out
+    //
+    // called with `list` and `f`, `filter` only pushes when `$f` says so, a `for` over either
+    // skips the list and calls `f` as it goes, see `lower_lazy_for`
+    fn lower_intrinsic(name: &str, args: &Vec<Expression>, pos: &Pos) -> Expression {
+        use self::ExpressionNode::*;
+
+        let at = |node: ExpressionNode| Expression::new(node, pos.clone());
+        let id = |name: &str| at(Identifier(name.to_string()));
+        let statement = |node: StatementNode| Statement::new(node, pos.clone());
+        let call = |callee: &str, args: Vec<Expression>| at(Call(Rc::new(id(callee)), args, Vec::new()));
+
+        let element = at(Binary(Rc::new(id("$list")), Operator::Index, Rc::new(id("$i"))));
+
+        let step = if name == "map" {
+            statement(StatementNode::Expression(
+                call("$push", vec!(id("$out"), call("$f", vec!(element))))
+            ))
+        } else {
+            statement(StatementNode::If(
+                call("$f", vec!(element.clone())),
+                vec!(statement(StatementNode::Expression(call("$push", vec!(id("$out"), element))))),
+                Vec::new()
+            ))
+        };
+
+        let body = vec!(
+            statement(StatementNode::Declaration("$out".to_string(), None, Some(at(Array(Vec::new()))))),
+            statement(StatementNode::Declaration("$i".to_string(), None, Some(at(Int(0))))),
+            statement(StatementNode::While(
+                at(Binary(Rc::new(id("$i")), Operator::Lt, Rc::new(call("$len", vec!(id("$list")))))),
+                vec!(
+                    step,
+                    statement(StatementNode::Assignment(
+                        id("$i"),
+                        at(Binary(Rc::new(id("$i")), Operator::Add, Rc::new(at(Int(1)))))
+                    )),
+                )
+            )),
+            statement(StatementNode::Return(Some(id("$out")))),
+        );
+
+        let function = at(AnonFunction(format!("${}", name), vec!("$list".to_string(), "$f".to_string()), body));
+
+        at(Call(Rc::new(function), args.clone(), Vec::new()))
+    }
+
+    // `filter` gives a list of what it was given, `map` one of what `f` gives back when that's
+    // a function of one `return` to type, otherwise a list of anything
+    fn type_intrinsic(&mut self, name: &str, args: &Vec<Expression>) -> Result<Type, ()> {
+        use self::ExpressionNode::*;
+
+        if args.len() != 2 {
+            return Ok(Type::from(TypeNode::Array(Box::new(TypeNode::Any))))
+        }
+
+        let element = match self.type_expression(&args[0])?.node {
+            TypeNode::Array(element) => *element,
+            _ => TypeNode::Any,
+        };
+
+        if name == "filter" {
+            return Ok(Type::from(TypeNode::Array(Box::new(element))))
+        }
+
+        let result = match args[1].node {
+            AnonFunction(_, ref params, ref body) if params.len() == 1 => match body.as_slice() {
+                [Statement { node: StatementNode::Return(Some(ref value)), .. }] => {
+                    self.symtab.push();
+                    self.assign(params[0].clone(), Type::from(element));
+
+                    let result = self.type_expression(value);
+
+                    self.symtab.pop();
+
+                    result?.node
+                },
+
+                _ => TypeNode::Any,
+            },
+
+            _ => TypeNode::Any,
+        };
+
+        Ok(Type::from(TypeNode::Array(Box::new(result))))
+    }
+
+    pub fn type_expression(&mut self, expression: &Expression) -> Result<Type, ()> {
+        let t = self.infer_expression(expression)?;
+
+        self.record_type(expression, &t);
+
+        Ok(t)
+    }
+
+    // the first type wins, sugar is lowered into nodes that carry the position of what they came from
+    fn record_type(&mut self, expression: &Expression, t: &Type) {
+        if let Some(ref mut types) = self.types {
+            types.entry(expression.pos.clone()).or_insert_with(|| t.clone());
+        }
+    }
+
+    // visits and types an expression where the context already says what it should be, an annotated
+    // `let` or param, for what can't be typed on its own. function types only go by arity, so that's
+    // all an anonymous function is checked for
+    pub fn visit_with_expected_type(&mut self, expression: &Expression, expected: &TypeNode) -> Result<Type, ()> {
+        use self::ExpressionNode::*;
+
+        let t = match (&expression.node, expected) {
+            // an empty array has no elements to say what it holds
+            (Array(ref content), _) if content.is_empty() => {
+                self.visit_expression(expression)?;
+
+                Type::from(expected.clone())
+            },
+
+            // a function of the wrong arity is wrong before anything in its body is
+            (AnonFunction(_, ref params, _), TypeNode::Func(n)) if params.len() != *n => {
+                return Err(response!(
+                    Wrong(format!("expected a function of {} argument{}, but this one takes {}", n, if *n == 1 { "" } else { "s" }, params.len())),
+                    self.source.file,
+                    expression.pos
+                ))
+            },
+
+            _ => {
+                self.visit_expression(expression)?;
+
+                return self.type_expression(expression)
+            },
+        };
+
+        self.record_type(expression, &t);
+
+        Ok(t)
+    }
+
+    fn infer_expression(&mut self, expression: &Expression) -> Result<Type, ()> {
+        use self::ExpressionNode::*;
+
+        let t = match expression.node {
+            Str(_) => Type::from(TypeNode::Str),
+            Char(_) => Type::from(TypeNode::Char),
+            Bool(_) => Type::from(TypeNode::Bool),
+            Int(_) => Type::from(TypeNode::Int),
+            Float(_) => Type::from(TypeNode::Float),
+            Binary(ref left, ref op, ref right) => {
+                use self::Operator::*;
+
+                if op == &Is {
+                    self.type_expression(left)?;
+                    self.type_test(right)?;
+
+                    return Ok(Type::from(TypeNode::Bool))
+                }
+
+                // whatever the host's native makes of its operands
+                if let Custom(ref symbol) = *op {
+                    if !self.operators.iter().any(|(registered, _)| registered == symbol) {
+                        return Err(response!(
+                            Wrong(format!("no operator `{}` was registered", symbol)),
+                            self.source.file,
+                            expression.pos
+                        ))
+                    }
+
+                    self.type_expression(left)?;
+                    self.type_expression(right)?;
+
+                    return Ok(Type::from(TypeNode::Any))
+                }
+
+                if op == &Index {
+                    let a = self.type_expression(left)?.node;
+                    let b = self.type_expression(right)?.node;
+
+                    let valid = [TypeNode::Any, TypeNode::Str, TypeNode::Int];
+
+                    if !valid.contains(&a) && !valid.contains(&b) {
+                        return Err(response!(
+                            Wrong(format!(
+                                "can't index like this `{:?} {} {:?}`",
+                                a, op, b
+                            )),
+                            self.source.file,
+                            expression.pos
+                        ))
+                    }
+
+                    // literal keys into a dict we know the shape of keep their field type
+                    if let (TypeNode::Dict(ref fields), Str(ref key)) = (&a, &right.node) {
+                        if let Some((_, t)) = fields.iter().find(|(name, _)| name == key) {
+                            return Ok(Type::from(t.clone()))
+                        }
+                    }
+
+                    // any other key gets the type every field has, if they all have one
+                    if let TypeNode::Dict(ref fields) = a {
+                        if let Some((_, first)) = fields.first() {
+                            if fields.iter().all(|(_, t)| t == first) && [TypeNode::Str, TypeNode::Any].contains(&b) {
+                                return Ok(Type::from(first.clone()))
+                            }
+                        }
+                    }
+
+                    // and so do the elements of a list, or the values of an annotated dict
+                    if let TypeNode::DictOf(ref value) = a {
+                        return Ok(Type::from((**value).clone()))
+                    }
+
+                    if let TypeNode::Array(ref element) = a {
+                        if b == TypeNode::Int || b == TypeNode::Any {
+                            return Ok(Type::from((**element).clone()))
+                        }
+                    }
+
+                    return Ok(Type::from(TypeNode::Any))
+                }
+
+                match (
+                    self.type_expression(left)?.node,
+                    op,
+                    self.type_expression(right)?.node,
+                ) {
+                    // the operand that can't do arithmetic says more than both types together would
+                    (TypeNode::Dict(_), ref op, _) | (_, ref op, TypeNode::Dict(_))
+                    | (TypeNode::DictOf(_), ref op, _) | (_, ref op, TypeNode::DictOf(_))
+                        if [Add, Sub, Mul, Div, Mod, Pow, WrapAdd, WrapSub, WrapMul].contains(op) =>
+                    {
+                        return Err(response!(
+                            Wrong(format!("can't perform `{}` on a dict", op)),
+                            self.source.file,
+                            expression.pos
+                        ))
+                    }
+
+                    // ranges included, they're lists of ints
+                    (TypeNode::Array(_), ref op, _) | (_, ref op, TypeNode::Array(_))
+                        if [Add, Sub, Mul, Div, Mod, Pow, WrapAdd, WrapSub, WrapMul].contains(op) =>
+                    {
+                        return Err(response!(
+                            Wrong(format!("can't perform `{}` on a list", op)),
+                            self.source.file,
+                            expression.pos
+                        ))
+                    }
+
+                    (ref a, ref op, ref b)
+                        if self.strict
+                            && [a, b].contains(&&TypeNode::Any)
+                            && ![And, Or, Concat].contains(op) =>
+                    {
+                        return Err(response!(
+                            Wrong(format!("can't use `Any` with `{}` in strict mode, narrow it with `is` first", op)),
+                            self.source.file,
+                            expression.pos
+                        ))
+                    }
+
+                    (ref a, ref op, ref b) => match **op {
+                        WrapAdd | WrapSub | WrapMul => {
+                            let ts = [TypeNode::Any, TypeNode::Int];
+
+                            if ts.contains(a) && ts.contains(b) {
+                                Type::from(TypeNode::Int)
+                            } else {
+                                return Err(response!(
+                                    Wrong(format!("wrapping arithmetic only works on ints, not `{:?} {} {:?}`", a, op, b)),
+                                    self.source.file,
+                                    expression.pos
+                                ));
+                            }
+                        }
+
+                        Add | Sub | Mul | Div | Mod => {
+                            if [a, b] != [&TypeNode::Nil, &TypeNode::Nil] {
+                                // real hack here
+                                if a == b || [a, b].contains(&&TypeNode::Any) {
+                                    match a {
+                                        TypeNode::Float | TypeNode::Int | TypeNode::Any => match b {
+                                            TypeNode::Float | TypeNode::Int | TypeNode::Any => {
+                                                Type::from(a.clone())
+                                            }
+
+                                            _ => {
+                                                return Err(response!(
+                                                    Wrong(format!(
+                                                        "can't perform operation `{:?} {} {:?}`",
+                                                        a, op, b
+                                                    )),
+                                                    self.source.file,
+                                                    expression.pos
+                                                ))
+                                            }
+                                        },
+
+                                        _ => {
+                                            return Err(response!(
+                                                Wrong(format!(
+                                                    "can't perform operation `{:?} {} {:?}`",
+                                                    a, op, b
+                                                )),
+                                                self.source.file,
+                                                expression.pos
+                                            ))
+                                        }
+                                    }
+                                } else {
+                                    return Err(response!(
+                                        Wrong(format!(
+                                            "can't perform operation `{:?} {} {:?}`",
+                                            a, op, b
+                                        )),
+                                        self.source.file,
+                                        expression.pos
+                                    ));
+                                }
+                            } else {
+                                return Err(response!(
+                                    Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
+                                    self.source.file,
+                                    expression.pos
+                                ));
+                            }
+                        }
+
+                        Pow => match a {
+                            TypeNode::Float | TypeNode::Int | TypeNode::Any => match b {
+                                TypeNode::Float | TypeNode::Int | TypeNode::Any => Type::from(a.clone()),
+
+                                _ => {
+                                    return Err(response!(
+                                        Wrong(format!(
+                                            "can't perform operation `{:?} {} {:?}`",
+                                            a, op, b
+                                        )),
+                                        self.source.file,
+                                        expression.pos
+                                    ))
+                                }
+                            },
+
+                            _ => {
+                                return Err(response!(
+                                    Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
+                                    self.source.file,
+                                    expression.pos
+                                ))
+                            }
+                        },
+
+                        And | Or => {
+                            if *a == TypeNode::Any || *a == TypeNode::Bool && [TypeNode::Bool, TypeNode::Any].contains(b) {
+                                Type::from(TypeNode::Bool)
+                            } else {
+                                return Err(response!(
+                                    Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
+                                    self.source.file,
+                                    expression.pos
+                                ));
+                            }
+                        }
+
+                        Concat => {
+                            // the right side gets shown as `print` would, but something typed nil is likely a mistake
+                            if [TypeNode::Str, TypeNode::Char, TypeNode::Any].contains(a) {
+                                match *b {
+                                    TypeNode::Nil => return Err(response!(
+                                        Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
+                                        self.source.file,
+                                        expression.pos
+                                    )),
+
+                                    _ => Type::from(TypeNode::Str),
+                                }
+                            } else {
+                                return Err(response!(
+                                    Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
+                                    self.source.file,
+                                    expression.pos
+                                ));
+                            }
+                        }
+
+                        Eq | NEq if Self::nil_compared(left, op, right).is_some() => Type::from(TypeNode::Bool),
+
+                        Eq | NEq => {
+                            let listed = |t: &TypeNode| if let TypeNode::Array(_) = t { true } else { false };
+
+                            // lists are compared by handle, never by what's in them
+                            if [a, b].contains(&&TypeNode::Nil) || listed(a) || listed(b) {
+                                return Err(response!(
+                                    Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
+                                    self.source.file,
+                                    expression.pos
+                                ));
+                            }
+
+                            Type::from(TypeNode::Bool)
+                        },
+
+                        Lt | Gt | LtEq | GtEq => {
+                            // ints and floats mix freely, the vm compares every number as f64
+                            let ts = [TypeNode::Any, TypeNode::Float, TypeNode::Int];
+                            if ts.contains(a) && ts.contains(b) {
+                                Type::from(TypeNode::Bool)
+                            } else {
+                                return Err(response!(
+                                    Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
+                                    self.source.file,
+                                    expression.pos
+                                ));
+                            }
+                        }
+
+                        _ => {
+                            return Err(response!(
+                                Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
+                                self.source.file,
+                                expression.pos
+                            ))
+                        }
+                    },
+                }
+            },
+
+            Neg(ref expr) => self.type_expression(expr)?,
+            Not(_) => Type::from(TypeNode::Bool),
+
+            // the type both branches agree on, `Any` when they don't
+            Conditional(ref cond, ref then, ref otherwise) => {
+                match self.type_expression(cond)?.node {
+                    TypeNode::Bool | TypeNode::Any => (),
+
+                    t => return Err(response!(
+                        Wrong(format!("a conditional expression needs a `Bool` condition, but got `{:?}`", t)),
+                        self.source.file,
+                        cond.pos
+                    )),
+                }
+
+                let then = self.type_expression(then)?.node;
+                let otherwise = self.type_expression(otherwise)?.node;
+
+                Type::from(if then == otherwise { then } else { TypeNode::Any })
+            },
+
+            Identifier(ref n) => match self.symtab.fetch(n) {
+                Some(mut t) => {
+                    self.symtab.mark_read(n, true);
+
+                    // the folded value belongs to the constant, and the annotation to the name, not to whatever they're copied into
+                    t.value = None;
+                    t.annotation = None;
+                    t
+                },
+                None    => return Err(response!(
+                    Wrong(format!("no such variable `{}`{}", n, self.suggest(n))),
+                    self.source.file,
+                    expression.pos
+                ))
+            },
+
+            Call(ref caller, ref args, _) => {
+                if let Some(call) = self.method_call(expression)? {
+                    return self.type_expression(&call)
+                }
+
+                if let Some(call) = self.spread_call(expression)? {
+                    return self.type_expression(&call)
+                }
+
+                match self.keyword_call(expression) {
+                    Ok(Some(call)) => return self.type_expression(&call),
+                    Err((message, pos)) => return Err(response!(Wrong(message), self.source.file, pos)),
+                    Ok(None) => (),
+                }
+
+                if let Some((name, arity, result)) = self.string_intrinsic(caller) {
+                    return self.type_string_intrinsic(name, arity, result, args, &caller.pos)
+                }
+
+                if let Some((name, arity)) = self.numeric_intrinsic(caller) {
+                    return self.type_numeric_intrinsic(name, arity, args, &caller.pos)
+                }
+
+                if self.is_range(caller) {
+                    return self.type_range(args, &caller.pos)
+                }
+
+                if self.is_embed(caller) {
+                    return match self.embed(args, &caller.pos) {
+                        Ok(_) => Ok(Type::from(TypeNode::Str)),
+                        Err((message, pos)) => Err(response!(Wrong(message), self.source.file, pos)),
+                    }
+                }
+
+                if let Some(name) = self.intrinsic(caller) {
+                    return self.type_intrinsic(name, args)
+                }
+
+                if self.strict && self.type_expression(caller)?.node == TypeNode::Any {
+                    return Err(response!(
+                        Wrong("can't call something of type `Any` in strict mode"),
+                        self.source.file,
+                        caller.pos
+                    ))
+                }
+
+                Type::from(TypeNode::Any)
+            },
+
+            // what didn't parse was already reported, it shouldn't set off more errors
+            OptionalIndex(..) | Labeled(..) | Tuple(..) | Error => Type::from(TypeNode::Any),
+
+            // strings slice into strings and lists into lists of the same elements
+            Slice(ref target, ref start, ref end) => {
+                for bound in start.iter().chain(end.iter()) {
+                    match self.type_expression(bound)?.node {
+                        TypeNode::Int | TypeNode::Any => (),
+
+                        t => return Err(response!(
+                            Wrong(format!("a slice needs integer bounds, but got `{:?}`", t)),
+                            self.source.file,
+                            bound.pos
+                        )),
+                    }
+                }
+
+                match self.type_expression(target)?.node {
+                    TypeNode::Str => Type::from(TypeNode::Str),
+                    TypeNode::Array(element) => Type::from(TypeNode::Array(element)),
+                    TypeNode::Any => Type::from(TypeNode::Any),
+
+                    t => return Err(response!(
+                        Wrong(format!("can't slice `{:?}`, only strings and lists", t)),
+                        self.source.file,
+                        target.pos
+                    )),
+                }
+            },
+
+            // a list of ints at runtime
+            Range(ref start, ref end) | RangeInclusive(ref start, ref end) => {
+                for bound in [start, end].iter() {
+                    match self.type_expression(bound)?.node {
+                        TypeNode::Int | TypeNode::Any => (),
+
+                        t => return Err(response!(
+                            Wrong(format!("a range needs integer bounds, but got `{:?}`", t)),
+                            self.source.file,
+                            bound.pos
+                        )),
+                    }
+                }
+
+                Type::from(TypeNode::Array(Box::new(TypeNode::Int)))
+            },
+
+            AnonFunction(_, ref params, _) => Type::from(TypeNode::Func(params.len())),
+
+            // elements of one type make a list of that type, mixed or no elements make one of `Any`
+            Array(ref content) => {
+                let mut element: Option<TypeNode> = None;
+
+                for value in content.iter() {
+                    let t = if let Spread(ref source) = value.node {
+                        match self.type_expression(source)?.node {
+                            TypeNode::Array(spread) => *spread,
+                            _ => TypeNode::Any,
+                        }
+                    } else {
+                        self.type_expression(value)?.node
+                    };
+
+                    element = match element {
+                        Some(known) if known != t => Some(TypeNode::Any),
+                        Some(known) => Some(known),
+                        None => Some(t),
+                    };
+                }
+
+                Type::from(TypeNode::Array(Box::new(element.unwrap_or(TypeNode::Any))))
+            },
+
+            Dict(ref content) => {
+                let mut fields: Vec<(String, TypeNode)> = Vec::new();
+
+                for entry in content.iter() {
+                    // later fields win, spread ones included
+                    let added = match *entry {
+                        DictEntry::Spread(ref source) => match self.type_expression(source)?.node {
+                            TypeNode::Dict(spread) => spread,
+                            _ => return Ok(Type::from(TypeNode::Any)),
+                        },
+
+                        DictEntry::Field(ref key, ref value) => {
+                            let t = self.type_expression(value)?.node;
+
+                            // a nil field has no type to go by, and reads the same as a missing one
+                            if t == TypeNode::Nil {
+                                return Err(response!(
+                                    Wrong(format!("can't put nil in a dict, `{}` has no usable type", key)),
+                                    self.source.file,
+                                    value.pos
+                                ))
+                            }
+
+                            vec!((key.clone(), t))
+                        },
+                    };
+
+                    for (key, t) in added {
+                        fields.retain(|(field, _)| *field != key);
+                        fields.push((key, t))
+                    }
+                }
+
+                Type::from(TypeNode::Dict(fields))
+            },
+
+            _ => Type::from(TypeNode::Nil),
+        };
+
+        Ok(t)
+    }
+
+    fn visit_variable(&mut self, variable: &StatementNode, pos: &Pos) -> Result<(), ()> {
+        use self::ExpressionNode::*;
+
+        if let &StatementNode::Declaration(ref name, ref annotation, ref right) = variable {
+            self.check_name(name, pos)?;
+            self.check_const_redefinition(name, pos)?;
+
+            self.symtab.declare(name, pos.clone());
+
+            let slot = self.hoisted.get(&(name.clone(), self.function_depth)).cloned();
+            let hoisted = slot.is_some();
+
+            if right.is_none() {
+                // `let x: int` is nil until it's assigned, which is when the annotation gets checked
+                let mut t = Type::from(TypeNode::Nil);
+                t.annotation = annotation.clone();
+
+                let binding = slot.unwrap_or_else(|| Binding::local(name, self.depth, self.function_depth));
+
+                t.set_offset(binding.clone());
+                
+                self.assign(name.to_owned(), t);
+                let right_ir = Expr::Literal(Literal::Nil).node(TypeInfo::nil()); // so `x == nil` holds until it's assigned
+
+                self.bind_local(binding, right_ir, hoisted);
+
+            } else {
+                let binding = if let Some(slot) = slot {
+                    slot
+                } else if let Some(ref t) = self.symtab.fetch(name) {
+                    t.meta.clone().unwrap()
+                } else {
+                    Binding::local(name.as_str(), self.depth, self.function_depth)
+                };
+
+                let mut t = match *annotation {
+                    Some(ref expected) => self.visit_with_expected_type(right.as_ref().unwrap(), expected)?,
+                    None => {
+                        self.visit_expression(right.as_ref().unwrap())?;
+                        self.type_expression(right.as_ref().unwrap())?
+                    },
+                };
+
+                if let Some(ref expected) = *annotation {
+                    if !t.node.fits(expected) {
+                        return Err(response!(
+                            Wrong(format!("`{}` is annotated as `{:?}`, but got `{:?}`", name, expected, t.node)),
+                            self.source.file,
+                            right.as_ref().unwrap().pos
+                        ))
+                    }
+
+                    t.node = expected.clone()
+                }
+
+                t.annotation = annotation.clone();
+                t.mode = TypeMode::Regular;
+                t.set_offset(binding.clone());
+
+                self.assign(name.to_owned(), t);
+
+                let right_ir = self.compile_expression(&right.clone().unwrap())?;
+
+                self.bind_local(binding, right_ir, hoisted);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_call(expression: &Expression) -> bool {
+        if let ExpressionNode::Call(..) = expression.node {
+            true
+        } else {
+            false
+        }
+    }
+
+    fn compile_textual(&mut self, expression: &Expression) -> Result<ExprNode, ()> {
+        let ir = self.compile_expression(expression)?;
+
+        match self.type_expression(expression)?.node {
+            TypeNode::Str | TypeNode::Char => Ok(ir),
+
+            _ => {
+                let native = self.builder.var(Binding::global("$stringify"));
+
+                Ok(self.builder.call(native, vec!(ir), None))
+            },
+        }
+    }
+
+    fn is_spread(expression: &Expression) -> bool {
+        if let ExpressionNode::Spread(_) = expression.node {
+            true
+        } else {
+            false
+        }
+    }
+
+    // calls type as `Any` and some expressions still come out as nil, so only what's surely not a list is refused
+    fn check_spread(&mut self, source: &Expression, dict: bool) -> Result<(), ()> {
+        let fits = match self.type_expression(source)?.node {
+            TypeNode::Any => true,
+            TypeNode::Dict(_) | TypeNode::DictOf(_) => dict,
+            TypeNode::Array(_) | TypeNode::Nil => !dict,
+            _ => false,
+        };
+
+        if fits {
+            Ok(())
+        } else {
+            let message = if dict {
+                "can only spread a dict into a dict"
+            } else {
+                "can only spread a list into a list or a call"
+            };
+
+            Err(response!(
+                Wrong(message),
+                self.source.file,
+                source.pos
+            ))
+        }
+    }
+
+    // `[a, ...xs, b]` joins `[a]`, `xs` and `[b]` with `$concat`, dicts do the same with `$merge`, and either
+    // way the result is a fresh collection, even for a lone `[...xs]`
+    fn lower_spread<T>(content: Vec<T>, join: &str, literal: fn(Vec<T>) -> ExpressionNode, pos: &Pos) -> Expression
+    where
+        T: SpreadEntry,
+    {
+        let at = |node: ExpressionNode| Expression::new(node, pos.clone());
+        let joined = |left: Expression, right: Expression| at(ExpressionNode::Call(Rc::new(at(ExpressionNode::Identifier(join.to_string()))), vec!(left, right), Vec::new()));
+
+        let mut result: Option<Expression> = None;
+        let mut run = Vec::new();
+
+        for entry in content {
+            let source = match entry.spread() {
+                Some(source) => source,
+
+                None => {
+                    run.push(entry);
+                    continue
+                },
+            };
+
+            if !run.is_empty() {
+                let part = at(literal(mem::replace(&mut run, Vec::new())));
+
+                result = Some(match result {
+                    Some(result) => joined(result, part),
+                    None => part,
+                })
+            }
+
+            result = Some(joined(result.unwrap_or(at(literal(Vec::new()))), source))
+        }
+
+        if !run.is_empty() {
+            let part = at(literal(run));
+
+            result = Some(match result {
+                Some(result) => joined(result, part),
+                None => part,
+            })
+        }
+
+        result.unwrap_or(at(literal(Vec::new())))
+    }
+
+    // a `fun` next to one of the same name in this scope but of another arity overloads it,
+    // every arity past the first gets its own binding so they don't clobber each other
+    fn overload(&self, name: &String, arity: usize) -> (Type, VarPos) {
+        let mut overloads = match self.symtab.current_frame().get(name) {
+            Some(ref t) if !t.overloads.is_empty() => t.overloads.clone(),
+
+            Some(Type { node: TypeNode::Func(n), meta: Some(ref binding), .. }) if n != arity => vec!((n, binding.clone())),
+
+            _ => Vec::new(),
+        };
+
+        let binding = match overloads.iter().find(|(n, _)| *n == arity) {
+            Some((_, binding)) => binding.clone(),
+
+            None if overloads.is_empty() => Binding::local(name, self.depth, self.function_depth),
+
+            None => {
+                let binding = Binding::local(&format!("{}/{}", name, arity), self.depth, self.function_depth);
+                overloads.push((arity, binding.clone()));
+
+                binding
+            },
+        };
+
+        let mut t = Type::from(TypeNode::Func(arity));
+
+        t.set_offset(binding.clone());
+        t.overloads = overloads;
+
+        (t, binding)
+    }
+
+    fn overloads(&self, callee: &Expression) -> Option<(String, Vec<(usize, VarPos)>)> {
+        if let ExpressionNode::Identifier(ref name) = callee.node {
+            if let Some(t) = self.symtab.fetch(name) {
+                if !t.overloads.is_empty() {
+                    return Some((name.clone(), t.overloads))
+                }
+            }
+        }
+
+        None
+    }
+
+    fn resolve_overload(&self, name: &String, overloads: &Vec<(usize, VarPos)>, arity: usize, pos: &Pos) -> Result<VarPos, ()> {
+        match overloads.iter().find(|(n, _)| *n == arity) {
+            Some((_, binding)) => Ok(binding.clone()),
+
+            None => {
+                let arities = overloads.iter().map(|(n, _)| n.to_string()).collect::<Vec<String>>();
+
+                Err(response!(
+                    Wrong(format!("no overload of `{}` takes {} arguments, only {}", name, arity, arities.join(" or "))),
+                    self.source.file,
+                    pos
+                ))
+            },
+        }
+    }
+
+    // a hoisted local already has its slot, binding it again would push a new one every iteration
+    fn bind_local(&mut self, binding: Binding, value: ExprNode, hoisted: bool) {
+        if hoisted {
+            let var = self.builder.var(binding);
+
+            self.builder.mutate(var, value);
+            self.builder.emit(Expr::Pop.node(TypeInfo::nil()))
+        } else {
+            self.builder.bind(binding, value)
+        }
+    }
+
+    // the vm has no block scopes and a `let` pushes a fresh slot each time it runs, or never if its branch
+    // doesn't, so every local declared in a loop or branch gets its slot up front and the `let` inside only
+    // assigns to it, the name is bound when the `let` is reached and goes out of scope with the block as usual
+    fn hoist_locals(&mut self, bodies: &[&Vec<Statement>], pos: &Pos) -> Vec<(String, usize)> {
+        let mut names = Vec::new();
+
+        for body in bodies {
+            Self::block_locals(body, &mut names)
+        }
+
+        self.hoist(names, pos)
+    }
+
+    fn hoist(&mut self, names: Vec<String>, pos: &Pos) -> Vec<(String, usize)> {
+        let mut hoisted = Vec::new();
+
+        for name in names {
+            let local = (name.clone(), self.function_depth);
+
+            if self.hoisted.contains_key(&local) {
+                continue
+            }
+
+            // its own slot name, the vm finds locals by name and an outer `x` would be found as this one
+            let slot = format!("{}$block-{}-{}", name, (pos.0).0, (pos.1).0);
+            let binding = Binding::local(slot.as_str(), self.depth, self.function_depth);
+
+            let nil = Expr::Literal(Literal::Nil).node(TypeInfo::nil());
+            self.builder.bind(binding.clone(), nil);
+
+            self.hoisted.insert(local.clone(), binding);
+            hoisted.push(local)
+        }
+
+        hoisted
+    }
+
+    // whether a loop body can leave the loop, breaks of nested loops only leave those
+    fn exits(body: &Vec<Statement>) -> bool {
+        use self::StatementNode::*;
+
+        body.iter().any(|statement| match statement.node {
+            Break | BreakLabel(..) | Return(_) => true,
+
+            If(_, ref then, ref else_) => Self::exits(then) || else_.iter().any(|(_, body)| Self::exits(body)),
+
+            Block(ref body) | When(_, ref body) => Self::exits(body),
+
+            Match(..) => Self::exits(&Self::lower_match(statement)),
+
+            // a `return` still gets out from inside a nested loop
+            While(_, ref body) | For(_, _, _, ref body) | WhileLet(_, _, ref body, _) => Self::returns(body),
+
+            _ => false,
+        })
+    }
+
+    fn returns(body: &Vec<Statement>) -> bool {
+        use self::StatementNode::*;
+
+        body.iter().any(|statement| match statement.node {
+            Return(_) => true,
+
+            If(_, ref then, ref else_) => Self::returns(then) || else_.iter().any(|(_, body)| Self::returns(body)),
+
+            Block(ref body) | When(_, ref body) | While(_, ref body) | For(_, _, _, ref body) => Self::returns(body),
+
+            WhileLet(_, _, ref body, ref else_) => Self::returns(body) || else_.as_ref().map_or(false, Self::returns),
+
+            Match(..) => Self::returns(&Self::lower_match(statement)),
+
+            _ => false,
+        })
+    }
+
+    // every local and function a block declares, including the ones its sugar lowers to, but not those inside functions
+    fn block_locals(body: &Vec<Statement>, names: &mut Vec<String>) {
+        use self::StatementNode::*;
+
+        for statement in body.iter() {
+            match statement.node {
+                Declaration(ref name, _, ref right) => {
+                    names.push(name.clone());
+
+                    if let Some(ref right) = *right {
+                        Self::labeled_locals(right, names)
+                    }
+                },
+
+                Expression(ref expression) | Assignment(_, ref expression) | Return(Some(ref expression)) => Self::labeled_locals(expression, names),
+
+                If(_, ref then, ref else_) => {
+                    Self::block_locals(then, names);
+
+                    for (_, body) in else_.iter() {
+                        Self::block_locals(body, names)
+                    }
+                },
+
+                While(_, ref body) | Block(ref body) | When(_, ref body) => Self::block_locals(body, names),
+
+                Match(..) => Self::block_locals(&Self::lower_match(statement), names),
+
+                Function(ref name, ..) => names.push(name.clone()),
+
+                ConstFunction(ref fun) => Self::block_locals(&vec!((**fun).clone()), names),
+
+                Interface(_, ref content) => Self::block_locals(content, names),
+
+                // over-counts a `map` or `filter` that's been rebound, an extra slot does no harm
+                For(..) if !Self::lower_lazy_for(statement).is_empty() => Self::block_locals(&Self::lower_lazy_for(statement), names),
+
+                For(ref name, ref value, _, ref body) => {
+                    names.push(Self::lowered_name("for-list", &statement.pos));
+                    names.push(Self::lowered_name("for-index", &statement.pos));
+                    names.push(name.clone());
+                    names.extend(value.iter().cloned());
+
+                    Self::block_locals(body, names)
+                },
+
+                WhileLet(ref name, _, ref body, ref else_) => {
+                    names.push(name.clone());
+                    names.push(Self::lowered_name("while-ran", &statement.pos));
+
+                    Self::block_locals(body, names);
+
+                    if let Some(ref else_) = *else_ {
+                        Self::block_locals(else_, names)
+                    }
+                },
+
+                _ => (),
+            }
+        }
+    }
+
+    // a labeled block right of a statement binds its result and flag, plus whatever its body declares
+    fn labeled_locals(expression: &Expression, names: &mut Vec<String>) {
+        if let ExpressionNode::Labeled(_, ref body) = expression.node {
+            names.push(Self::lowered_name("block-result", &expression.pos));
+            names.push(Self::lowered_name("block-done", &expression.pos));
+
+            Self::block_locals(body, names)
+        }
+    }
+
+    // locals introduced by lowering sugar, the `$` keeps them out of reach of hugorm code
+    fn lowered_name(what: &str, pos: &Pos) -> String {
+        format!("${}-{}-{}", what, (pos.0).0, (pos.1).0)
+    }
+
+    // constants always bind fresh, so one in a nested scope shadows the outer one instead of clobbering it
+    fn visit_const(&mut self, name: &String, right: &Expression, pos: &Pos) -> Result<(), ()> {
+        self.check_const_redefinition(name, pos)?;
+
+        self.visit_expression(right)?;
+
+        // the vm has no block scopes, a shadow in the same function needs its own slot name
+        let shadows = self.symtab.fetch(name)
+            .and_then(|t| t.meta)
+            .map_or(false, |b| b.function_depth == self.function_depth);
+
+        let slot = if shadows {
+            format!("{}$shadow-{}-{}", name, (pos.0).0, (pos.1).0)
+        } else {
+            name.clone()
+        };
+
+        let binding = Binding::local(slot.as_str(), self.depth, self.function_depth);
+
+        let mut t = self.type_expression(right)?;
+        let value = self.fold_constant(right)?;
+
+        t.mode = TypeMode::Immutable;
+        t.set_offset(binding.clone());
+        t.value = Some(value.clone());
+
+        self.assign(name.to_owned(), t);
+
+        let right_ir = self.compile_expression(&Expression::new(value, right.pos.clone()))?;
+
+        self.builder.bind(binding, right_ir);
+
+        Ok(())
+    }
+
+    // evaluates a constant initializer at compile time, uses of the constant get the literal inlined
+    fn fold_constant(&self, expression: &Expression) -> Result<ExpressionNode, ()> {
+        self.fold(expression, &HashMap::new(), 0).map_err(|(message, pos)| response!(
+            Wrong(message),
+            self.source.file,
+            pos
+        ))
+    }
+
+    // what a constant expression comes to at compile time, as the literal the vm would load for it
+    pub fn eval_const(&self, expression: &Expression) -> Result<Literal, ()> {
+        let literal = match self.fold_constant(expression)? {
+            ExpressionNode::Int(n) => Literal::Number(n as f64),
+            ExpressionNode::Float(n) => Literal::Number(n),
+            ExpressionNode::Str(s) => Literal::String(s),
+            ExpressionNode::Char(c) => Literal::String(c.to_string()),
+            ExpressionNode::Bool(b) => Literal::Boolean(b),
+            ExpressionNode::Nil => Literal::Nil,
+
+            _ => return Err(response!(
+                Wrong("this constant doesn't come to a number, string, bool or nil"),
+                self.source.file,
+                expression.pos
+            )),
+        };
+
+        Ok(literal)
+    }
+
+    // `locals` are the parameters and lets of the const function being run, if any
+    fn fold(&self, expression: &Expression, locals: &HashMap<String, ExpressionNode>, depth: usize) -> Result<ExpressionNode, (String, Pos)> {
+        use self::ExpressionNode::*;
+        use self::Operator::*;
+
+        let value = match expression.node {
+            Int(_) | Float(_) | Str(_) | Char(_) | Bool(_) | Nil => Some(expression.node.clone()),
+
+            Identifier(ref n) if locals.contains_key(n) => locals.get(n).cloned(),
+
+            Identifier(ref n) => match self.symtab.fetch(n) {
+                Some(Type { value: Some(value), .. }) => Some(value),
+
+                Some(_) => return Err((
+                    format!("constant initializer can't use `{}`, which isn't a constant", n),
+                    expression.pos.clone()
+                )),
+
+                None => return Err((
+                    format!("no such variable `{}`{}", n, self.suggest(n)),
+                    expression.pos.clone()
+                )),
+            },
+
+            Call(_, _, ref keywords) if !keywords.is_empty() => match self.keyword_call(expression)? {
+                Some(call) => return self.fold(&call, locals, depth),
+                None => None,
+            },
+
+            Call(ref callee, ref args, _) => match callee.node {
+                Identifier(ref n) if self.const_functions.contains_key(n) => {
+                    let mut values = Vec::new();
+
+                    for arg in args.iter() {
+                        values.push(self.fold(arg, locals, depth)?)
+                    }
+
+                    Some(self.call_const(n, values, depth + 1, &expression.pos)?)
+                },
+
+                Identifier(_) if self.is_embed(callee) => Some(Str(self.embed(args, &callee.pos)?)),
+
+                _ => None,
+            },
+
+            Neg(ref expr) => match self.fold(expr, locals, depth)? {
+                Int(a) => Some(Int(a.checked_neg().ok_or_else(|| Self::overflow(expression))?)),
+                Float(a) => Some(Float(-a)),
+                _ => None,
+            },
+
+            Not(ref expr) => match self.fold(expr, locals, depth)? {
+                Bool(a) => Some(Bool(!a)),
+                _ => None,
+            },
+
+            Conditional(ref cond, ref then, ref otherwise) => match self.fold(cond, locals, depth)? {
+                Bool(true) => Some(self.fold(then, locals, depth)?),
+                Bool(false) => Some(self.fold(otherwise, locals, depth)?),
+                _ => None,
+            },
+
+            Binary(ref left, ref op, ref right) => {
+                let a = self.fold(left, locals, depth)?;
+                let b = self.fold(right, locals, depth)?;
+
+                let checked = |result: Option<i64>| result.map(Int).ok_or_else(|| Self::overflow(expression));
+
+                match (a, op, b) {
+                    (Int(_), Div, Int(0)) | (Int(_), Mod, Int(0)) => return Err((
+                        "division by zero in constant".to_string(),
+                        expression.pos.clone()
+                    )),
+
+                    (Int(a), Add, Int(b)) => Some(checked(a.checked_add(b))?),
+                    (Int(a), Sub, Int(b)) => Some(checked(a.checked_sub(b))?),
+                    (Int(a), Mul, Int(b)) => Some(checked(a.checked_mul(b))?),
+                    (Int(a), Mod, Int(b)) => Some(checked(a.checked_rem(b))?),
+                    (Int(a), WrapAdd, Int(b)) => Some(Int(a.wrapping_add(b))),
+                    (Int(a), WrapSub, Int(b)) => Some(Int(a.wrapping_sub(b))),
+                    (Int(a), WrapMul, Int(b)) => Some(Int(a.wrapping_mul(b))),
+                    // numbers are floats at runtime, so only exact quotients stay ints
+                    (Int(a), Div, Int(b)) => match checked(a.checked_rem(b))? {
+                        Int(0) => Some(checked(a.checked_div(b))?),
+                        _ => Some(Float(a as f64 / b as f64)),
+                    },
+
+                    (Float(a), ref op, Float(b)) => Self::fold_floats(a, op, b),
+
+                    // the type check only lets ints meet floats in comparisons, where an int is the float it is at runtime
+                    (Int(a), ref op, Float(b)) if COMPARISONS.contains(op) => Self::fold_floats(a as f64, op, b),
+                    (Float(a), ref op, Int(b)) if COMPARISONS.contains(op) => Self::fold_floats(a, op, b as f64),
+
+                    (Int(a), Lt, Int(b)) => Some(Bool(a < b)),
+                    (Int(a), Gt, Int(b)) => Some(Bool(a > b)),
+                    (Int(a), LtEq, Int(b)) => Some(Bool(a <= b)),
+                    (Int(a), GtEq, Int(b)) => Some(Bool(a >= b)),
+                    (Int(a), Eq, Int(b)) => Some(Bool(a == b)),
+                    (Int(a), NEq, Int(b)) => Some(Bool(a != b)),
+
+                    (Str(a), Concat, Str(b)) => Some(Str(a + &b)),
+
+                    (Bool(a), And, Bool(b)) => Some(Bool(a && b)),
+                    (Bool(a), Or, Bool(b)) => Some(Bool(a || b)),
+
+                    _ => None,
+                }
+            },
+
+            _ => None,
+        };
+
+        match value {
+            Some(value) => Ok(value),
+            None => Err((
+                "constant initializer can't be evaluated at compile time".to_string(),
+                expression.pos.clone()
+            )),
+        }
+    }
+
+    fn fold_floats(a: f64, op: &Operator, b: f64) -> Option<ExpressionNode> {
+        use self::ExpressionNode::*;
+        use self::Operator::*;
+
+        match op {
+            Add => Some(Float(a + b)),
+            Sub => Some(Float(a - b)),
+            Mul => Some(Float(a * b)),
+            Div => Some(Float(a / b)),
+            Mod => Some(Float(a % b)),
+            Lt => Some(Bool(a < b)),
+            Gt => Some(Bool(a > b)),
+            LtEq => Some(Bool(a <= b)),
+            GtEq => Some(Bool(a >= b)),
+            Eq => Some(Bool(a == b)),
+            NEq => Some(Bool(a != b)),
+            _ => None,
+        }
+    }
+
+    fn overflow(expression: &Expression) -> (String, Pos) {
+        ("integer overflow in constant".to_string(), expression.pos.clone())
+    }
+
+    // runs a `const fun` on folded arguments, a body that never returns gives nil
+    fn call_const(&self, name: &str, args: Vec<ExpressionNode>, depth: usize, pos: &Pos) -> Result<ExpressionNode, (String, Pos)> {
+        if depth > MAX_CONST_DEPTH {
+            return Err((format!("`{}` recurses too deep to run at compile time", name), pos.clone()))
+        }
+
+        let function = self.const_functions[name].clone();
+        let (ref params, ref body) = *function;
+
+        if params.len() != args.len() {
+            return Err((format!("wrong amount of arguments, expected {} but got {}", params.len(), args.len()), pos.clone()))
+        }
+
+        let mut locals = params.iter().cloned().zip(args.into_iter()).collect::<HashMap<_, _>>();
+        let mut steps = 0;
+
+        match self.run_const(body, &mut locals, depth, &mut steps)? {
+            ConstFlow::Return(value) => Ok(value),
+            _ => Ok(ExpressionNode::Nil),
+        }
+    }
+
+    fn run_const(&self, body: &Vec<Statement>, locals: &mut HashMap<String, ExpressionNode>, depth: usize, steps: &mut usize) -> Result<ConstFlow, (String, Pos)> {
+        use self::StatementNode::*;
+
+        for statement in body.iter() {
+            let flow = match statement.node {
+                Expression(ref expression) => {
+                    self.fold(expression, locals, depth)?;
+                    ConstFlow::Next
+                },
+
+                Declaration(ref name, _, ref value) => {
+                    let value = match *value {
+                        Some(ref value) => self.fold(value, locals, depth)?,
+                        None => ExpressionNode::Nil,
+                    };
+
+                    locals.insert(name.clone(), value);
+                    ConstFlow::Next
+                },
+
+                Assignment(ref target, ref value) => match target.node {
+                    ExpressionNode::Identifier(ref name) if locals.contains_key(name) => {
+                        let value = self.fold(value, locals, depth)?;
+
+                        locals.insert(name.clone(), value);
+                        ConstFlow::Next
+                    },
+
+                    _ => return Err(("a const function can only assign its own locals".to_string(), statement.pos.clone())),
+                },
+
+                Return(ref value) => ConstFlow::Return(match *value {
+                    Some(ref value) => self.fold(value, locals, depth)?,
+                    None => ExpressionNode::Nil,
+                }),
+
+                Break => ConstFlow::Break,
+
+                Block(ref body) => self.run_const(body, locals, depth, steps)?,
+
+                If(ref cond, ref then, ref else_) => {
+                    let mut branch = None;
+
+                    if self.fold_condition(cond, locals, depth)? {
+                        branch = Some(then)
+                    } else {
+                        for (cond, body) in else_.iter() {
+                            let taken = match *cond {
+                                Some(ref cond) => self.fold_condition(cond, locals, depth)?,
+                                None => true,
+                            };
+
+                            if taken {
+                                branch = Some(body);
+                                break
+                            }
+                        }
+                    }
+
+                    match branch {
+                        Some(body) => self.run_const(body, locals, depth, steps)?,
+                        None => ConstFlow::Next,
+                    }
+                },
+
+                While(ref cond, ref body) => {
+                    let mut flow = ConstFlow::Next;
+
+                    while self.fold_condition(cond, locals, depth)? {
+                        *steps += 1;
+
+                        if *steps > MAX_CONST_STEPS {
+                            return Err(("this loop runs too long to evaluate at compile time".to_string(), statement.pos.clone()))
+                        }
+
+                        match self.run_const(body, locals, depth, steps)? {
+                            ConstFlow::Next => (),
+                            ConstFlow::Break => break,
+                            ret => {
+                                flow = ret;
+                                break
+                            },
+                        }
+                    }
+
+                    flow
+                },
+
+                _ => return Err(("this can't be evaluated at compile time".to_string(), statement.pos.clone())),
+            };
+
+            if flow != ConstFlow::Next {
+                return Ok(flow)
+            }
+        }
+
+        Ok(ConstFlow::Next)
+    }
+
+    fn fold_condition(&self, cond: &Expression, locals: &HashMap<String, ExpressionNode>, depth: usize) -> Result<bool, (String, Pos)> {
+        match self.fold(cond, locals, depth)? {
+            ExpressionNode::Bool(b) => Ok(b),
+            ExpressionNode::Nil => Ok(false),
+            _ => Ok(true),
+        }
+    }
+
+    // a const function may only use its parameters, its own lets and other constants
+    fn check_const_body(&self, params: &Vec<String>, body: &Vec<Statement>) -> Result<(), (String, Pos)> {
+        let mut known = params.iter().cloned().collect::<HashSet<String>>();
+
+        self.check_const_statements(body, &mut known)
+    }
+
+    fn check_const_statements(&self, body: &Vec<Statement>, known: &mut HashSet<String>) -> Result<(), (String, Pos)> {
+        use self::StatementNode::*;
+
+        for statement in body.iter() {
+            match statement.node {
+                Expression(ref e) | Return(Some(ref e)) => self.check_const_expression(e, known)?,
+
+                Declaration(ref name, _, ref value) => {
+                    if let Some(ref value) = *value {
+                        self.check_const_expression(value, known)?
+                    }
+
+                    known.insert(name.clone());
+                },
+
+                Assignment(ref target, ref value) => {
+                    self.check_const_expression(target, known)?;
+                    self.check_const_expression(value, known)?
+                },
+
+                If(ref cond, ref then, ref else_) => {
+                    self.check_const_expression(cond, known)?;
+                    self.check_const_statements(then, known)?;
+
+                    for (cond, body) in else_.iter() {
+                        if let Some(ref cond) = *cond {
+                            self.check_const_expression(cond, known)?
                         }
 
-                        Concat => {
-                            if [TypeNode::Str, TypeNode::Any].contains(a)  {
-                                match *b {
-                                    TypeNode::Nil => return Err(response!(
-                                        Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
-                                        self.source.file,
-                                        expression.pos
-                                    )),
+                        self.check_const_statements(body, known)?
+                    }
+                },
+
+                While(ref cond, ref body) => {
+                    self.check_const_expression(cond, known)?;
+                    self.check_const_statements(body, known)?
+                },
+
+                Block(ref body) => self.check_const_statements(body, known)?,
+
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_const_expression(&self, expression: &Expression, known: &HashSet<String>) -> Result<(), (String, Pos)> {
+        use self::ExpressionNode::*;
+
+        match expression.node {
+            Identifier(ref n) => {
+                let constant = self.const_functions.contains_key(n)
+                    || self.symtab.fetch(n).map_or(false, |t| t.value.is_some());
+
+                if !known.contains(n) && !constant {
+                    return Err((format!("a const function can't use `{}`, which isn't a constant", n), expression.pos.clone()))
+                }
+
+                Ok(())
+            },
+
+            Call(ref callee, ref args, ref keywords) => {
+                self.check_const_expression(callee, known)?;
+
+                for arg in args.iter().chain(keywords.iter().map(|(_, arg)| arg)) {
+                    self.check_const_expression(arg, known)?
+                }
+
+                Ok(())
+            },
+
+            Binary(ref left, _, ref right) => {
+                self.check_const_expression(left, known)?;
+                self.check_const_expression(right, known)
+            },
+
+            Neg(ref e) | Not(ref e) => self.check_const_expression(e, known),
+
+            Conditional(ref cond, ref then, ref otherwise) => {
+                self.check_const_expression(cond, known)?;
+                self.check_const_expression(then, known)?;
+                self.check_const_expression(otherwise, known)
+            },
+
+            _ => Ok(()),
+        }
+    }
+
+    // `for x in list: body` lowers to the loop below, a dict is walked by its keys
+    //
+    //     let list = $elements(list)
+    //     let i = 0
+    //     while i < $len(list):
+    //         let x = list[i]
+    //         i = i + 1
+    //         body
+    //
+    // and `for (key, value) in dict: body` walks `$entries(dict)` the same way, with
+    // `let key = list[i][0]` and `let value = list[i][1]` in place of `x`
+    fn visit_for(&mut self, name: &String, value: &Option<String>, iterable: &Expression, body: &Vec<Statement>, pos: &Pos) -> Result<(), ()> {
+        use self::ExpressionNode::*;
+
+        if let Call(ref callee, ref args, _) = iterable.node {
+            if let Some(intrinsic) = self.intrinsic(callee) {
+                let lowered = Self::lower_lazy_for(&Statement::new(
+                    StatementNode::For(name.clone(), value.clone(), iterable.clone(), body.clone()),
+                    pos.clone()
+                ));
+
+                if !lowered.is_empty() {
+                    self.visit_intrinsic(intrinsic, args, &callee.pos)?;
+
+                    for statement in lowered.iter() {
+                        self.visit_statement(statement)?
+                    }
+
+                    return Ok(())
+                }
+            }
+        }
+
+        self.visit_expression(iterable)?;
+
+        let list = Self::lowered_name("for-list", pos);
+        let index = Self::lowered_name("for-index", pos);
+
+        let at = |node: ExpressionNode| Expression::new(node, pos.clone());
+        let id = |name: &str| at(Identifier(name.to_string()));
+        let statement = |node: StatementNode| Statement::new(node, pos.clone());
+        let element = || at(Binary(Rc::new(id(&list)), Operator::Index, Rc::new(id(&index))));
+
+        let mut looped = match *value {
+            Some(ref value) => {
+                let half = |n: i64| at(Binary(Rc::new(element()), Operator::Index, Rc::new(at(Int(n)))));
+
+                vec!(
+                    statement(StatementNode::Declaration(name.clone(), None, Some(half(0)))),
+                    statement(StatementNode::Declaration(value.clone(), None, Some(half(1)))),
+                )
+            },
+
+            None => vec!(statement(StatementNode::Declaration(name.clone(), None, Some(element())))),
+        };
+
+        looped.push(statement(StatementNode::Assignment(
+            id(&index),
+            at(Binary(Rc::new(id(&index)), Operator::Add, Rc::new(at(Int(1)))))
+        )));
+
+        looped.extend(body.iter().cloned());
+
+        let length = at(Call(Rc::new(id("$len")), vec!(id(&list)), Vec::new()));
+
+        let walked = if value.is_some() { "$entries" } else { "$elements" };
+        let elements = at(Call(Rc::new(id(walked)), vec!(iterable.clone()), Vec::new()));
+
+        self.visit_statement(&statement(StatementNode::Declaration(list.clone(), None, Some(elements))))?;
+        self.visit_statement(&statement(StatementNode::Declaration(index.clone(), None, Some(at(Int(0))))))?;
+        self.visit_statement(&statement(StatementNode::While(at(Binary(Rc::new(id(&index)), Operator::Lt, Rc::new(length))), looped)))
+    }
+
+    // `for x in map(xs, f): body` lowers to
+    //
+    //     let $f = f
+    //     for $element in xs:
+    //         let x = $f($element)
+    //         body
+    //
+    // and `filter` wraps `let x = $element` and the body in `if $f($element):`, so `f` runs as the loop
+    // gets to each element and a `break` stops it early, `xs` is bound first when `f` could have effects,
+    // to keep the order they're written in, and to nothing when it isn't a `for` over either
+    fn lower_lazy_for(statement: &Statement) -> Vec<Statement> {
+        use self::ExpressionNode::*;
+
+        let (name, iterable, body) = match statement.node {
+            StatementNode::For(ref name, None, ref iterable, ref body) => (name, iterable, body),
+            _ => return Vec::new(),
+        };
+
+        let (callee, args) = match iterable.node {
+            Call(ref callee, ref args, ref keywords) if keywords.is_empty() && args.len() == 2 => (callee, args),
+            _ => return Vec::new(),
+        };
+
+        let mapping = match callee.node {
+            Identifier(ref n) if n == "map" => true,
+            Identifier(ref n) if n == "filter" => false,
+            _ => return Vec::new(),
+        };
+
+        let for_pos = statement.pos.clone();
+        let pos = &callee.pos;
+
+        let f = Self::lowered_name("lazy-f", pos);
+        let element = Self::lowered_name("lazy-element", pos);
+
+        let at = |node: ExpressionNode| Expression::new(node, pos.clone());
+        let id = |name: &str| at(Identifier(name.to_string()));
+        let statement = |node: StatementNode| Statement::new(node, pos.clone());
+        let declare = |name: &str, value: Expression| statement(StatementNode::Declaration(name.to_string(), None, Some(value)));
+
+        let mut lowered = Vec::new();
+
+        let pure = Self::is_pure(&args[1]) || if let AnonFunction(..) = args[1].node { true } else { false };
+
+        let source = if pure {
+            args[0].clone()
+        } else {
+            let source = Self::lowered_name("lazy-source", pos);
+
+            lowered.push(declare(&source, args[0].clone()));
+
+            id(&source)
+        };
+
+        lowered.push(declare(&f, args[1].clone()));
+
+        let applied = at(Call(Rc::new(id(&f)), vec!(id(&element)), Vec::new()));
+
+        let looped = if mapping {
+            let mut looped = vec!(declare(name, applied));
+            looped.extend(body.iter().cloned());
+
+            looped
+        } else {
+            let mut kept = vec!(declare(name, id(&element)));
+            kept.extend(body.iter().cloned());
+
+            vec!(statement(StatementNode::If(applied, kept, Vec::new())))
+        };
+
+        lowered.push(Statement::new(StatementNode::For(element, None, source, looped), for_pos));
+
+        lowered
+    }
+
+    fn visit_check(&mut self, cond: &Expression, pos: &Pos) -> Result<(), ()> {
+        self.visit_expression(cond)?;
+
+        let t = self.type_expression(cond)?.node;
+
+        if ![TypeNode::Bool, TypeNode::Any].contains(&t) {
+            return Err(response!(
+                Wrong(format!("a `check` needs a `Bool` condition, but got `{:?}`", t)),
+                self.source.file,
+                cond.pos
+            ))
+        }
+
+        if self.release {
+            return Ok(())
+        }
+
+        let Pos((line, ref text), _) = *pos;
+
+        let at = |node: ExpressionNode| Expression::new(node, pos.clone());
+        let message = at(ExpressionNode::Str(format!("check failed on line {}: {}", line, text.trim())));
+
+        let call = at(ExpressionNode::Call(Rc::new(at(ExpressionNode::Identifier("$check".to_string()))), vec!(cond.clone(), message), Vec::new()));
+
+        let ir = self.compile_expression(&call)?;
+
+        self.builder.emit(ir);
+        self.builder.emit(Expr::Pop.node(TypeInfo::nil()));
+
+        Ok(())
+    }
+
+    fn visit_match(&mut self, statement: &Statement, scrutinee: &Expression, arms: &Vec<(Expression, Vec<Statement>)>) -> Result<(), ()> {
+        let t = self.type_expression(scrutinee)?.node;
+
+        for (pattern, _) in arms.iter() {
+            let pattern_t = self.type_expression(pattern)?.node;
+
+            let numbers = [TypeNode::Int, TypeNode::Float];
+            let comparable = t == TypeNode::Any || pattern_t == t || numbers.contains(&t) && numbers.contains(&pattern_t);
+
+            if !comparable {
+                return Err(response!(
+                    Wrong(format!("can't match `{:?}` against a `{:?}` case", t, pattern_t)),
+                    self.source.file,
+                    pattern.pos
+                ))
+            }
+        }
+
+        for lowered in Self::lower_match(statement).iter() {
+            self.visit_statement(lowered)?
+        }
+
+        Ok(())
+    }
+
+    // `match x: case a: ... case b: ... else: ...` lowers to
+    //
+    //     let $value = x
+    //     if $equals($value, a): ...
+    //     elif $equals($value, b): ...
+    //     else: ...
+    //
+    // and to nothing for any other statement
+    fn lower_match(statement: &Statement) -> Vec<Statement> {
+        use self::ExpressionNode::*;
+
+        let (scrutinee, arms, else_) = match statement.node {
+            StatementNode::Match(ref scrutinee, ref arms, ref else_) => (scrutinee, arms, else_),
+            _ => return Vec::new(),
+        };
+
+        let pos = &statement.pos;
+        let value = Self::lowered_name("match-value", pos);
+
+        let at = |node: ExpressionNode| Expression::new(node, pos.clone());
+        let is = |pattern: &Expression| at(Call(Rc::new(at(Identifier("$equals".to_string()))), vec!(at(Identifier(value.clone())), pattern.clone()), Vec::new()));
+
+        let mut rest = arms[1 ..].iter()
+            .map(|(pattern, body)| (Some(is(pattern)), body.clone()))
+            .collect::<Vec<(Option<Expression>, Vec<Statement>)>>();
+
+        if let Some(ref else_) = *else_ {
+            rest.push((None, else_.clone()))
+        }
+
+        vec!(
+            Statement::new(StatementNode::Declaration(value.clone(), None, Some(scrutinee.clone())), pos.clone()),
+            Statement::new(StatementNode::If(is(&arms[0].0), arms[0].1.clone(), rest), pos.clone()),
+        )
+    }
+
+    // the vm has no coroutines, so yielding is eager: a function that yields runs to the end, collecting
+    // everything it yields into a list, and returns that, which is why a yield in an endless loop is an error
+    fn lower_generator(&self, body: &Vec<Statement>) -> Result<Vec<Statement>, ()> {
+        if !Self::yields(body) {
+            return Ok(body.clone())
+        }
+
+        let pos = body[0].pos.clone();
+        let yielded = Expression::new(ExpressionNode::Identifier("$yielded".to_string()), pos.clone());
+
+        let mut lowered = vec!(
+            Statement::new(StatementNode::Declaration("$yielded".to_string(), None, Some(Expression::new(ExpressionNode::Array(Vec::new()), pos.clone()))), pos.clone())
+        );
+
+        lowered.extend(self.lower_yields(body, &yielded)?);
+        lowered.push(Statement::new(StatementNode::Return(Some(yielded)), pos));
+
+        Ok(lowered)
+    }
+
+    fn lower_yields(&self, body: &Vec<Statement>, yielded: &Expression) -> Result<Vec<Statement>, ()> {
+        let mut lowered = Vec::new();
+
+        for statement in body.iter() {
+            let pos = statement.pos.clone();
+
+            let node = match statement.node {
+                StatementNode::Yield(ref value) => {
+                    let push = Expression::new(ExpressionNode::Identifier("$push".to_string()), pos.clone());
+
+                    StatementNode::Expression(Expression::new(ExpressionNode::Call(Rc::new(push), vec!(yielded.clone(), value.clone()), Vec::new()), pos.clone()))
+                },
+
+                StatementNode::Return(Some(_)) => return Err(response!(
+                    Wrong("can't return a value from a function that yields"),
+                    self.source.file,
+                    pos
+                )),
+
+                StatementNode::Return(None) => StatementNode::Return(Some(yielded.clone())),
+
+                StatementNode::If(ref cond, ref then, ref else_) => {
+                    let mut lowered_else = Vec::new();
+
+                    for (cond, body) in else_.iter() {
+                        lowered_else.push((cond.clone(), self.lower_yields(body, yielded)?))
+                    }
+
+                    StatementNode::If(cond.clone(), self.lower_yields(then, yielded)?, lowered_else)
+                },
+
+                StatementNode::While(ref cond, ref body) if cond.node == ExpressionNode::Bool(true) && Self::yields(body) && !Self::exits(body) => return Err(response!(
+                    Wrong("this loop never ends, and a function that yields only gives back what it yielded once it returns"),
+                    self.source.file,
+                    pos
+                )),
+
+                StatementNode::While(ref cond, ref body) => StatementNode::While(cond.clone(), self.lower_yields(body, yielded)?),
+                StatementNode::For(ref name, ref value, ref iterable, ref body) => StatementNode::For(name.clone(), value.clone(), iterable.clone(), self.lower_yields(body, yielded)?),
+                StatementNode::Block(ref body) => StatementNode::Block(self.lower_yields(body, yielded)?),
+                StatementNode::When(ref flag, ref body) => StatementNode::When(flag.clone(), self.lower_yields(body, yielded)?),
+                StatementNode::Match(..) => StatementNode::Block(self.lower_yields(&Self::lower_match(statement), yielded)?),
+
+                StatementNode::WhileLet(ref name, ref right, ref body, ref else_) => StatementNode::WhileLet(
+                    name.clone(),
+                    right.clone(),
+                    self.lower_yields(body, yielded)?,
+                    match *else_ {
+                        Some(ref else_) => Some(self.lower_yields(else_, yielded)?),
+                        None => None,
+                    }
+                ),
+
+                ref node => node.clone(),
+            };
+
+            lowered.push(Statement::new(node, pos))
+        }
+
+        Ok(lowered)
+    }
 
-                                    _ => Type::from(TypeNode::Str),
-                                }
-                            } else {
-                                return Err(response!(
-                                    Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
-                                    self.source.file,
-                                    expression.pos
-                                ));
-                            }
-                        }
+    // whether a function body yields, nested functions yield for themselves
+    fn yields(body: &Vec<Statement>) -> bool {
+        use self::StatementNode::*;
 
-                        Eq | NEq => {
-                            if [a, b].contains(&&TypeNode::Nil) {
-                                return Err(response!(
-                                    Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
-                                    self.source.file,
-                                    expression.pos
-                                ));
-                            }
+        body.iter().any(|statement| match statement.node {
+            Yield(_) => true,
 
-                            Type::from(TypeNode::Bool)
-                        },
+            If(_, ref then, ref else_) => Self::yields(then) || else_.iter().any(|(_, body)| Self::yields(body)),
 
-                        Lt | Gt | LtEq | GtEq => {
-                            let ts = [TypeNode::Any, TypeNode::Float, TypeNode::Int];
-                            if ts.contains(a) && ts.contains(b) {
-                                Type::from(TypeNode::Bool)
-                            } else {
-                                return Err(response!(
-                                    Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
-                                    self.source.file,
-                                    expression.pos
-                                ));
-                            }
-                        }
+            While(_, ref body) | For(_, _, _, ref body) | Block(ref body) | When(_, ref body) => Self::yields(body),
 
-                        _ => {
-                            return Err(response!(
-                                Wrong(format!("can't perform operation `{:?} {} {:?}`", a, op, b)),
-                                self.source.file,
-                                expression.pos
-                            ))
-                        }
-                    },
-                }
-            },
+            WhileLet(_, _, ref body, ref else_) => Self::yields(body) || else_.as_ref().map_or(false, Self::yields),
 
-            Neg(ref expr) => self.type_expression(expr)?,
-            Not(_) => Type::from(TypeNode::Bool),
+            Match(..) => Self::yields(&Self::lower_match(statement)),
 
-            Identifier(ref n) => match self.symtab.fetch(n) {
-                Some(t) => t,
-                None    => return Err(response!(
-                    Wrong(format!("no such variable `{}`", n)),
-                    self.source.file,
-                    expression.pos
-                ))
-            },
+            _ => false,
+        })
+    }
 
-            Call(ref caller, ref args) => Type::from(TypeNode::Any),
+    // `while let x = f(): body else: alt` lowers to
+    //
+    //     let x = f()
+    //     let ran = false
+    //     while x:
+    //         ran = true
+    //         body
+    //         x = f()
+    //     if not ran: alt
+    //
+    // so a `break` still counts as having run, `x` and `ran` are gone after the loop
+    fn visit_while_let(&mut self, name: &String, right: &Expression, body: &Vec<Statement>, else_: &Option<Vec<Statement>>, pos: &Pos) -> Result<(), ()> {
+        let ran = Self::lowered_name("while-ran", pos);
+
+        // their own slots, so an outer `x` is still itself after the loop
+        let hoisted = self.hoist(vec!(name.clone(), ran.clone()), pos);
+
+        self.push_scope();
+        self.depth -= 1; // the slots stay at this depth, like an if's
+
+        let lowered = self.lower_while_let(name, &ran, right, body, else_, pos);
+
+        self.depth += 1;
+        self.pop_scope();
+
+        for local in hoisted {
+            self.hoisted.remove(&local);
+        }
 
-            _ => Type::from(TypeNode::Nil),
-        };
+        lowered
+    }
 
-        Ok(t)
+    fn lower_while_let(&mut self, name: &String, ran: &String, right: &Expression, body: &Vec<Statement>, else_: &Option<Vec<Statement>>, pos: &Pos) -> Result<(), ()> {
+
+        let identifier = |name: &str| Expression::new(ExpressionNode::Identifier(name.to_string()), pos.clone());
+        let statement = |node: StatementNode| Statement::new(node, pos.clone());
+
+        let mut looped = vec!(
+            statement(StatementNode::Assignment(identifier(ran), Expression::new(ExpressionNode::Bool(true), pos.clone())))
+        );
+
+        looped.extend(body.iter().cloned());
+        looped.push(statement(StatementNode::Assignment(identifier(name), right.clone())));
+
+        self.visit_statement(&statement(StatementNode::Declaration(name.clone(), None, Some(right.clone()))))?;
+        self.visit_statement(&statement(StatementNode::Declaration(ran.clone(), None, Some(Expression::new(ExpressionNode::Bool(false), pos.clone())))))?;
+        // only nil stops it, a bound `false` or string still goes around
+        let equals = Expression::new(ExpressionNode::Call(Rc::new(identifier("$equals")), vec!(identifier(name), Expression::new(ExpressionNode::Nil, pos.clone())), Vec::new()), pos.clone());
+        let bound = Expression::new(ExpressionNode::Not(Rc::new(equals)), pos.clone());
+
+        self.visit_statement(&statement(StatementNode::While(bound, looped)))?;
+
+        if let Some(ref else_) = *else_ {
+            let never_ran = Expression::new(ExpressionNode::Not(Rc::new(identifier(ran))), pos.clone());
+
+            self.visit_statement(&statement(StatementNode::If(never_ran, else_.clone(), Vec::new())))?;
+        }
+
+        Ok(())
     }
 
-    fn visit_variable(&mut self, variable: &StatementNode, pos: &Pos) -> Result<(), ()> {
-        use self::ExpressionNode::*;
+    // the block runs as a loop that goes around once, so `break @name value` is a plain break after
+    // storing the value, and the loops in between see `done` and break as well
+    // the loop a `while` lowers to, and the one a labeled block runs its body in once
+    fn visit_loop(&mut self, cond: ExprNode, body: &Vec<Statement>, inside: Inside, pos: &Pos) -> Result<(), ()> {
+        let hoisted = self.hoist_locals(&[body], pos);
 
-        if let &StatementNode::Declaration(ref name, ref right) = variable {
-            if name.as_str().chars().last().unwrap() == '-' {
-                response!(
-                    Weird("kebab-case at identifier end is not cool"),
-                    self.source.file,
-                    pos
-                )
-            }
+        // a `break @name` in here only leaves this loop, the labeled blocks around it take over from there
+        let checks = self.labels.iter().filter(|label| label.looping).map(|label| label.done.clone()).collect::<Vec<String>>();
+        let looping = self.labels.iter().map(|label| label.looping).collect::<Vec<bool>>();
 
-            if right.is_none() {
-                let mut t = Type::from(TypeNode::Nil);
+        for label in self.labels.iter_mut() {
+            label.looping = true
+        }
 
-                t.set_offset(Binding::local(name.as_str(), self.depth, self.function_depth));
-                
-                self.assign(name.to_owned(), t);
-                let right_ir = self.builder.number(0.0);
-                let binding = Binding::local(name, self.depth, self.function_depth);
+        let old_current = self.builder.clone();
+        self.builder = IrBuilder::new();
 
-                self.builder.bind(binding, right_ir);
+        self.push_scope();
+        self.depth -= 1; // brother bruh
 
-            } else {
-                let binding = if let Some(ref t) = self.symtab.fetch(name) {
-                    t.meta.clone().unwrap()
-                } else {
-                    Binding::local(name.as_str(), self.depth, self.function_depth)
-                };
+        // a labeled block runs its body once
+        let once = inside == Inside::Block;
 
-                let mut t = self.type_expression(right.as_ref().unwrap())?;
+        self.inside.push(inside);
 
-                t.set_offset(binding.clone());
+        for statement in body.iter() {
+            self.visit_statement(statement)?;
+        }
 
-                self.assign(name.to_owned(), t);
+        if once {
+            self.builder.break_()
+        }
 
-                let right_ir = self.compile_expression(&right.clone().unwrap())?;
+        self.inside.pop();
+
+        for (label, looping) in self.labels.iter_mut().zip(looping) {
+            label.looping = looping
+        }
+
+        self.depth += 1; // hehe
+        self.pop_scope();
+
+
+        let body = Expr::Block(self.builder.build()).node(TypeInfo::nil());
+
+        self.builder = old_current;
+
+        self.builder.emit(
+            Expr::While(cond, body).node(TypeInfo::nil())
+        );
+
+        for done in checks {
+            self.break_if_done(&done, pos)?
+        }
+
+        for local in hoisted {
+            self.hoisted.remove(&local);
+        }
+
+        Ok(())
+    }
+
+    fn visit_labeled(&mut self, name: &String, body: &Vec<Statement>, pos: &Pos) -> Result<ExprNode, ()> {
+        let result = Self::lowered_name("block-result", pos);
+        let done = Self::lowered_name("block-done", pos);
+        let broke = Self::lowered_name("block-broke", pos);
+
+        let identifier = |name: &str| Expression::new(ExpressionNode::Identifier(name.to_string()), pos.clone());
+        let statement = |node: StatementNode| Statement::new(node, pos.clone());
+
+        self.visit_statement(&statement(StatementNode::Declaration(result.clone(), None, Some(Expression::new(ExpressionNode::Nil, pos.clone())))))?;
+        self.visit_statement(&statement(StatementNode::Declaration(done.clone(), None, Some(Expression::new(ExpressionNode::Bool(false), pos.clone())))))?;
+        self.visit_statement(&statement(StatementNode::Declaration(broke.clone(), None, Some(Expression::new(ExpressionNode::Bool(false), pos.clone())))))?;
+
+        self.labels.push(Label { name: name.clone(), result: result.clone(), done, broke: broke.clone(), rerouted: false, looping: false });
+
+        let always = self.builder.bool(true);
+        let visited = self.visit_loop(always, body, Inside::Block, pos);
+
+        let label = self.labels.pop().unwrap();
+        visited?;
+
+        // a plain `break` in the block was meant for the loop around it
+        if label.rerouted {
+            self.visit_statement(&statement(StatementNode::If(identifier(&broke), vec!(statement(StatementNode::Break)), Vec::new())))?
+        }
+
+        self.compile_expression(&identifier(&result))
+    }
+
+    // the labeled blocks between a `break` and its loop run in loops of their own,
+    // so it leaves the innermost one, which breaks again once it's done
+    fn reroute_break(&mut self, pos: &Pos) -> Result<(), ()> {
+        let label = self.labels.last_mut().unwrap();
+        label.rerouted = true;
+
+        let broke = Expression::new(ExpressionNode::Identifier(label.broke.clone()), pos.clone());
+
+        self.visit_statement(&Statement::new(StatementNode::Assignment(broke, Expression::new(ExpressionNode::Bool(true), pos.clone())), pos.clone()))
+    }
+
+    fn visit_break_label(&mut self, name: &String, value: &Option<Expression>, pos: &Pos) -> Result<(), ()> {
+        let label = match self.labels.iter().rev().find(|label| label.name == *name) {
+            Some(label) => label.clone(),
+
+            None => return Err(response!(
+                Wrong(format!("there's no block labeled `@{}` to break out of here", name)),
+                self.source.file,
+                pos
+            )),
+        };
+
+        let identifier = |name: &str| Expression::new(ExpressionNode::Identifier(name.to_string()), pos.clone());
+        let statement = |node: StatementNode| Statement::new(node, pos.clone());
+
+        let value = value.clone().unwrap_or(Expression::new(ExpressionNode::Nil, pos.clone()));
+
+        self.visit_statement(&statement(StatementNode::Assignment(identifier(&label.result), value)))?;
+        self.visit_statement(&statement(StatementNode::Assignment(identifier(&label.done), Expression::new(ExpressionNode::Bool(true), pos.clone()))))?;
+
+        self.builder.break_();
+
+        Ok(())
+    }
+
+    // leaves whatever loop is around, a labeled block's own included, so it isn't a `break` statement
+    fn break_if_done(&mut self, done: &str, pos: &Pos) -> Result<(), ()> {
+        let done = self.compile_expression(&Expression::new(ExpressionNode::Identifier(done.to_string()), pos.clone()))?;
+        let leave = self.builder.if_(done, |builder| builder.break_(), None);
+
+        self.builder.emit(leave);
+
+        Ok(())
+    }
+
+    // for every name the program binds, lets, `fun`s, params and assignment targets
+    fn check_name(&mut self, name: &str, pos: &Pos) -> Result<(), ()> {
+        // the lexer never starts a name with `-`, but a host building its own ast could
+        if name.starts_with('-') {
+            return Err(response!(
+                Wrong(format!("`{}` can't start with `-`, that's a negation", name)),
+                self.source.file,
+                pos
+            ))
+        }
+
+        if name.ends_with('-') {
+            self.emit_warning("kebab-case at identifier end is not cool", pos)
+        } else if name.contains('-') && !name.starts_with('$') {
+            // `foo-bar` is one name, but it reads like `foo - bar`, the `$` ones come from lowering
+            self.emit_warning(format!("kebab-case `{}` looks like a subtraction, `{}` doesn't", name, name.replace('-', "_")), pos)
+        }
+
+        Ok(())
+    }
 
-                self.builder.bind(binding, right_ir);
+    fn check_const_redefinition(&self, name: &String, pos: &Pos) -> Result<(), ()> {
+        if let Some(t) = self.symtab.current_frame().get(name) {
+            if t.mode == TypeMode::Immutable {
+                return Err(response!(
+                    Wrong(format!("constant `{}` is already defined in this scope", name)),
+                    self.source.file,
+                    pos
+                ))
             }
         }
 
         Ok(())
     }
 
+    // every assignment form evaluates its target left to right and the assigned value last,
+    // `a[i()] = f()` calls `i` before `f`
     fn visit_ass(&mut self, ass: &StatementNode, pos: &Pos) -> Result<(), ()> {
         use self::ExpressionNode::*;
 
-        if let &StatementNode::Assignment(ref name, ref right) = ass {  
-            match name.node {          
+        if let &StatementNode::Assignment(ref name, ref right) = ass {
+            self.visit_expression(right)?;
+
+            let target = &name.pos;
+
+            if let Identifier(ref name) = name.node {
+                self.check_name(name, target)?
+            }
+
+            match name.node {
                 Identifier(ref name) => if let Some(left_t) = self.symtab.fetch(name) {
+                        if left_t.mode == TypeMode::Immutable {
+                            // constants have a folded value or run at compile time, `const` params have neither
+                            let message = if left_t.value.is_some() || self.const_functions.contains_key(name) {
+                                format!("can't reassign constant `{}`", name)
+                            } else {
+                                format!("can't mutate immutable `{}`", name)
+                            };
+
+                            return Err(response!(
+                                Wrong(message),
+                                self.source.file,
+                                target
+                            ))
+                        }
+
                         let binding = left_t.meta.unwrap().clone();
-        
+
+                        // the value's type, but the mode stays the name's, a constant on the right doesn't make it one
                         let mut t = self.type_expression(&right)?;
+
+                        if let Some(ref expected) = left_t.annotation {
+                            if !t.node.fits(expected) {
+                                return Err(response!(
+                                    Wrong(format!("`{}` is annotated as `{:?}`, but got `{:?}`", name, expected, t.node)),
+                                    self.source.file,
+                                    right.pos
+                                ))
+                            }
+
+                            t.node = expected.clone()
+                        }
+
+                        t.mode = left_t.mode;
+                        t.annotation = left_t.annotation;
                         t.set_offset(binding);
-        
+
                         self.assign(name.to_owned(), t)
                     } else {
                         return Err(response!(
-                            Wrong(format!("can't assign non-existent `{}`", name)),
+                            Wrong(format!("can't assign non-existent `{}`{}", name, self.suggest(name))),
                             self.source.file,
                             pos
                         ))
                     },
 
                 Binary(ref left, ref op, ref index) if *op == Operator::Index => {
-                    let left_ir = self.compile_expression(left)?;
-                    let index_ir = self.compile_expression(index)?;
+                    match (&left.node, &index.node) {
+                        (Identifier(ref name), Str(ref key)) => self.retype_field(name, key, right)?,
+                        (Identifier(ref name), _) => self.retype_element(name, right)?,
+                        _ => (),
+                    }
+
+                    let mut left_ir = self.compile_expression(left)?;
+                    let mut index_ir = self.compile_expression(index)?;
+
+                    // the vm evaluates the value before the target, so targets with side effects are bound first
+                    if !Self::is_pure(left) || !Self::is_pure(index) {
+                        let Pos((line, _), (col, _)) = pos;
+
+                        let list = Binding::local(&format!("$set-list-{}-{}", line, col), self.depth, self.function_depth);
+                        let key = Binding::local(&format!("$set-index-{}-{}", line, col), self.depth, self.function_depth);
+
+                        self.builder.bind(list.clone(), left_ir);
+                        self.builder.bind(key.clone(), index_ir);
+
+                        left_ir = self.builder.var(list);
+                        index_ir = self.builder.var(key);
+                    }
+
                     let right_ir = self.compile_expression(right)?;
 
                     let set = self.builder.set_element(left_ir, index_ir, right_ir);
@@ -895,17 +4253,81 @@ impl<'a> Visitor<'a> {
                 _ => (),
             }
 
-            self.visit_expression(right)?;
+            // the target compiles like any other name, but writing to it isn't reading it
+            let was_read = match name.node {
+                Identifier(ref n) => self.symtab.mark_read(n, true),
+                _ => true,
+            };
 
             let left_ir = self.compile_expression(name)?;
+
+            if let Identifier(ref n) = name.node {
+                self.symtab.mark_read(n, was_read);
+            }
+
             let right_ir = self.compile_expression(right)?;
 
-            self.builder.mutate(left_ir, right_ir)
+            self.builder.mutate(left_ir, right_ir);
+
+            // the vm leaves the assigned value on the stack, which would shift every later local
+            self.builder.emit(Expr::Pop.node(TypeInfo::nil()))
+        }
+
+        Ok(())
+    }
+
+    fn retype_field(&mut self, name: &String, key: &String, value: &Expression) -> Result<(), ()> {
+        if let Some(mut t) = self.symtab.fetch(name) {
+            if let TypeNode::Dict(ref mut fields) = t.node {
+                let value_t = self.type_expression(value)?.node;
+
+                if let Some(field) = fields.iter_mut().find(|(field, _)| field == key) {
+                    field.1 = value_t
+                } else {
+                    fields.push((key.clone(), value_t))
+                }
+            } else {
+                return Ok(())
+            }
+
+            self.symtab.retype(name, t)
+        }
+
+        Ok(())
+    }
+
+    // a list that gets an element of another type holds `Any` from then on
+    fn retype_element(&mut self, name: &String, value: &Expression) -> Result<(), ()> {
+        if let Some(mut t) = self.symtab.fetch(name) {
+            if let TypeNode::Array(ref element) = t.node {
+                if **element == self.type_expression(value)?.node {
+                    return Ok(())
+                }
+            } else {
+                return Ok(())
+            }
+
+            t.node = TypeNode::Array(Box::new(TypeNode::Any));
+
+            self.symtab.retype(name, t)
         }
 
         Ok(())
     }
 
+    fn is_pure(expression: &Expression) -> bool {
+        use self::ExpressionNode::*;
+
+        match expression.node {
+            Nil | Int(_) | Float(_) | Str(_) | Char(_) | Bool(_) | Identifier(_) => true,
+
+            Binary(ref left, Operator::Index, ref key) | OptionalIndex(ref left, ref key) =>
+                Self::is_pure(left) && Self::is_pure(key),
+
+            _ => false,
+        }
+    }
+
     fn assign_str(&mut self, name: &str, t: Type) {
         self.symtab.assign_str(name, t)
     }
@@ -921,8 +4343,85 @@ impl<'a> Visitor<'a> {
     }
 
     fn pop_scope(&mut self) {
+        // `_` marks a name as meant to go unused, `$` one the compiler made up
+        for (name, pos) in self.symtab.unread() {
+            if !name.starts_with('_') && !name.starts_with('$') {
+                self.emit_warning(format!("unused variable `{}`", name), &pos)
+            }
+        }
+
         self.symtab.pop();
 
         self.depth -= 1
     }
-}
\ No newline at end of file
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + if ca == *cb { 0 } else { 1 };
+            previous = row[j + 1];
+
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+// an element of an array or dict literal, which may be `...source`
+trait SpreadEntry {
+    fn spread(&self) -> Option<Expression>;
+}
+
+impl SpreadEntry for Expression {
+    fn spread(&self) -> Option<Expression> {
+        match self.node {
+            ExpressionNode::Spread(ref source) => Some((**source).clone()),
+            _ => None,
+        }
+    }
+}
+
+impl SpreadEntry for DictEntry {
+    fn spread(&self) -> Option<Expression> {
+        match *self {
+            DictEntry::Spread(ref source) => Some(source.clone()),
+            DictEntry::Field(..) => None,
+        }
+    }
+}
+
+fn count_nodes(node: &ExprNode) -> usize {
+    let children = match *node.inner() {
+        Expr::Bind(_, ref value) | Expr::BindGlobal(_, ref value) => count_nodes(value),
+
+        Expr::Mutate(ref left, ref right) | Expr::Binary(ref left, _, ref right) | Expr::While(ref left, ref right) => count_nodes(left) + count_nodes(right),
+
+        Expr::Call(ref call) => count_nodes(&call.callee) + call.args.iter().map(count_nodes).sum::<usize>(),
+
+        Expr::Function(ref function) | Expr::AnonFunction(ref function) => function.body.borrow().inner.iter().map(count_nodes).sum(),
+
+        Expr::Unary(_, ref value) | Expr::Not(ref value) | Expr::Neg(ref value) => count_nodes(value),
+
+        Expr::Return(ref value) => value.as_ref().map_or(0, count_nodes),
+
+        Expr::If(ref cond, ref then, ref else_) => count_nodes(cond) + count_nodes(then) + else_.as_ref().map_or(0, count_nodes),
+
+        Expr::List(ref content) | Expr::Block(ref content) => content.iter().map(count_nodes).sum(),
+
+        Expr::Dict(ref keys, ref values) => keys.iter().chain(values.iter()).map(count_nodes).sum(),
+
+        Expr::SetElement(ref list, ref index, ref value) => count_nodes(list) + count_nodes(index) + count_nodes(value),
+
+        Expr::Data(_) | Expr::Literal(_) | Expr::Var(_) | Expr::Break | Expr::Pop => 0,
+    };
+
+    children + 1
+}
@@ -6,11 +6,12 @@ extern crate gag;
 
 use std::io::Read;
 
-mod hugorm;
+pub mod hugorm;
 use hugorm::lexer::*;
 use hugorm::source::*;
 use hugorm::parser::*;
 use hugorm::visitor::*;
+use hugorm::prelude::core;
 
 use zub::vm::*;
 
@@ -47,7 +48,7 @@ fn run(path: &str, content: &str) {
                     visitor.symtab.pop(); // gotta cachce root scope
 
                     fn print(heap: &mut Heap<Object>, args: &[Value]) -> Value {
-                        println!("{}", args[1].with_heap(heap));
+                        println!("{}", core::display(heap, &args[1]));
                         Value::nil()
                     }
 
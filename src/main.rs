@@ -13,7 +13,7 @@ use crate::hugorm::lexer::*;
 use crate::hugorm::source::*;
 use crate::hugorm::parser::*;
 use crate::hugorm::visitor::*;
-use crate::hugorm::prelude::math;
+use crate::hugorm::prelude::{core, math};
 
 use zub::vm::*;
 use zub::compiler::*;
@@ -60,12 +60,16 @@ fn run(path: &str, content: &str) {
             visitor.set_global("input", TypeNode::Func(0));
             visitor.set_global("len", TypeNode::Func(1));
 
+            let mut vm = VM::new();
+
+            core::include_core(&mut visitor, &mut vm);
+
             match visitor.visit(&ast) {
                 Ok(_) => {
                     visitor.symtab.pop(); // gotta cachce root scope
 
                     fn print(heap: &mut Heap<Object>, args: &[Value]) -> Value {
-                        println!("{}", args[1].with_heap(heap));
+                        println!("{}", core::display(heap, &args[1]));
                         Value::nil()
                     }
 
@@ -96,7 +100,6 @@ fn run(path: &str, content: &str) {
                         }
                     }
 
-                    let mut vm = VM::new();
                     vm.add_native("print", print, 1);
                     vm.add_native("len", len, 1);
 
@@ -165,7 +168,7 @@ fn repl() {
     let source = Source::from("<repl>", Vec::new());
 
     fn print(heap: &mut Heap<Object>, args: &[Value]) -> Value {
-        println!("{}", args[1].with_heap(heap));
+        println!("{}", core::display(heap, &args[1]));
         Value::nil()
     }
 
@@ -190,6 +193,8 @@ fn repl() {
     visitor.set_global("print", TypeNode::Func(1));
     visitor.set_global("len", TypeNode::Func(1));
 
+    core::include_core(&mut visitor, &mut vm);
+
     let mut last_len = 0usize;
 
     let caret_normal = format!("{}", ">> ".green());
@@ -263,8 +268,15 @@ fn repl() {
                 }
 
                 let mut parser = Parser::new(tokens, &source);
+                parser.set_trace_indents(debug);
+
+                let parsed = parser.parse();
+
+                if debug {
+                    print!("{}", parser.dump_indents())
+                }
 
-                match parser.parse() {
+                match parsed {
                     Ok(ast) => {
                         let mut repl_ast = Vec::new();
 
@@ -276,6 +288,7 @@ fn repl() {
                                     Statement::new(
                                         StatementNode::Declaration(
                                             "$".to_string(), // to capture print return no fucks
+                                            None,
                                             Some(
                                                 Expression::new(
                                                     ExpressionNode::Call(
@@ -287,7 +300,8 @@ fn repl() {
                                                                 pos.clone()
                                                             )
                                                         ),
-                                                        vec!(expr.clone())
+                                                        vec!(expr.clone()),
+                                                        Vec::new()
                                                     ),
                                                     pos.clone()
                                                 )
@@ -0,0 +1,29 @@
+// what a program prints is all that reaches stdout, checked on the binary since the tests capture their own
+
+use std::env;
+use std::fs;
+use std::process::Command;
+
+fn stdout(name: &str, code: &str) -> String {
+    let path = env::temp_dir().join(format!("hugorm-{}-{}.hug", name, std::process::id()));
+
+    fs::write(&path, code).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_hugorm")).arg(&path).output().unwrap();
+
+    fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn parsing_binary_expressions_prints_nothing() {
+    assert_eq!(stdout("binary", "let n = 1 + 2 * 3 - 4\nlet b = n > 2 and n < 10\n"), "");
+}
+
+#[test]
+fn compiling_anonymous_functions_prints_nothing() {
+    assert_eq!(stdout("anonymous", "print(map([1, 2], fun(x):\n    return x * 2\n))\n"), "[2, 4]\n");
+}
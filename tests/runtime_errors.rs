@@ -0,0 +1,78 @@
+// runtime errors end the program, so these run the binary on a file instead of a vm in the test process
+
+use std::env;
+use std::fs;
+use std::process::{Command, Output};
+
+fn run(name: &str, code: &str) -> Output {
+    let path = env::temp_dir().join(format!("hugorm-{}-{}.hug", name, std::process::id()));
+
+    fs::write(&path, code).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_hugorm")).arg(&path).output().unwrap();
+
+    fs::remove_file(&path).unwrap();
+
+    output
+}
+
+fn fails_with(name: &str, code: &str, message: &str) {
+    let output = run(name, code);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success(), "{} ran without failing", name);
+    assert!(stderr.contains(message), "{}", stderr);
+}
+
+#[test]
+fn optionally_indexing_a_bool_is_an_error() {
+    fails_with("optional-bool", "let flag = false\nprint(flag?.x)\n", "can't index `false`");
+}
+
+#[test]
+fn failed_checks_are_errors() {
+    fails_with("check", "let n = 1\ncheck n > 2\n", "check failed on line 2: check n > 2");
+}
+
+#[test]
+fn spreading_too_short_a_list_is_an_error() {
+    fails_with("short-spread", "fun f(a, b):\n    return a\nprint(f(...[1]))\n", "`f` takes 2 arguments, the list spread into it doesn't make up the difference");
+}
+
+#[test]
+fn numeric_builtins_on_non_numbers_are_errors() {
+    fails_with("abs", "fun f(x):\n    return abs(x)\nprint(f(\"x\"))\n", "`abs` takes numbers, not `x`");
+}
+
+#[test]
+fn slicing_out_of_bounds_is_an_error() {
+    fails_with("backwards-slice", "print([1, 2, 3, 4][3:1])\n", "can't slice 3..1 out of 4 elements");
+    fails_with("negative-slice", "let xs = [1, 2]\nprint(xs[-1:2])\n", "can't slice from a negative index, `-1`");
+}
+
+#[test]
+fn ranging_with_bad_bounds_is_an_error() {
+    fails_with("range-step", "fun f(s):\n    return range(0, 3, s)\nprint(f(0))\n", "can't range with a step of 0, it has to be at least 1");
+    fails_with("range-bound", "fun f(s):\n    return range(0, s)\nprint(f(1.5))\n", "can't range over `1.5`, bounds are integers");
+}
+
+#[test]
+fn list_natives_on_non_lists_are_errors() {
+    fails_with("zip", "fun f(x):\n    return zip(x, [1])\nprint(f(3))\n", "can't zip `3`, it's not a list");
+    fails_with("enumerate", "fun f(x):\n    return enumerate(x)\nprint(f(true))\n", "can't enumerate `true`, it's not a list");
+}
+
+#[test]
+fn spreading_a_non_dict_into_a_dict_is_an_error() {
+    fails_with("dict-spread", "fun f(x):\n    return {...x, a: 1}\nprint(f(3))\n", "can't spread `3`, it's not a dict");
+}
+
+#[test]
+fn looping_over_a_non_collection_is_an_error() {
+    fails_with("loop", "fun f(x):\n    for a in x:\n        print(a)\nf(3)\n", "can't loop over `3`, only over a list or a dict");
+}
+
+#[test]
+fn wrapping_arithmetic_on_non_integers_is_an_error() {
+    fails_with("wrapping", "fun f(x):\n    return x +% 1\nprint(f(1.5))\n", "can't do wrapping arithmetic on `1.5` and `1`, only on integers");
+}